@@ -0,0 +1,296 @@
+use std::{
+    io::Write,
+    path::{Component, Path, PathBuf},
+};
+
+use ame::{
+    client::native_client::{build_ame_client, AmeClient},
+    grpc::{
+        DataSetGraph, DataSetGraphRequest, DataSetId, ListDataSetsRequest, PreviewDataSetRequest,
+        ProjectCfg,
+    },
+    AmeServiceClientCfg,
+};
+use anyhow::{bail, Result};
+use clap::Subcommand;
+use colored::Colorize;
+use futures_util::StreamExt;
+use tonic::Request;
+
+use crate::{task::Table, CliConfiguration};
+
+/// Manage data sets
+#[derive(Subcommand)]
+pub enum DataSetCommand {
+    /// Print the data sets in a project and the data sets they depend on.
+    ///
+    /// Data sets are listed in the order they are generated, upstream data sets first.
+    Graph {
+        /// Name of the project, defaults to the project in the current directory.
+        project: Option<String>,
+    },
+
+    /// List the data sets present in the `AME` instance.
+    List {
+        /// Only list data sets in this project.
+        #[clap(long)]
+        project: Option<String>,
+    },
+
+    /// View the configuration, status and files of a data set.
+    View {
+        /// Name of the data set.
+        name: String,
+
+        /// Name of the project, defaults to the project in the current directory.
+        #[clap(long)]
+        project: Option<String>,
+    },
+
+    /// Print the first rows of the CSV, JSONL and Parquet files in a data set.
+    ///
+    /// At most five files are previewed, use `--file` to preview a specific file.
+    Preview {
+        /// Name of the data set.
+        name: String,
+
+        /// Name of the project, defaults to the project in the current directory.
+        #[clap(long)]
+        project: Option<String>,
+
+        /// Number of rows to print from each file.
+        #[clap(long, short)]
+        rows: Option<u32>,
+
+        /// Only preview this file, relative to the data set.
+        #[clap(long)]
+        file: Option<String>,
+    },
+
+    /// Download the files in a data set.
+    Pull {
+        /// Name of the data set.
+        name: String,
+
+        /// Name of the project, defaults to the project in the current directory.
+        #[clap(long)]
+        project: Option<String>,
+
+        /// Directory to download the data set to, defaults to the data set's name.
+        #[clap(long, short)]
+        out: Option<PathBuf>,
+    },
+}
+
+pub async fn exec_data_set_command(cfg: CliConfiguration, cmd: &DataSetCommand) -> Result<()> {
+    let mut client = build_ame_client(AmeServiceClientCfg {
+        disable_tls_cert_check: true,
+        endpoint: cfg.endpoint.parse().unwrap(),
+        id_token: cfg.id_token,
+    })
+    .await?;
+
+    match cmd {
+        DataSetCommand::Graph { project } => {
+            let project = match project {
+                Some(project) => project.clone(),
+                None => ProjectCfg::try_from_working_dir()?.name,
+            };
+
+            let graph = client
+                .get_data_set_graph(Request::new(DataSetGraphRequest { project }))
+                .await?
+                .into_inner();
+
+            print_graph(&graph)
+        }
+        DataSetCommand::List { project } => exec_data_set_list(client, project.clone()).await,
+        DataSetCommand::View { name, project } => {
+            let id = data_set_id(name, project)?;
+
+            let info = client.get_data_set(Request::new(id)).await?.into_inner();
+
+            println!("{}", serde_yaml::to_string(&info)?);
+
+            Ok(())
+        }
+        DataSetCommand::Preview {
+            name,
+            project,
+            rows,
+            file,
+        } => {
+            let preview = client
+                .preview_data_set(Request::new(PreviewDataSetRequest {
+                    id: Some(data_set_id(name, project)?),
+                    rows: *rows,
+                    file: file.clone(),
+                }))
+                .await?
+                .into_inner();
+
+            if preview.previews.is_empty() {
+                println!(
+                    "No CSV, JSONL or Parquet files found, the data set contains: {}",
+                    preview.files.join(", ")
+                );
+            }
+
+            for file in preview.previews {
+                println!("{}", file.path.bold());
+
+                for row in file.rows {
+                    println!("{row}");
+                }
+
+                println!();
+            }
+
+            Ok(())
+        }
+        DataSetCommand::Pull { name, project, out } => {
+            let out = out.clone().unwrap_or(PathBuf::from(name));
+            let id = data_set_id(name, project)?;
+
+            exec_data_set_pull(client, id, &out).await
+        }
+    }
+}
+
+fn data_set_id(name: &str, project: &Option<String>) -> Result<DataSetId> {
+    let project = match project {
+        Some(project) => project.clone(),
+        None => ProjectCfg::try_from_working_dir()?.name,
+    };
+
+    Ok(DataSetId {
+        project,
+        name: name.to_string(),
+    })
+}
+
+async fn exec_data_set_list(mut client: AmeClient, project: Option<String>) -> Result<()> {
+    let data_sets = client
+        .list_data_sets(Request::new(ListDataSetsRequest { project }))
+        .await?
+        .into_inner()
+        .data_sets;
+
+    let mut table = Table::new(
+        vec![
+            "Name".to_string(),
+            "Project".to_string(),
+            "Status".to_string(),
+            "Size".to_string(),
+        ],
+        data_sets
+            .into_iter()
+            .map(|ds| {
+                vec![
+                    ds.name,
+                    ds.project,
+                    ds.phase,
+                    ds.size_bytes
+                        .map(|s| s.to_string())
+                        .unwrap_or("Unknown".to_string()),
+                ]
+            })
+            .collect(),
+    );
+
+    table.sort(true);
+
+    println!("{}", table.try_string_colored()?);
+
+    Ok(())
+}
+
+async fn exec_data_set_pull(mut client: AmeClient, id: DataSetId, out: &Path) -> Result<()> {
+    let mut stream = client.pull_data_set(Request::new(id)).await?.into_inner();
+    let mut pulled: Option<PathBuf> = None;
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let path = local_path(out, &chunk.path)?;
+
+        // Files are sent in consecutive chunks, the first one starting at offset 0.
+        let mut file = if chunk.offset == 0 {
+            if let Some(pulled) = pulled.replace(path.clone()) {
+                println!("{} {}", "Pulled".green().bold(), pulled.display());
+            }
+
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::File::create(&path)?
+        } else {
+            std::fs::OpenOptions::new().append(true).open(&path)?
+        };
+
+        file.write_all(&chunk.contents)?;
+    }
+
+    if let Some(pulled) = pulled {
+        println!("{} {}", "Pulled".green().bold(), pulled.display());
+    }
+
+    Ok(())
+}
+
+/// Returns where a pulled file is written, paths sent by the server must stay inside `out`.
+fn local_path(out: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+
+    if path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!(
+            "refusing to write {path:?} as it is not a path inside {}",
+            out.display()
+        );
+    }
+
+    Ok(out.join(relative))
+}
+
+fn print_graph(graph: &DataSetGraph) -> Result<()> {
+    let order = match graph.generation_order() {
+        Ok(order) => order,
+        Err(e) => {
+            println!("{} {}", "Invalid".red().bold(), e);
+            graph.nodes.iter().map(|n| n.name.clone()).collect()
+        }
+    };
+
+    for name in order {
+        println!("{}", name.bold());
+
+        for dependency in graph.dependencies(&name).unwrap_or_default() {
+            println!("  <- {dependency}");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pulled_files_stay_inside_the_output_directory() {
+        let out = Path::new("data");
+
+        assert_eq!(
+            local_path(out, "train/part-0.csv").unwrap(),
+            PathBuf::from("data/train/part-0.csv")
+        );
+        assert!(local_path(out, "/etc/passwd").is_err());
+        assert!(local_path(out, "../outside.csv").is_err());
+        assert!(local_path(out, "train/../../outside.csv").is_err());
+        assert!(local_path(out, "").is_err());
+    }
+}
@@ -58,6 +58,9 @@ pub enum Error {
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+pub mod data_set;
+pub mod lsp;
+pub mod model;
 pub mod project_cmd;
 pub mod projectsrc;
 pub mod secrets;
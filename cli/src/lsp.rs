@@ -0,0 +1,530 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::Mutex,
+};
+
+use ame::{
+    grpc::{Empty, ProjectCfg},
+    validation,
+};
+use tonic::Request;
+use tower_lsp::{
+    jsonrpc::Result,
+    lsp_types::{
+        CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams,
+        CompletionResponse, Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams,
+        DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams, Hover,
+        HoverContents, HoverParams, HoverProviderCapability, InitializeParams, InitializeResult,
+        MarkupContent, MarkupKind, MessageType, Position, Range, ServerCapabilities, ServerInfo,
+        TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+    },
+    Client, LanguageServer, LspService, Server,
+};
+
+use crate::CliConfiguration;
+
+/// Files a project is read from, other files are only diagnosed through the project including
+/// them.
+static AME_FILES: [&str; 2] = ["ame.yaml", "ame.yml"];
+
+/// Documentation shown when hovering fields and executor tags.
+static FIELD_DOCS: &[(&str, &str)] = &[
+    ("name", "Name of the project, task, template, data set or model."),
+    ("tasks", "Tasks in the project, each one runs a command with an executor."),
+    ("templates", "Tasks other tasks can start from with `fromTemplate`."),
+    ("dataSets", "Data sets produced by a task and stored at `path`. In a task, the data sets loaded before it runs."),
+    ("models", "Models trained, validated, deployed and monitored by the project."),
+    ("defaults", "Task configuration every task in the project starts from."),
+    ("include", "Paths or globs of files whose tasks, templates, data sets and models are merged into the project."),
+    ("vars", "Variables referenced from strings with `${{ vars.name }}`."),
+    ("grants", "Other projects and namespaces allowed to use data sets of this project."),
+    ("enableTriggers", "Whether the triggers of tasks in the project are active."),
+    ("taskRef", "Runs the task with this name, from another project if `project` is set."),
+    ("fromTemplate", "Starts the task from the template with this name, from another project if `project` is set."),
+    ("executor", "How the task is run, one of `!poetry`, `!pip`, `!pipEnv`, `!mlflow` or `!custom`."),
+    ("resources", "Resources requested for the task, e.g. `cpu: 2`, `memory: 4Gi` or `nvidia.com/gpu: 1`."),
+    ("secrets", "Secrets injected into the task as environment variables."),
+    ("env", "Environment variables set for the task."),
+    ("triggers", "Runs the task on a cron schedule, when data sets are updated or when the repository is pushed to."),
+    ("artifactCfg", "Files saved to object storage after the task has run."),
+    ("key", "Key of the secret in the AME secret store, see `ame secret list`."),
+    ("injectAs", "Environment variable the secret is injected as."),
+    ("poetry", "Installs dependencies with Poetry before running `command`."),
+    ("pip", "Installs `requirements.txt` with pip before running `command`."),
+    ("pipEnv", "Installs dependencies with Pipenv before running `command`."),
+    ("mlflow", "Runs the task as an MLflow project."),
+    ("custom", "Runs `command` without installing any dependencies."),
+];
+
+/// Kinds of names offered as completions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    Task,
+    Template,
+    DataSet,
+    Secret,
+}
+
+/// Language server for `ame.yaml` files, offering diagnostics, completions and hover docs.
+pub async fn run_language_server() {
+    let (service, socket) = LspService::new(|client| Backend {
+        client,
+        documents: Mutex::new(BTreeMap::new()),
+        published: Mutex::new(BTreeMap::new()),
+        secrets: tokio::sync::Mutex::new(None),
+    });
+
+    Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
+        .serve(service)
+        .await;
+}
+
+#[derive(Debug, Clone)]
+struct Document {
+    text: String,
+
+    /// The project of the last version of the document which could be parsed, names are completed
+    /// from it while the document is being edited.
+    project: Option<ProjectCfg>,
+}
+
+struct Backend {
+    client: Client,
+    documents: Mutex<BTreeMap<Url, Document>>,
+
+    /// Files diagnostics were published for by each project file, so they can be cleared.
+    published: Mutex<BTreeMap<Url, BTreeSet<Url>>>,
+
+    /// Secret keys in the secret store, fetched the first time they are completed.
+    secrets: tokio::sync::Mutex<Option<Vec<String>>>,
+}
+
+impl Backend {
+    async fn update(&self, uri: Url, text: String) {
+        let diagnosed = diagnose(&uri, &text);
+
+        {
+            let mut documents = self.documents.lock().unwrap();
+            let previous = documents.remove(&uri).and_then(|d| d.project);
+            let project = diagnosed
+                .as_ref()
+                .and_then(|(project, _)| project.clone())
+                .or(previous);
+
+            documents.insert(uri.clone(), Document { text, project });
+        }
+
+        if let Some((_, diagnostics)) = diagnosed {
+            self.publish(uri, diagnostics).await;
+        }
+    }
+
+    async fn publish(&self, uri: Url, mut diagnostics: BTreeMap<Url, Vec<Diagnostic>>) {
+        let stale = {
+            let mut published = self.published.lock().unwrap();
+            let files: BTreeSet<Url> = diagnostics.keys().cloned().collect();
+
+            published.insert(uri, files).unwrap_or_default()
+        };
+
+        for file in stale {
+            diagnostics.entry(file).or_default();
+        }
+
+        for (file, diagnostics) in diagnostics {
+            self.client
+                .publish_diagnostics(file, diagnostics, None)
+                .await;
+        }
+    }
+
+    /// Diagnoses every open project file again, e.g. after a file they include was saved.
+    async fn diagnose_open_projects(&self) {
+        let documents = self.documents.lock().unwrap().clone();
+
+        for (uri, document) in documents {
+            self.update(uri, document.text).await;
+        }
+    }
+
+    fn text(&self, uri: &Url) -> Option<String> {
+        self.documents
+            .lock()
+            .unwrap()
+            .get(uri)
+            .map(|d| d.text.clone())
+    }
+
+    /// Returns the project a document belongs to, included files are looked up through the
+    /// closest project file in their directory or its parents.
+    fn project(&self, uri: &Url) -> Option<ProjectCfg> {
+        if let Some(project) = self
+            .documents
+            .lock()
+            .unwrap()
+            .get(uri)
+            .and_then(|d| d.project.clone())
+        {
+            return Some(project);
+        }
+
+        let path = uri.to_file_path().ok()?;
+
+        path.ancestors().skip(1).find_map(|dir| {
+            AME_FILES.iter().find_map(|file| {
+                let source = std::fs::read_to_string(dir.join(file)).ok()?;
+                ProjectCfg::diagnose(dir, file, &source).0
+            })
+        })
+    }
+
+    async fn secret_keys(&self) -> Vec<String> {
+        let mut secrets = self.secrets.lock().await;
+
+        if secrets.is_none() {
+            match fetch_secret_keys().await {
+                Ok(keys) => *secrets = Some(keys),
+                Err(e) => {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("could not list secrets: {e}"))
+                        .await;
+                }
+            }
+        }
+
+        secrets.clone().unwrap_or_default()
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+        Ok(InitializeResult {
+            capabilities: ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::FULL,
+                )),
+                completion_provider: Some(CompletionOptions::default()),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: "ame".to_string(),
+                version: None,
+            }),
+        })
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        self.update(params.text_document.uri, params.text_document.text)
+            .await;
+    }
+
+    async fn did_change(&self, mut params: DidChangeTextDocumentParams) {
+        // Documents are synced in full, so the last change holds the whole document.
+        if let Some(change) = params.content_changes.pop() {
+            self.update(params.text_document.uri, change.text).await;
+        }
+    }
+
+    async fn did_save(&self, _: DidSaveTextDocumentParams) {
+        self.diagnose_open_projects().await;
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.documents
+            .lock()
+            .unwrap()
+            .remove(&params.text_document.uri);
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let Some(text) = self.text(&uri) else {
+            return Ok(None);
+        };
+
+        let Some(kind) =
+            completion_kind(&text, position.line as usize, position.character as usize)
+        else {
+            return Ok(None);
+        };
+
+        let names = match kind {
+            NameKind::Secret => self.secret_keys().await,
+            kind => self
+                .project(&uri)
+                .map(|project| project_names(&project, kind))
+                .unwrap_or_default(),
+        };
+
+        let items = names
+            .into_iter()
+            .map(|name| CompletionItem {
+                label: name,
+                kind: Some(CompletionItemKind::REFERENCE),
+                detail: Some(kind.to_string()),
+                ..CompletionItem::default()
+            })
+            .collect();
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some(text) = self.text(&uri) else {
+            return Ok(None);
+        };
+
+        Ok(
+            field_docs(&text, position.line as usize, position.character as usize).map(|docs| {
+                Hover {
+                    contents: HoverContents::Markup(MarkupContent {
+                        kind: MarkupKind::Markdown,
+                        value: docs,
+                    }),
+                    range: None,
+                }
+            }),
+        )
+    }
+}
+
+impl std::fmt::Display for NameKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let output = match self {
+            NameKind::Task => "task",
+            NameKind::Template => "template",
+            NameKind::DataSet => "data set",
+            NameKind::Secret => "secret",
+        };
+
+        write!(f, "{output}")
+    }
+}
+
+async fn fetch_secret_keys() -> anyhow::Result<Vec<String>> {
+    let mut client = CliConfiguration::gather()?.ame_client().await?;
+    let secrets = client.list_secrets(Request::new(Empty {})).await?;
+
+    Ok(secrets
+        .into_inner()
+        .secrets
+        .into_iter()
+        .map(|s| s.key)
+        .collect())
+}
+
+/// Diagnoses a project file, returning the project if it could be parsed and the diagnostics
+/// for it and every file it includes. Documents which are not project files are skipped.
+fn diagnose(uri: &Url, text: &str) -> Option<(Option<ProjectCfg>, BTreeMap<Url, Vec<Diagnostic>>)> {
+    let path = uri.to_file_path().ok()?;
+    let file = path.file_name()?.to_str()?;
+
+    if !AME_FILES.contains(&file) {
+        return None;
+    }
+
+    let root = path.parent().unwrap_or(Path::new("/"));
+    let (project, diagnostics) = ProjectCfg::diagnose(root, file, text);
+
+    let mut files = BTreeMap::from([(uri.clone(), vec![])]);
+
+    for diagnostic in diagnostics {
+        let Ok(file) = Url::from_file_path(root.join(&diagnostic.file)) else {
+            continue;
+        };
+
+        files
+            .entry(file)
+            .or_default()
+            .push(lsp_diagnostic(&diagnostic));
+    }
+
+    Some((project, files))
+}
+
+fn lsp_diagnostic(diagnostic: &validation::Diagnostic) -> Diagnostic {
+    let position = Position::new(
+        diagnostic.line.saturating_sub(1) as u32,
+        diagnostic.column.saturating_sub(1) as u32,
+    );
+
+    Diagnostic {
+        range: Range::new(position, position),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("ame".to_string()),
+        message: diagnostic.message.clone(),
+        ..Diagnostic::default()
+    }
+}
+
+fn project_names(project: &ProjectCfg, kind: NameKind) -> Vec<String> {
+    match kind {
+        NameKind::Task => project.task_names(),
+        NameKind::Template => project
+            .templates
+            .iter()
+            .filter_map(|t| t.name.clone())
+            .collect(),
+        NameKind::DataSet => project.data_sets.iter().map(|ds| ds.name.clone()).collect(),
+        NameKind::Secret => vec![],
+    }
+}
+
+/// Splits a line into the column of its list item dash, if it is a list item, the column its
+/// content starts at and the content.
+fn split_line(line: &str) -> (Option<usize>, usize, &str) {
+    let content = line.trim_start();
+    let indentation = line.len() - content.len();
+
+    if content == "-" || content.starts_with("- ") {
+        let item = content[1..].trim_start();
+        (Some(indentation), line.len() - item.len(), item)
+    } else {
+        (None, indentation, content)
+    }
+}
+
+/// Finds the key of the mapping holding content at `column` by searching `lines` backwards.
+/// With `inclusive` a key at `column` also counts, as sequences may be indented as far as their
+/// key.
+fn parent_key<'a>(lines: &[&'a str], column: usize, inclusive: bool) -> Option<(&'a str, usize)> {
+    for line in lines.iter().rev() {
+        let trimmed = line.trim();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let (dash, key_column, content) = split_line(line);
+
+        if key_column < column || (inclusive && dash.is_none() && key_column == column) {
+            return content
+                .trim_end()
+                .strip_suffix(':')
+                .map(|key| (key.trim(), key_column));
+        }
+    }
+
+    None
+}
+
+/// Returns the kind of names to complete at the position, if the position is the value of a
+/// reference to a task, template, data set or secret.
+pub fn completion_kind(text: &str, line: usize, character: usize) -> Option<NameKind> {
+    let lines: Vec<&str> = text.lines().collect();
+    let current = lines.get(line)?;
+    let prefix = current.get(..character).unwrap_or(current);
+
+    let (dash, key_column, content) = split_line(prefix);
+
+    match content.split_once(':') {
+        Some((key, _)) => {
+            if matches!(key, "inputDataSet" | "outputDataSet" | "referenceDataSet") {
+                return Some(NameKind::DataSet);
+            }
+
+            let (parent, _) = parent_key(&lines[..line], key_column, false)?;
+
+            match (parent, key) {
+                ("taskRef", "name") => Some(NameKind::Task),
+                ("fromTemplate", "name") => Some(NameKind::Template),
+                ("ame", "key") => Some(NameKind::Secret),
+                _ => None,
+            }
+        }
+        None => {
+            let (parent, parent_column) = parent_key(&lines[..line], dash?, true)?;
+
+            // Data sets at the top of a file are definitions rather than references.
+            (matches!(parent, "dataSets" | "onDataSetUpdate") && parent_column > 0)
+                .then_some(NameKind::DataSet)
+        }
+    }
+}
+
+/// Returns the documentation of the field or executor tag at the position.
+pub fn field_docs(text: &str, line: usize, character: usize) -> Option<String> {
+    let current = text.lines().nth(line)?;
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '!';
+
+    let start = current
+        .get(..character)?
+        .rfind(|c| !is_word(c))
+        .map_or(0, |i| i + 1);
+    let end = current[start..]
+        .find(|c| !is_word(c))
+        .map_or(current.len(), |i| start + i);
+
+    let word = &current[start..end];
+
+    let field = match word.strip_prefix('!') {
+        Some(tag) => tag,
+        None if current[end..].starts_with(':') => word,
+        None => return None,
+    };
+
+    FIELD_DOCS
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(name, docs)| format!("**{name}**\n\n{docs}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static PROJECT: &str = r#"name: myproject
+dataSets:
+  - name: features
+    path: data
+tasks:
+  - name: train
+    taskRef:
+      name:
+    fromTemplate:
+      name: gpu
+    dataSets:
+      -
+    secrets:
+      - ame:
+          key:
+    executor:
+      !poetry
+      command: python train.py
+"#;
+
+    #[test]
+    fn completes_references() {
+        assert_eq!(completion_kind(PROJECT, 7, 12), Some(NameKind::Task));
+        assert_eq!(completion_kind(PROJECT, 9, 12), Some(NameKind::Template));
+        assert_eq!(completion_kind(PROJECT, 11, 8), Some(NameKind::DataSet));
+        assert_eq!(completion_kind(PROJECT, 14, 15), Some(NameKind::Secret));
+
+        assert_eq!(completion_kind(PROJECT, 0, 6), None);
+        assert_eq!(completion_kind(PROJECT, 2, 4), None);
+        assert_eq!(completion_kind(PROJECT, 5, 10), None);
+    }
+
+    #[test]
+    fn documents_fields_and_executors() {
+        assert!(field_docs(PROJECT, 6, 6)
+            .unwrap()
+            .starts_with("**taskRef**"));
+        assert!(field_docs(PROJECT, 16, 8)
+            .unwrap()
+            .starts_with("**poetry**"));
+        assert_eq!(field_docs(PROJECT, 17, 20), None);
+    }
+}
@@ -1,10 +1,14 @@
 use ame::{
     client::{auth::browser_login, native_client::build_ame_client},
     grpc::{ProjectCfg, TaskIdentifier, TrainRequest},
+    validation::project_schema,
     AmeServiceClientCfg,
 };
 use clap::{Parser, Subcommand};
 use cli::{
+    data_set::{exec_data_set_command, DataSetCommand},
+    lsp::run_language_server,
+    model::{exec_model_command, ModelCommand},
     project::Project,
     project_cmd::{exec_project_command, ProjectCommands},
     projectsrc::ProjectSrcCommands,
@@ -47,7 +51,20 @@ enum Commands {
     Task(TaskCommand),
     #[command(subcommand)]
     Project(ProjectCommands),
+    #[command(subcommand)]
+    Dataset(DataSetCommand),
+    #[command(subcommand)]
+    Model(ModelCommand),
     Validate,
+
+    /// Print the JSON Schema of `ame.yaml` files.
+    Schema,
+
+    /// Run a language server for `ame.yaml` files over stdio.
+    ///
+    /// It offers diagnostics, hover docs and completion of task, template, data set and secret
+    /// names.
+    Lsp,
 }
 
 #[tokio::main]
@@ -128,8 +145,18 @@ async fn main() -> Result<()> {
         Commands::Secret(cmd) => exec_secret_command(config, cmd).await,
         Commands::Task(cmd) => exec_task_command(config, cmd).await,
         Commands::Project(cmd) => exec_project_command(config, cmd).await,
+        Commands::Dataset(cmd) => exec_data_set_command(config, cmd).await,
+        Commands::Model(cmd) => exec_model_command(config, cmd).await,
         Commands::Validate => {
-            ProjectCfg::try_from_working_dir().unwrap();
+            ProjectCfg::try_from_working_dir()?;
+            Ok(())
+        }
+        Commands::Schema => {
+            println!("{}", serde_json::to_string_pretty(&project_schema())?);
+            Ok(())
+        }
+        Commands::Lsp => {
+            run_language_server().await;
             Ok(())
         }
     }?;
@@ -0,0 +1,164 @@
+use ame::{
+    client::native_client::build_ame_client,
+    grpc::{
+        ModelStatus, ProjectCfg, PromoteModelRolloutRequest, PromoteModelVersionRequest,
+        RegisterModelVersionRequest, RollbackModelRequest,
+    },
+    AmeServiceClientCfg,
+};
+use anyhow::Result;
+use clap::Subcommand;
+use colored::Colorize;
+use tonic::Request;
+
+use crate::CliConfiguration;
+
+/// Manage deployed Models
+///
+/// Note: These commands assume that they are run from the root of an `AME` project directory.
+#[derive(Subcommand)]
+pub enum ModelCommand {
+    /// Pin the deployed version of a Model.
+    ///
+    /// The pinned version is deployed even when newer validated versions exist. Omitting the
+    /// version removes the pin, so the latest validated version is deployed again. Run
+    /// `ame model promote-rollout <name>` to promote the candidate of a blue/green or canary
+    /// rollout instead.
+    Promote {
+        /// Name of the Model.
+        name: String,
+
+        /// Version to deploy.
+        version: Option<String>,
+    },
+
+    /// Deploy the version of a Model which was deployed before the current one.
+    ///
+    /// The previous version is pinned, run `ame model promote <name>` to follow the latest
+    /// validated version again.
+    Rollback {
+        /// Name of the Model.
+        name: String,
+    },
+
+    /// Promote the candidate version of a Model's rollout.
+    ///
+    /// A blue/green candidate receives all traffic once promoted, a canary candidate skips its
+    /// remaining steps. Use `ame model promote` to pin a version instead.
+    PromoteRollout {
+        /// Name of the Model.
+        name: String,
+    },
+
+    /// Register a version of a Model with the object storage model registry.
+    ///
+    /// The artifacts are expected under `models/<name>/<version>/` in the AME bucket unless a
+    /// source is given. Versions of Models tracked by MLflow are registered through MLflow.
+    Register {
+        /// Name of the Model.
+        name: String,
+
+        /// Version to register.
+        version: String,
+
+        /// URI of the version's artifacts.
+        #[arg(long)]
+        source: Option<String>,
+
+        /// Run which produced the version.
+        #[arg(long)]
+        run_id: Option<String>,
+    },
+}
+
+pub async fn exec_model_command(cfg: CliConfiguration, cmd: &ModelCommand) -> Result<()> {
+    let mut client = build_ame_client(AmeServiceClientCfg {
+        disable_tls_cert_check: true,
+        endpoint: cfg.endpoint.parse().unwrap(),
+        id_token: cfg.id_token,
+    })
+    .await?;
+
+    let project = ProjectCfg::try_from_working_dir()?;
+
+    let (name, status) = match cmd {
+        ModelCommand::Promote { name, version } => {
+            let status = client
+                .promote_model_version(Request::new(PromoteModelVersionRequest {
+                    project: project.name,
+                    model: name.clone(),
+                    version: version.clone(),
+                }))
+                .await?
+                .into_inner();
+
+            (name, status)
+        }
+        ModelCommand::Rollback { name } => {
+            let status = client
+                .rollback_model(Request::new(RollbackModelRequest {
+                    project: project.name,
+                    model: name.clone(),
+                }))
+                .await?
+                .into_inner();
+
+            (name, status)
+        }
+        ModelCommand::PromoteRollout { name } => {
+            let status = client
+                .promote_model_rollout(Request::new(PromoteModelRolloutRequest {
+                    project: project.name,
+                    model: name.clone(),
+                }))
+                .await?
+                .into_inner();
+
+            if let Some(candidate) = status.rollout.and_then(|r| r.candidate_source) {
+                println!("{} {name} to {candidate}", "Promoting".green().bold());
+            }
+
+            return Ok(());
+        }
+        ModelCommand::Register {
+            name,
+            version,
+            source,
+            run_id,
+        } => {
+            let registered = client
+                .register_model_version(Request::new(RegisterModelVersionRequest {
+                    project: project.name,
+                    model: name.clone(),
+                    version: version.clone(),
+                    source: source.clone(),
+                    run_id: run_id.clone(),
+                }))
+                .await?
+                .into_inner();
+
+            println!(
+                "{} {name} version {} from {}",
+                "Registered".green().bold(),
+                registered.version,
+                registered.source
+            );
+
+            return Ok(());
+        }
+    };
+
+    print_pin(name, &status);
+
+    Ok(())
+}
+
+fn print_pin(name: &str, status: &ModelStatus) {
+    match status.pinned_version {
+        Some(ref version) => println!("{} {name} to version {version}", "Pinned".green().bold()),
+        None => println!(
+            "{} {name}, the latest validated version will be deployed",
+            "Unpinned".green().bold()
+        ),
+    }
+}
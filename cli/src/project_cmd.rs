@@ -1,16 +1,17 @@
 use ame::{
-    client::native_client::build_ame_client,
+    client::native_client::{build_ame_client, AmeClient},
     grpc::{
-        project_file_chunk::Messages, CreateProjectRequest, FileChunk, ProjectCfg,
-        ProjectFileChunk, ProjectFileIdentifier,
+        project_file_chunk::Messages, Condition, CreateProjectRequest, FileChunk, ProjectCfg,
+        ProjectFileChunk, ProjectFileIdentifier, ProjectId,
     },
     AmeServiceClientCfg,
 };
 use clap::Subcommand;
 
 use tokio::{fs::File, io::AsyncReadExt};
+use tonic::Request;
 
-use crate::CliConfiguration;
+use crate::{task::Table, CliConfiguration};
 use anyhow::Result;
 
 #[derive(Subcommand)]
@@ -18,8 +19,20 @@ pub enum ProjectCommands {
     Push {
         #[clap(short, long)]
         triggers: bool,
+
+        /// What happens to the project's objects when it is deleted: Cascade, Purge, RetainData
+        /// or RetainDeployments. Only Purge deletes the project's objects in storage.
+        #[clap(long)]
+        deletion_policy: Option<String>,
     },
     Delete,
+
+    /// Show the conditions of a project and its models, data sets and tasks.
+    Status {
+        /// Name of the project, defaults to the project in the working directory.
+        #[clap(short, long)]
+        project: Option<String>,
+    },
 }
 
 pub async fn exec_project_command(cfg: CliConfiguration, cmd: &ProjectCommands) -> Result<()> {
@@ -30,10 +43,23 @@ pub async fn exec_project_command(cfg: CliConfiguration, cmd: &ProjectCommands)
     })
     .await?;
 
-    let triggers = if let ProjectCommands::Push { triggers } = cmd {
-        *triggers
+    if let ProjectCommands::Status { project } = cmd {
+        let name = match project {
+            Some(project) => project.clone(),
+            None => ProjectCfg::try_from_working_dir()?.name,
+        };
+
+        return exec_project_status(client, name).await;
+    }
+
+    let (triggers, deletion_policy) = if let ProjectCommands::Push {
+        triggers,
+        deletion_policy,
+    } = cmd
+    {
+        (*triggers, deletion_policy.clone())
     } else {
-        false
+        (false, None)
     };
 
     let project = ProjectCfg::try_from_working_dir()?;
@@ -42,6 +68,7 @@ pub async fn exec_project_command(cfg: CliConfiguration, cmd: &ProjectCommands)
         .create_project(CreateProjectRequest {
             cfg: Some(project),
             enable_triggers: Some(triggers),
+            deletion_policy,
         })
         .await?
         .into_inner();
@@ -111,3 +138,65 @@ pub async fn exec_project_command(cfg: CliConfiguration, cmd: &ProjectCommands)
 
     Ok(())
 }
+
+async fn exec_project_status(mut client: AmeClient, name: String) -> Result<()> {
+    let report = client
+        .get_project_status(Request::new(ProjectId { name }))
+        .await?
+        .into_inner();
+
+    let status = report.status.unwrap_or_default();
+
+    let mut rows = condition_rows(&format!("project/{}", report.project), &status.conditions);
+
+    for (model, model_status) in status.models.iter() {
+        rows.extend(condition_rows(
+            &format!("model/{model}"),
+            &model_status.conditions,
+        ));
+    }
+
+    for data_set in report.data_sets.iter() {
+        rows.extend(condition_rows(
+            &format!("dataset/{}", data_set.name),
+            &data_set.conditions,
+        ));
+    }
+
+    for task in report.tasks.iter() {
+        rows.extend(condition_rows(
+            &format!("task/{}", task.name),
+            &task.conditions,
+        ));
+    }
+
+    let table = Table::new(
+        vec![
+            "Object".to_string(),
+            "Condition".to_string(),
+            "Status".to_string(),
+            "Reason".to_string(),
+            "Message".to_string(),
+        ],
+        rows,
+    );
+
+    println!("{}", table.try_string_colored()?);
+
+    Ok(())
+}
+
+fn condition_rows(object: &str, conditions: &[Condition]) -> Vec<Vec<String>> {
+    conditions
+        .iter()
+        .map(|condition| {
+            vec![
+                object.to_string(),
+                condition.r#type.clone(),
+                condition.status.clone(),
+                condition.reason.clone().unwrap_or_default(),
+                condition.message.clone().unwrap_or_default(),
+            ]
+        })
+        .collect()
+}
@@ -36,6 +36,15 @@ pub enum ProjectSrcCommands {
         /// A Git user name that will work with the secret.
         #[arg(short, long)]
         user: Option<String>,
+
+        /// Only create projects for `ame.yaml` files in directories matching this glob, can be
+        /// repeated. All directories are included by default.
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// Skip `ame.yaml` files in directories matching this glob, can be repeated.
+        #[arg(long)]
+        exclude: Vec<String>,
     },
 
     /// Delete a project source.
@@ -62,6 +71,15 @@ pub enum ProjectSrcCommands {
         /// A Git user name that will work with the secret.
         #[arg(short, long)]
         user: Option<String>,
+
+        /// Only create projects for `ame.yaml` files in directories matching this glob, can be
+        /// repeated. All directories are included by default.
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// Skip `ame.yaml` files in directories matching this glob, can be repeated.
+        #[arg(long)]
+        exclude: Vec<String>,
     },
 
     /// List all Project Sources
@@ -117,6 +135,8 @@ impl ProjectSrcCommands {
                 repository,
                 secret,
                 user,
+                include,
+                exclude,
             } => {
                 let id = client
                     .create_project_src(Request::new(ProjectSourceCfg {
@@ -125,6 +145,8 @@ impl ProjectSrcCommands {
                             sync_interval: Some("10s".to_string()),
                             secret: secret.clone(),
                             username: user.clone(),
+                            include: include.clone(),
+                            exclude: exclude.clone(),
                         }),
                     }))
                     .await?
@@ -197,6 +219,8 @@ impl ProjectSrcCommands {
                 repository,
                 secret,
                 user,
+                include,
+                exclude,
             } => {
                 let id = client
                     .get_project_src_id(Request::new(ProjectSrcIdRequest {
@@ -214,6 +238,8 @@ impl ProjectSrcCommands {
                                 sync_interval: Some("10s".to_string()),
                                 secret: secret.clone(),
                                 username: user.clone(),
+                                include: include.clone(),
+                                exclude: exclude.clone(),
                             }),
                         }),
                     }))
@@ -3,8 +3,8 @@ use std::cmp::max;
 use ame::{
     client::native_client::{build_ame_client, AmeClient},
     grpc::{
-        CreateProjectRequest, ListTasksRequest, ProjectCfg, RunTaskRequest, TaskIdentifier,
-        TaskLogRequest,
+        BackfillTaskRequest, CreateProjectRequest, ListTasksRequest, ProjectCfg, RunTaskRequest,
+        TaskIdentifier, TaskLogRequest,
     },
     AmeServiceClientCfg,
 };
@@ -78,6 +78,27 @@ pub enum TaskCommand {
 
     /// View the configuration for a Task
     View { name: Option<String> },
+
+    /// Run a scheduled Task once for every schedule instant between two dates.
+    ///
+    /// Each run receives the instant it stands in for through the `AME_LOGICAL_TIME`
+    /// environment variable. Dates are given as RFC 3339 timestamps or as `YYYY-MM-DD`.
+    Backfill {
+        /// Name of the scheduled Task.
+        name: String,
+
+        /// First instant to include.
+        #[clap(long)]
+        from: String,
+
+        /// Last instant to include.
+        #[clap(long)]
+        to: String,
+
+        /// Maximum number of runs executing at the same time.
+        #[clap(long, default_value_t = 1)]
+        parallelism: u32,
+    },
 }
 
 pub async fn select_task(client: &mut AmeClient) -> Result<String> {
@@ -171,7 +192,7 @@ async fn exec_task_rm(
     return Ok(());
 }
 
-struct Table {
+pub(crate) struct Table {
     rows: Vec<Vec<String>>,
     sort: bool,
 }
@@ -187,7 +208,7 @@ impl Table {
         }
     }
 
-    fn sort(&mut self, sort: bool) -> &mut Self {
+    pub fn sort(&mut self, sort: bool) -> &mut Self {
         self.sort = sort;
         self
     }
@@ -230,20 +251,51 @@ impl Table {
         }
 
         let rows: String = rows.iter().fold("\n".to_string(), |acc, row| {
-            acc + &row
+            let row: String = row
                 .iter()
                 .zip(widths.clone())
                 .map(|(v, width)| {
                     format!("{}{} ", v, vec![" "; width - v.chars().count()].join(""))
                 })
-                .collect::<String>()
-                + "\n"
+                .collect();
+
+            format!("{acc}{row}\n")
         });
 
-        Ok(headers + &rows)
+        Ok(format!("{headers}{rows}"))
     }
 }
 
+async fn exec_task_backfill(
+    mut client: AmeClient,
+    name: &str,
+    from: &str,
+    to: &str,
+    parallelism: u32,
+) -> Result<()> {
+    let project = ProjectCfg::try_from_working_dir()?;
+
+    let runs = client
+        .backfill_task(Request::new(BackfillTaskRequest {
+            project: project.name,
+            task: name.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            parallelism: Some(parallelism),
+        }))
+        .await?
+        .into_inner()
+        .tasks;
+
+    for run in runs.iter() {
+        println!("{} {run}", "Created".green().bold());
+    }
+
+    println!("backfilling {} runs of {name}", runs.len());
+
+    Ok(())
+}
+
 async fn exec_task_view(mut client: AmeClient, name: Option<String>) -> Result<()> {
     let task_name = if let Some(name) = name {
         name
@@ -319,6 +371,14 @@ pub async fn exec_task_command(cfg: CliConfiguration, cmd: &TaskCommand) -> Resu
         TaskCommand::View { name } => {
             return exec_task_view(client, name.to_owned()).await;
         }
+        TaskCommand::Backfill {
+            name,
+            from,
+            to,
+            parallelism,
+        } => {
+            return exec_task_backfill(client, name, from, to, *parallelism).await;
+        }
         _ => (),
     };
 
@@ -361,6 +421,7 @@ pub async fn exec_task_command(cfg: CliConfiguration, cmd: &TaskCommand) -> Resu
         .create_project(CreateProjectRequest {
             cfg: Some(project),
             enable_triggers: Some(false),
+            deletion_policy: None,
         })
         .await?
         .into_inner();
@@ -73,6 +73,21 @@ fn ame_file_does_not_exist() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn ame_schema_describes_project_files() -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::cargo_bin("ame")?.arg("schema").output()?;
+
+    assert!(output.status.success());
+
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+
+    assert!(schema["properties"]["tasks"].is_object());
+    assert!(schema["properties"]["include"].is_object());
+    assert!(schema["definitions"]["TaskCfg"].is_object());
+
+    Ok(())
+}
+
 #[tokio::test]
 #[serial]
 async fn ame_setup_cli() -> Result<(), Box<dyn std::error::Error>> {
@@ -1,4 +1,13 @@
-use ame::custom_resources::{common::parent_project, new_task::Task, project::Project};
+use ame::{
+    custom_resources::{
+        common::parent_project,
+        data_set_graph::qualified_data_set_name,
+        new_task::Task,
+        project::{local_name, project_name, Project},
+    },
+    grpc::{DataSetGraph, ProjectCfg},
+};
+use chrono::Utc;
 use std::{sync::Arc, time::Duration};
 
 use ame::{
@@ -90,12 +99,119 @@ async fn apply(
     tasks: &Api<Task>,
     projects: &Api<Project>,
 ) -> Result<Action> {
-    let mut task = data_set.generate_task()?;
-
     let parent_project = projects
         .get(&parent_project(data_set.owner_references().to_vec())?)
         .await?;
 
+    let all_projects = projects.list(&ListParams::default()).await?.items;
+    let project_cfgs: Vec<ProjectCfg> = all_projects.iter().map(|p| p.spec.cfg.clone()).collect();
+
+    let (graph, missing) = DataSetGraph::from_data_set(
+        &parent_project.spec.cfg,
+        &project_cfgs,
+        &data_set.spec.cfg.name,
+    );
+    let name = qualified_data_set_name(&parent_project.spec.cfg.name, &data_set.spec.cfg.name);
+
+    if !missing.is_empty() {
+        error!(
+            "data set {} depends on data sets which do not exist: {:?}",
+            data_set.name_any(),
+            missing
+        );
+
+        patch_status(
+            data_set,
+            data_sets,
+            DataSetStatus {
+                phase: Some(DataSetPhase::MissingDataSets { data_sets: missing }),
+                ..DataSetStatus::default()
+            },
+        )
+        .await?;
+
+        return Ok(Action::requeue(Duration::from_secs(60)));
+    }
+
+    if let Err(AmeError::DataSetCycle(cycle)) = graph.upstream_of(&name) {
+        error!(
+            "data set {} depends on itself: {}",
+            data_set.name_any(),
+            cycle.join(" -> ")
+        );
+
+        patch_status(
+            data_set,
+            data_sets,
+            DataSetStatus {
+                phase: Some(DataSetPhase::DependencyCycle { cycle }),
+                ..DataSetStatus::default()
+            },
+        )
+        .await?;
+
+        return Ok(Action::requeue(Duration::from_secs(300)));
+    }
+
+    // Upstream data sets generate their own dependencies in turn, so only the direct
+    // dependencies are created here.
+    let mut waiting_for = vec![];
+
+    for dependency in graph.dependencies(&name).unwrap_or_default() {
+        let Some(project) = all_projects
+            .iter()
+            .find(|p| Some(&p.spec.cfg.name) == project_name(dependency.clone()).as_ref())
+        else {
+            return Err(AmeError::MissingDataSet(
+                dependency.clone(),
+                parent_project.name_any(),
+            ));
+        };
+
+        let mut upstream = project.generate_data_set(local_name(dependency.clone()))?;
+
+        if let Some(mut oref) = data_set.controller_owner_ref(&()) {
+            oref.controller = Some(false);
+            upstream.owner_references_mut().push(oref);
+        }
+
+        let upstream = data_sets
+            .patch(
+                &upstream.name_any(),
+                &PatchParams::apply(DATA_SET_CONTROLLER).force(),
+                &Patch::Apply(&upstream),
+            )
+            .await?;
+
+        if !matches!(upstream.phase(), DataSetPhase::Ready { .. }) {
+            waiting_for.push(dependency.clone());
+        }
+    }
+
+    if !waiting_for.is_empty() {
+        info!(
+            "data set {} is waiting for data sets: {:?}",
+            data_set.name_any(),
+            waiting_for
+        );
+
+        patch_status(
+            data_set,
+            data_sets,
+            DataSetStatus {
+                phase: Some(DataSetPhase::WaitingForDataSets {
+                    data_sets: waiting_for,
+                }),
+                ..DataSetStatus::default()
+            },
+        )
+        .await?;
+
+        return Ok(Action::requeue(Duration::from_secs(10)));
+    }
+
+    let mut task = data_set.generate_task()?;
+
     let mut project_oref = parent_project.controller_owner_ref(&()).unwrap();
     project_oref.controller = Some(false);
 
@@ -110,17 +226,27 @@ async fn apply(
         )
         .await?;
 
-    let status = DataSetStatus {
-        phase: Some(DataSetPhase::from_task(task)),
-    };
+    let status =
+        DataSetStatus::from_task(task).with_ready_time(data_set.status.as_ref(), Utc::now());
 
+    patch_status(data_set, data_sets, status).await?;
+
+    Ok(Action::requeue(Duration::from_secs(300)))
+}
+
+async fn patch_status(
+    data_set: &DataSet,
+    data_sets: &Api<DataSet>,
+    status: DataSetStatus,
+) -> Result<()> {
     debug!("patching data set status {:?}  ", status.clone());
 
+    let status = status.with_conditions(data_set.status.as_ref(), Utc::now());
+
     let mut data_set = data_set.clone();
 
-    // let mut data_set = data_set.clone();
     data_set.metadata.managed_fields = None;
-    data_set.status = Some(status.clone());
+    data_set.status = Some(status);
 
     data_sets
         .patch_status(
@@ -130,7 +256,7 @@ async fn apply(
         )
         .await?;
 
-    Ok(Action::requeue(Duration::from_secs(300)))
+    Ok(())
 }
 
 async fn cleanup(data_set: &DataSet) -> Result<Action> {
@@ -147,7 +273,7 @@ async fn cleanup(data_set: &DataSet) -> Result<Action> {
 mod test {
     use ame::{
         custom_resources::data_set::DataSetSpec,
-        grpc::{DataSetCfg, ProjectCfg, TaskCfg, TaskRef},
+        grpc::{DataSetCfg, TaskCfg, TaskRef},
     };
     use kube::{
         api::{DeleteParams, PostParams},
@@ -176,6 +302,10 @@ mod test {
             tasks: vec![],
             templates: vec![],
             enable_triggers: None,
+            grants: vec![],
+            defaults: None,
+            include: vec![],
+            vars: Default::default(),
         };
         let project = Project::from_cfg(project);
         let project = projects.create(&PostParams::default(), &project).await?;
@@ -208,11 +338,13 @@ mod test {
                         secrets: vec![],
                     }),
                     size: None,
+                    validation: None,
                 },
                 deletion_approved: false,
             },
             status: Some(DataSetStatus {
                 phase: Some(DataSetPhase::Pending {}),
+                ..DataSetStatus::default()
             }),
         };
 
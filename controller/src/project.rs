@@ -1,31 +1,64 @@
 use ame::{
+    condition::{
+        set_condition, HealthReport, CONDITION_FALSE, CONDITION_TRUE, CONDITION_UNKNOWN,
+        DEGRADED_CONDITION,
+    },
     ctrl::AmeResource,
     custom_resources::{
+        artifact_storage::{model_prefix, task_prefix, ArtifactStorage},
+        common::{bounded_name, MAX_NAME_LEN},
+        data_set::DataSet,
+        model_registry::{MlflowRegistry, ModelRegistry, ObjectStorageRegistry},
         new_task::{Task, TaskBuilder},
-        project::{generate_task_name, get_latest_model_version, Project},
+        project::{generate_data_set_task_name, generate_task_name, DeletionPolicy, Project},
+        task_ctrl::{approve_deletion, TaskCtrl},
     },
+    model_validation::{OUTCOME_FAILED, OUTCOME_SUCCEEDED},
+    rollout::promote_annotation,
+    schedule::ConcurrencyPolicy,
 };
 
 use ame::{error::AmeError, Result};
 
-use ame::grpc::{task_status::Phase, ModelDeploymentCfg, ModelTrainingCfg, TaskStatus, TriggerCfg};
+use ame::grpc::{
+    model_deployment_cfg::Rollout, task_status::Phase, BatchInferenceCfg, BatchInferenceStatus,
+    Condition, Model, ModelDeploymentCfg, ModelMonitoringCfg, ModelStatus, ModelTrainingCfg,
+    ModelVersionStatus, MonitoringStatus, RolloutStatus, TaskCfg, TaskPhaseFailed,
+    TaskPhaseSucceeded, TaskScheduleStatus, TaskStatus, TriggerCfg,
+};
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use futures::{future::BoxFuture, FutureExt, StreamExt};
-use k8s_openapi::api::{apps::v1::Deployment, core::v1::Service, networking::v1::Ingress};
+use k8s_openapi::{
+    api::{
+        apps::v1::Deployment, autoscaling::v2::HorizontalPodAutoscaler, core::v1::Service,
+        networking::v1::Ingress,
+    },
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+};
 use kube::{
-    api::{ListParams, Patch, PatchParams},
+    api::{DeleteParams, ListParams, Patch, PatchParams},
     runtime::{controller::Action, finalizer, Controller},
-    Api, Client, ResourceExt,
+    Api, Client, Resource, ResourceExt,
 };
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
 
 use std::{sync::Arc, time::Duration};
 use tracing::{debug, error, info};
 
 static PROJECT_CONTROLLER: &str = "projects.ame.teainspace.com";
 
+const MLFLOW_REGISTRY: &str = "mlflow";
+const OBJECT_STORAGE_REGISTRY: &str = "object-storage";
+
+/// Bucket the object storage model registry keeps models in, and tasks their artifacts.
+static MODEL_REGISTRY_BUCKET: &str = "ame";
+
+/// Label identifying the runs of a scheduled task.
+static SCHEDULED_TASK_LABEL: &str = "ame.teainspace.com/scheduled-task";
+
 #[derive(Clone)]
 struct Context {
     cfg: ProjectControllerCfg,
@@ -45,6 +78,13 @@ pub struct ProjectControllerCfg {
     model_deployment_ingress: Option<Ingress>,
     model_ingress_annotations: Option<BTreeMap<String, String>>,
     model_ingress_host: Option<String>,
+
+    /// Registry models are deployed from, either `mlflow` or `object-storage`.
+    pub model_registry: Option<String>,
+    pub s3_endpoint: Option<String>,
+    pub s3_region: Option<String>,
+    pub s3_access_id: Option<String>,
+    pub s3_secret: Option<String>,
 }
 
 impl ProjectControllerCfg {
@@ -61,8 +101,70 @@ impl ProjectControllerCfg {
             model_ingress_annotations: Some(BTreeMap::new()),
             model_ingress_host: std::env::var(format!("{prefix}_MODEL_INGRESS_HOST")).ok(),
             mlflow_url: std::env::var(format!("{prefix}_MLFLOW_URL")).ok(),
+            model_registry: std::env::var(format!("{prefix}_MODEL_REGISTRY")).ok(),
+            s3_endpoint: std::env::var("S3_ENDPOINT").ok(),
+            s3_region: std::env::var("S3_REGION").ok(),
+            s3_access_id: std::env::var("S3_ACCESS_ID").ok(),
+            s3_secret: std::env::var("S3_SECRET").ok(),
         })
     }
+
+    /// Creates the configured model registry, MLflow is used unless another registry is
+    /// selected.
+    pub fn model_registry(&self) -> Result<Box<dyn ModelRegistry>> {
+        match self.model_registry.as_deref().unwrap_or(MLFLOW_REGISTRY) {
+            MLFLOW_REGISTRY => {
+                let Some(ref mlflow_url) = self.mlflow_url else {
+                    return Err(AmeError::MissingModelRegistryCfg(
+                        "AME_MLFLOW_URL".to_string(),
+                    ));
+                };
+
+                Ok(Box::new(MlflowRegistry::new(mlflow_url.clone())))
+            }
+            OBJECT_STORAGE_REGISTRY => {
+                let (Some(endpoint), Some(access_id), Some(secret)) =
+                    (&self.s3_endpoint, &self.s3_access_id, &self.s3_secret)
+                else {
+                    return Err(AmeError::MissingModelRegistryCfg(
+                        "S3_ENDPOINT, S3_ACCESS_ID and S3_SECRET".to_string(),
+                    ));
+                };
+
+                Ok(Box::new(
+                    ObjectStorageRegistry::new(
+                        MODEL_REGISTRY_BUCKET,
+                        endpoint.clone(),
+                        self.s3_region.clone().unwrap_or("eu-central-1".to_string()),
+                        access_id,
+                        secret,
+                    )
+                    .map_err(|e| AmeError::ModelRegistryError(e.to_string()))?,
+                ))
+            }
+            registry => Err(AmeError::UnknownModelRegistry(registry.to_string())),
+        }
+    }
+
+    /// Creates access to the object storage tasks store their artifacts in, `None` is returned
+    /// when object storage is not configured.
+    pub fn artifact_storage(&self) -> Result<Option<ArtifactStorage>> {
+        let (Some(endpoint), Some(access_id), Some(secret)) =
+            (&self.s3_endpoint, &self.s3_access_id, &self.s3_secret)
+        else {
+            return Ok(None);
+        };
+
+        ArtifactStorage::new(
+            MODEL_REGISTRY_BUCKET,
+            endpoint.clone(),
+            self.s3_region.clone().unwrap_or("eu-central-1".to_string()),
+            access_id,
+            secret,
+        )
+        .map(Some)
+        .map_err(|e| AmeError::StorageError(e.to_string()))
+    }
 }
 
 impl ProjectControllerCfg {
@@ -74,6 +176,11 @@ impl ProjectControllerCfg {
             model_deployment_ingress: None,
             model_ingress_annotations: None,
             model_ingress_host: None,
+            model_registry: None,
+            s3_endpoint: None,
+            s3_region: None,
+            s3_access_id: None,
+            s3_secret: None,
         }
     }
 }
@@ -93,7 +200,7 @@ async fn reconcile(project: Arc<Project>, ctx: Arc<Context>) -> Result<Action> {
                 finalizer::Event::Apply(project) => {
                     apply(&project, &projects, ctx.client.clone(), ctx).await
                 }
-                finalizer::Event::Cleanup(project) => cleanup(&project, &projects).await,
+                finalizer::Event::Cleanup(project) => cleanup(&project, &ctx).await,
             }
         })
         .await?,
@@ -102,82 +209,212 @@ async fn reconcile(project: Arc<Project>, ctx: Arc<Context>) -> Result<Action> {
 
 async fn apply(
     project: &Project,
-    _projects: &Api<Project>,
+    projects: &Api<Project>,
     client: Client,
     ctx: Arc<Context>,
 ) -> Result<Action> {
     let project_status = project.status.clone().unwrap_or_default();
     let tasks = Api::<Task>::namespaced(client.clone(), &project.namespace().unwrap());
-    let deployments = Api::<Deployment>::namespaced(client.clone(), &project.namespace().unwrap());
-    let services = Api::<Service>::namespaced(client.clone(), &project.namespace().unwrap());
-    let ingresses = Api::<Ingress>::namespaced(client, &project.namespace().unwrap());
+    let model_apis = ModelApis::namespaced(client, &project.namespace().unwrap());
 
     let Some(project_oref) = project.gen_owner_ref() else {
         return Err(AmeError::FailedToCreateOref(project.name_any()));
     };
 
+    let mut requeue_after = Duration::from_secs(60);
+    let mut health = HealthReport::default();
+
+    let now = Utc::now();
+
     if project.spec.enable_triggers.unwrap_or(false) {
         info!("checking for triggered tasks");
-        let instant = Utc::now();
+        let mut schedules = project_status.schedules.clone();
+
+        let task_ctrl = TaskCtrl::new(ctx.client.clone(), &project.namespace().unwrap());
 
         for task in project.spec.cfg.tasks.iter() {
-            if let Some(TriggerCfg {
-                schedule: Some(ref schedule),
-            }) = task.triggers
-            {
-                // TODO this parser is brittle and panics if len < 3;
-                let schedule = match cron_parser::parse(schedule, &instant) {
-                    Ok(schedule) => schedule,
-                    e @ Err(_) => {
+            let Some(ref trigger) = task.triggers else {
+                continue;
+            };
+
+            let task_name = task.name.clone().unwrap_or_default();
+            let mut status = schedules.get(&task_name).cloned().unwrap_or_default();
+
+            if trigger.schedule.is_some() {
+                match schedule_task(
+                    project,
+                    task,
+                    trigger,
+                    &tasks,
+                    &status,
+                    project_oref.clone(),
+                    now,
+                )
+                .await
+                {
+                    Ok(Some(instant)) => status.last_schedule_time = Some(instant.to_rfc3339()),
+                    Ok(None) => (),
+                    Err(e) => {
                         error!(
-                            "failed to pass cron schedule for task {}: {e:?}",
-                            task.name.as_ref().unwrap_or(&"".to_string())
+                            "failed to schedule task {} in project {}: {}",
+                            task_name,
+                            project.name_any(),
+                            e
                         );
-                        continue;
+                        health.degraded("TaskScheduleFailed", format!("task {task_name}: {e}"));
                     }
-                };
-
-                if schedule.signed_duration_since(instant).num_seconds() < 120 {
-                    let mut task_builder = TaskBuilder::from_cfg(task.clone());
-                    task_builder.add_owner_reference(project_oref.clone());
-
-                    task_builder.set_name(generate_task_name(
-                        project.name_any(),
-                        task.name.clone().unwrap_or("".to_string()),
-                    ));
-                    task_builder.set_project(project.spec.cfg.name.clone());
-
-                    let task = task_builder.build();
-
-                    let Some(ref task_name) = task.metadata.name else {
-                        error!("task is missing a name in project {}", project.name_any());
-                        continue;
-                    };
+                }
 
-                    let res = tasks
-                        .patch(
-                            task_name,
-                            &PatchParams::apply(PROJECT_CONTROLLER),
-                            &Patch::Apply(task.clone()),
-                        )
-                        .await;
+                if let Ok(Some(next)) = trigger.next_run(now) {
+                    if let Ok(until_next) = next.signed_duration_since(now).to_std() {
+                        requeue_after = requeue_after.min(until_next);
+                    }
+                }
+            }
 
-                    if let e @ Err(_) = res {
+            if !trigger.on_data_set_update.is_empty() {
+                match trigger_on_data_set_update(
+                    project,
+                    task,
+                    trigger,
+                    &tasks,
+                    &task_ctrl,
+                    &status,
+                    project_oref.clone(),
+                    now,
+                )
+                .await
+                {
+                    Ok(Some(version)) => status.last_data_set_update = Some(version.to_rfc3339()),
+                    Ok(None) => (),
+                    Err(e) => {
                         error!(
-                            "failed to patch task {} in project {}: {:?}",
-                            task.name_any(),
+                            "failed to trigger task {} in project {} on data set updates: {}",
+                            task_name,
                             project.name_any(),
                             e
                         );
-                        continue;
-                    };
+                        health.degraded("TaskTriggerFailed", format!("task {task_name}: {e}"));
+                    }
+                }
+
+                if let Ok(debounce) = trigger.debounce().to_std() {
+                    requeue_after = requeue_after.min(debounce.max(Duration::from_secs(1)));
                 }
             }
+
+            if status != TaskScheduleStatus::default() {
+                schedules.insert(task_name, status);
+            }
+        }
+
+        if schedules != project_status.schedules {
+            projects
+                .patch_status(
+                    &project.name_any(),
+                    &PatchParams::default(),
+                    &Patch::Merge(json!({ "status": { "schedules": schedules } })),
+                )
+                .await?;
         }
     } else {
         debug!("triggers are disabled");
     }
 
+    // Batch inference and monitoring are configured on models rather than triggered tasks, so
+    // they run whether or not triggers are enabled.
+    let data_sets = Api::<DataSet>::namespaced(ctx.client.clone(), &project.namespace().unwrap());
+
+    for model in project.spec.cfg.models.iter() {
+        let Some(ref batch_inference) = model.batch_inference else {
+            continue;
+        };
+
+        let model_status = project_status
+            .models
+            .get(&model.name)
+            .cloned()
+            .unwrap_or_default();
+
+        match schedule_batch_inference(
+            project,
+            model,
+            batch_inference,
+            &model_status,
+            &ctx,
+            &data_sets,
+            now,
+        )
+        .await
+        {
+            Ok(Some(status)) => {
+                projects
+                    .patch_status(
+                        &project.name_any(),
+                        &PatchParams::default(),
+                        &Patch::Merge(json!({ "status": { "models": { model.name.clone(): { "batchInference": status } } } })),
+                    )
+                    .await?;
+            }
+            Ok(None) => (),
+            Err(e) => {
+                error!(
+                    "failed to schedule batch inference of model {} in project {}: {}",
+                    model.name,
+                    project.name_any(),
+                    e
+                );
+                health.degraded("BatchInferenceFailed", format!("model {}: {e}", model.name));
+            }
+        }
+
+        if let Ok(Some(next)) = batch_inference.trigger().next_run(now) {
+            if let Ok(until_next) = next.signed_duration_since(now).to_std() {
+                requeue_after = requeue_after.min(until_next);
+            }
+        }
+    }
+
+    for model in project.spec.cfg.models.iter() {
+        let Some(ref monitoring) = model.monitoring else {
+            continue;
+        };
+
+        let model_status = project_status
+            .models
+            .get(&model.name)
+            .cloned()
+            .unwrap_or_default();
+
+        match monitor_model(project, model, monitoring, &model_status, &ctx, &tasks, now).await {
+            Ok(Some((status, conditions))) => {
+                projects
+                    .patch_status(
+                        &project.name_any(),
+                        &PatchParams::default(),
+                        &Patch::Merge(json!({ "status": { "models": { model.name.clone(): { "monitoring": status, "conditions": conditions } } } })),
+                    )
+                    .await?;
+            }
+            Ok(None) => (),
+            Err(e) => {
+                error!(
+                    "failed to monitor model {} in project {}: {}",
+                    model.name,
+                    project.name_any(),
+                    e
+                );
+                health.degraded("MonitoringFailed", format!("model {}: {e}", model.name));
+            }
+        }
+
+        if let Ok(Some(next)) = monitoring.trigger().next_run(now) {
+            if let Ok(until_next) = next.signed_duration_since(now).to_std() {
+                requeue_after = requeue_after.min(until_next);
+            }
+        }
+    }
+
     // Note task controller is not updating task status to succeeded.
     // Why is controller not reacting owned task status changing?
     for model in project.spec.cfg.models.iter() {
@@ -190,8 +427,18 @@ async fn apply(
             ..
         }) = model.training
         {
-            let Ok(training_task) = project.generate_model_training_task(&model.name) else {
-                continue;
+            let training_task = match project.generate_model_training_task(&model.name) {
+                Ok(task) => task,
+                Err(e) => {
+                    error!(
+                        "failed to generate training task for model {} in project {}: {}",
+                        model.name,
+                        project.name_any(),
+                        e
+                    );
+                    health.degraded("TrainingFailed", format!("model {}: {e}", model.name));
+                    continue;
+                }
             };
 
             debug!("Patching training task: {:?}", training_task);
@@ -218,18 +465,18 @@ async fn apply(
                     model.name,
                     project.name_any()
                 );
+                health.degraded(
+                    "MissingDeploymentImage",
+                    format!("model {}: no deployment image is configured", model.name),
+                );
                 continue;
             };
 
-            let Some(mlflow_url) = ctx.cfg.mlflow_url.clone() else {
-                error!("missing MLflow URL, skipping deployment");
-                continue;
-            };
-
-            let model_source = match get_latest_model_version(model, mlflow_url).await {
-                Ok(ms) => ms,
+            let registry = match ctx.cfg.model_registry() {
+                Ok(registry) => registry,
                 Err(e) => {
-                    error!("failed to get latest model version, skipping deployment error: {e}");
+                    error!("failed to create model registry, skipping deployment error: {e}");
+                    health.degraded("ModelRegistryFailed", format!("model {}: {e}", model.name));
                     continue;
                 }
             };
@@ -240,36 +487,75 @@ async fn apply(
                 .cloned()
                 .unwrap_or_default();
 
+            let model_source = match model_status.target_version(model.deployment.as_ref()) {
+                Some(version) => registry.find_version(&model.name, version).await,
+                None => registry.latest_version(&model.name).await,
+            };
+
+            let model_source = match model_source {
+                Ok(ms) => ms,
+                Err(e) => {
+                    error!("failed to get model version, skipping deployment error: {e}");
+                    health.degraded("ModelVersionNotFound", format!("model {}: {e}", model.name));
+                    continue;
+                }
+            };
+
             if model_status
                 .latest_validated_model_version
                 .as_ref()
                 .map(|s| s != &model_source.source)
                 .unwrap_or(true)
                 && model.validation_task.is_some()
+                && !model_status
+                    .version_status(&model_source.source)
+                    .map(|v| v.is_validated())
+                    .unwrap_or(false)
             {
+                let mut version_status = model_status
+                    .version_status(&model_source.source)
+                    .cloned()
+                    .unwrap_or(ModelVersionStatus::pending(
+                        &model_source.version,
+                        &model_source.source,
+                        Utc::now(),
+                    ));
+
+                if version_status.is_failed() {
+                    info!(
+                        "version {} of model {} in project {} failed validation, skipping deployment until validation is retried",
+                        model_source.version,
+                        model.name,
+                        project.name_any()
+                    );
+                    health.degraded(
+                        "ValidationFailed",
+                        format!(
+                            "model {}: version {} failed validation",
+                            model.name, model_source.version
+                        ),
+                    );
+                    continue;
+                }
+
                 debug!(
                     "Generating validation task for model {} in project {}",
                     model.name, project.spec.cfg.name
                 );
 
-                let val_task = match project
+                let mut val_task = match project
                     .generate_validation_task(model, model_source.version.clone())
                 {
-                    Ok(t) => Some(t),
+                    Ok(t) => t,
                     Err(e) => {
                         error!("failed to generate validaion task for model {} in project {}, aborting model validation and deployment: {}", model.name, project.spec.cfg.name, e);
-                        None
+                        health.degraded("ValidationFailed", format!("model {}: {e}", model.name));
+                        continue;
                     }
                 };
 
-                let Some(ref val_task) = val_task else {
-                    continue;
-                };
-
-                let Some(ref task_name) = val_task.metadata.name else {
-                    error!("validation task for model {} in project {} is missing a name, aborting model validation and deployment", model.name, project.spec.cfg.name);
-                    continue;
-                };
+                let task_name = version_status.validation_task_name(&model.name);
+                val_task.metadata.name = Some(task_name.clone());
 
                 info!(
                     "patching validation task for model {} in project {}",
@@ -277,129 +563,934 @@ async fn apply(
                     project.name_any()
                 );
 
-                // TODO; we need to look at the name generation for validated model tasks.
                 tasks
                     .patch(
-                        task_name,
+                        &task_name,
                         &PatchParams::apply(PROJECT_CONTROLLER),
-                        &Patch::Apply(val_task),
+                        &Patch::Apply(&val_task),
                     )
                     .await?;
 
-                // TODO: what about when validation has failed?
-                if let Ok(Task {
-                    status:
-                        Some(TaskStatus {
-                            phase: Some(Phase::Succeeded(_)),
-                        }),
-                    ..
-                }) = tasks.get_status(task_name).await
-                {
-                    info!(
-                        "model {} in project {} is validated for version {}",
+                version_status.validation_task = Some(task_name.clone());
+
+                let phase = tasks
+                    .get_status(&task_name)
+                    .await
+                    .ok()
+                    .and_then(|task| task.status)
+                    .and_then(|status| status.phase);
+
+                match phase {
+                    Some(Phase::Succeeded(_)) => {
+                        info!(
+                            "model {} in project {} is validated for version {}",
+                            model.name,
+                            project.name_any(),
+                            model_source.version
+                        );
+                        version_status.outcome = OUTCOME_SUCCEEDED.to_string();
+                        version_status.reason = None;
+                        version_status.timestamp = Some(Utc::now().to_rfc3339());
+                        model_status.latest_validated_model_version =
+                            Some(model_source.source.clone());
+                        model_status.record_validation(version_status);
+                    }
+                    Some(Phase::Failed(TaskPhaseFailed {
+                        failed_step,
+                        message,
+                        ..
+                    })) => {
+                        info!(
+                            "model {} in project {} failed validation for version {}",
+                            model.name,
+                            project.name_any(),
+                            model_source.version
+                        );
+                        version_status.outcome = OUTCOME_FAILED.to_string();
+                        version_status.reason = Some(
+                            message
+                                .or(failed_step.map(|step| format!("step {step} failed")))
+                                .unwrap_or("validation task failed".to_string()),
+                        );
+                        health.degraded(
+                            "ValidationFailed",
+                            format!(
+                                "model {}: version {} failed validation",
+                                model.name, model_source.version
+                            ),
+                        );
+                        version_status.timestamp = Some(Utc::now().to_rfc3339());
+                        model_status.record_validation(version_status);
+                        patch_model_status(projects, project, &model.name, &model_status).await?;
+                        continue;
+                    }
+                    _ => {
+                        info!("model {} in project {} is not validated for version {}, skipping deployment", model.name, project.name_any(), model_source.version);
+                        health.reconciling(
+                            "ValidationPending",
+                            format!(
+                                "model {}: version {} is being validated",
+                                model.name, model_source.version
+                            ),
+                        );
+                        if model_status.version_status(&model_source.source)
+                            != Some(&version_status)
+                        {
+                            model_status.record_validation(version_status);
+                            patch_model_status(projects, project, &model.name, &model_status)
+                                .await?;
+                        }
+                        continue;
+                    }
+                }
+            }
+
+            let promote_annotation = promote_annotation(&model.name);
+            let promote = project
+                .annotations()
+                .get(&promote_annotation)
+                .map(|val| val == "true")
+                .unwrap_or(false);
+
+            let rollout = match model_status.rollout.clone().unwrap_or_default().progress(
+                model.deployment.as_ref().and_then(|d| d.rollout.as_ref()),
+                &registry.model_uri(&model_source),
+                promote,
+                Utc::now(),
+            ) {
+                Ok(rollout) => rollout,
+                Err(e) => {
+                    error!(
+                        "failed to progress rollout of model {} in project {}: {}",
                         model.name,
                         project.name_any(),
-                        model_source.version
+                        e
                     );
-                    model_status.latest_validated_model_version = Some(model_source.source.clone());
-                } else {
-                    info!("model {} in project {} is not validated for version {}, skipping deployment", model.name, project.name_any(), model_source.version);
+                    health.degraded("RolloutFailed", format!("model {}: {e}", model.name));
                     continue;
                 }
-            }
+            };
 
-            let deployment = model
-                .generate_model_deployment(deployment_image.clone(), model_source.source)
-                .await;
-            match deployment {
-                Ok(deployment) => {
-                    let Some(ref name) = deployment.metadata.name else {
-                        error!("Deployment object for model {} in project {} is missing a name, skipping deployment", model.name, project.name_any());
-                        continue;
-                    };
-
-                    info!("Patching model deployment");
-
-                    // TODO: adjust error handling to avoid breaking out of the reconciliation for errors on a single object.
-                    deployments
-                        .patch(
-                            name,
-                            &PatchParams::apply(PROJECT_CONTROLLER),
-                            &Patch::Apply(deployment.clone()),
-                        )
-                        .await?;
-                }
-                Err(e) => error!(
-                    "Failed to generated model deployment for {} in project {} error: {}",
+            if let Err(e) = deploy_model(
+                model,
+                &rollout,
+                deployment_image,
+                registry.as_ref(),
+                ctx.cfg.model_ingress_host.clone().unwrap_or("".to_string()),
+                project.name_any(),
+                &model_apis,
+            )
+            .await
+            {
+                error!(
+                    "failed to deploy model {} in project {}: {}",
                     model.name,
                     project.name_any(),
                     e
-                ),
-            };
+                );
+                health.degraded("DeploymentFailed", format!("model {}: {e}", model.name));
+                continue;
+            }
 
-            let service = model.generate_model_service();
+            if promote {
+                projects
+                    .patch(
+                        &project.name_any(),
+                        &PatchParams::default(),
+                        &Patch::Merge(
+                            json!({ "metadata": { "annotations": { promote_annotation: null } } }),
+                        ),
+                    )
+                    .await?;
+            }
 
-            match service {
-                Ok(service) => {
-                    let Some(ref name) = service.metadata.name else {
-                        error!("Service object for model {} in project {} is missing a name, skipping service", model.name, project.name_any());
-                        continue;
-                    };
-
-                    info!("Patching model service");
-                    services
-                        .patch(
-                            name,
-                            &PatchParams::apply(PROJECT_CONTROLLER),
-                            &Patch::Apply(service.clone()),
-                        )
-                        .await?;
+            if let Some(Rollout::Canary(ref canary)) =
+                model.deployment.as_ref().and_then(|d| d.rollout.as_ref())
+            {
+                if rollout.candidate_source.is_some() {
+                    if let Ok(interval) = canary.step_interval().to_std() {
+                        requeue_after = requeue_after.min(interval);
+                    }
                 }
-                Err(e) => error!(
-                    "Failed to generated service for model {} in project {}: {}",
-                    model.name,
-                    project.name_any(),
-                    e
-                ),
-            };
+            }
 
-            // TODO: put some thought into how the project name is used and the path for a model created.
-            let ingress = model.generate_model_ingress(
-                ctx.cfg.model_ingress_host.clone().unwrap_or("".to_string()),
-                None,
-                project.name_any(),
+            if rollout.candidate_source.is_none() {
+                model_status.record_deployment(&model_source.version);
+            } else {
+                health.reconciling(
+                    "RolloutInProgress",
+                    format!(
+                        "model {}: version {} is being rolled out ({})",
+                        model.name, model_source.version, rollout.phase
+                    ),
+                );
+            }
+
+            model_status.rollout = Some(rollout);
+
+            if project_status.models.get(&model.name) != Some(&model_status) {
+                patch_model_status(projects, project, &model.name, &model_status).await?;
+            }
+        } else {
+            // The autoscaler should not keep scaling a deployment which is no longer configured.
+            delete_if_present(&model_apis.autoscalers, &model.name).await?;
+        }
+    }
+
+    let mut conditions = project_status.conditions.clone();
+    health.set_conditions(&mut conditions, Utc::now());
+
+    if conditions != project_status.conditions {
+        projects
+            .patch_status(
+                &project.name_any(),
+                &PatchParams::default(),
+                &Patch::Merge(json!({ "status": { "conditions": conditions } })),
+            )
+            .await?;
+    }
+
+    Ok(Action::requeue(requeue_after))
+}
+
+/// Returns the name shared by the triggered runs of a task, it is the value of their
+/// [`SCHEDULED_TASK_LABEL`] and is bounded so a run name made of it and a timestamp still fits
+/// in a label.
+fn scheduled_task_name(project: &Project, task_cfg: &TaskCfg) -> String {
+    bounded_name(
+        &generate_task_name(
+            project.name_any(),
+            task_cfg.name.clone().unwrap_or_default(),
+        ),
+        MAX_NAME_LEN - 11,
+    )
+}
+
+/// Starts a run of a scheduled task if one is due, returning the instant the run was
+/// scheduled for.
+///
+/// The instant is also returned for runs which are skipped because they missed their
+/// starting deadline or are forbidden by the concurrency policy, so they are not
+/// considered again.
+async fn schedule_task(
+    project: &Project,
+    task_cfg: &TaskCfg,
+    trigger: &TriggerCfg,
+    tasks: &Api<Task>,
+    status: &TaskScheduleStatus,
+    project_oref: OwnerReference,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>> {
+    let last_run = status_time(project, status.last_schedule_time.as_ref(), now)?;
+
+    let Some(instant) = trigger.due_run(last_run, now)? else {
+        return Ok(None);
+    };
+
+    let task_name = scheduled_task_name(project, task_cfg);
+
+    if !trigger.is_within_deadline(instant, now) {
+        info!(
+            "skipping run of {} scheduled for {} as it missed its starting deadline",
+            task_name, instant
+        );
+        return Ok(Some(instant));
+    }
+
+    let active_runs: Vec<Task> = tasks
+        .list(&ListParams::default().labels(&format!("{SCHEDULED_TASK_LABEL}={task_name}")))
+        .await?
+        .items
+        .into_iter()
+        .filter(|t| {
+            !matches!(
+                t.status,
+                Some(TaskStatus {
+                    phase: Some(Phase::Succeeded(_) | Phase::Failed(_)),
+                    ..
+                })
+            )
+        })
+        .collect();
+
+    match trigger.parsed_concurrency_policy()? {
+        ConcurrencyPolicy::Forbid if !active_runs.is_empty() => {
+            info!(
+                "skipping run of {} scheduled for {} as a previous run is still active",
+                task_name, instant
             );
+            return Ok(Some(instant));
+        }
+        ConcurrencyPolicy::Replace => {
+            for run in active_runs {
+                info!("replacing active run {}", run.name_any());
+                approve_deletion(tasks, &run.name_any()).await?;
+                tasks
+                    .delete(&run.name_any(), &DeleteParams::default())
+                    .await?;
+            }
+        }
+        _ => (),
+    }
 
-            match ingress {
-                Ok(ingress) => {
-                    let Some(ref name) = ingress.metadata.name else {
-                        error!("Ingress object for model {} in project {} is missing a name, skipping ingress", model.name, project.name_any());
-                        continue;
-                    };
-
-                    info!("Patching model ingress");
-                    ingresses
-                        .patch(
-                            name,
-                            &PatchParams::apply(PROJECT_CONTROLLER),
-                            &Patch::Apply(ingress.clone()),
-                        )
-                        .await?;
-                }
-                Err(e) => error!(
-                    "Failed to generated ingress for model {} in project {}: {}",
+    let mut task_cfg = project.spec.cfg.with_task_defaults(task_cfg.clone());
+    task_cfg.set_logical_time(instant);
+
+    let mut task_builder = TaskBuilder::from_cfg(task_cfg);
+    task_builder.add_owner_reference(project_oref);
+    task_builder.set_name(format!("{task_name}-{}", instant.timestamp()));
+    task_builder.set_project(project.spec.cfg.name.clone());
+
+    let mut task = task_builder.build();
+    task.labels_mut()
+        .insert(SCHEDULED_TASK_LABEL.to_string(), task_name);
+
+    info!("starting run {} scheduled for {}", task.name_any(), instant);
+
+    tasks
+        .patch(
+            &task.name_any(),
+            &PatchParams::apply(PROJECT_CONTROLLER),
+            &Patch::Apply(task.clone()),
+        )
+        .await?;
+
+    Ok(Some(instant))
+}
+
+/// Starts a run of a task when a data set it is triggered by has become Ready since the
+/// previous run, returning the data set version the run was started for.
+///
+/// Runs are debounced, a run is only started once none of the data sets have been updated
+/// for the debounce period so a burst of updates results in a single run.
+#[allow(clippy::too_many_arguments)]
+async fn trigger_on_data_set_update(
+    project: &Project,
+    task_cfg: &TaskCfg,
+    trigger: &TriggerCfg,
+    tasks: &Api<Task>,
+    task_ctrl: &TaskCtrl,
+    status: &TaskScheduleStatus,
+    project_oref: OwnerReference,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>> {
+    let handled = status_time(project, status.last_data_set_update.as_ref(), now)?;
+
+    let mut data_sets = vec![];
+
+    for data_set_ref in trigger.on_data_set_update.iter() {
+        data_sets.push(
+            task_ctrl
+                .resolve_data_set_ref(data_set_ref.clone(), project.name_any())
+                .await,
+        );
+    }
+
+    let Some(version) = latest_data_set_version(data_sets)?.filter(|version| version > &handled)
+    else {
+        return Ok(None);
+    };
+
+    let task_name = scheduled_task_name(project, task_cfg);
+
+    if now.signed_duration_since(version) < trigger.debounce() {
+        debug!(
+            "waiting for data set updates to settle before starting {}",
+            task_name
+        );
+        return Ok(None);
+    }
+
+    let mut task_builder =
+        TaskBuilder::from_cfg(project.spec.cfg.with_task_defaults(task_cfg.clone()));
+    task_builder.add_owner_reference(project_oref);
+    task_builder.set_name(format!("{task_name}-{}", version.timestamp()));
+    task_builder.set_project(project.spec.cfg.name.clone());
+
+    let mut task = task_builder.build();
+    task.labels_mut()
+        .insert(SCHEDULED_TASK_LABEL.to_string(), task_name);
+
+    info!(
+        "starting run {} for data sets updated at {}",
+        task.name_any(),
+        version
+    );
+
+    tasks
+        .patch(
+            &task.name_any(),
+            &PatchParams::apply(PROJECT_CONTROLLER),
+            &Patch::Apply(task.clone()),
+        )
+        .await?;
+
+    Ok(Some(version))
+}
+
+/// Returns the latest version of the resolved data sets. Data sets which do not exist yet have
+/// no version, their project may not have materialised them.
+fn latest_data_set_version(data_sets: Vec<Result<DataSet>>) -> Result<Option<DateTime<Utc>>> {
+    let mut latest_version = None;
+
+    for data_set in data_sets {
+        match data_set {
+            Ok(data_set) => latest_version = latest_version.max(data_set.ready_time()),
+            Err(AmeError::MissingDataSet(data_set, project)) => {
+                debug!("data set {data_set} in project {project} does not exist yet");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(latest_version)
+}
+
+/// Starts a batch inference run of `model` if one is due, returning the updated batch inference
+/// status.
+///
+/// The run scores the input data set with the latest validated version of the model, or the
+/// latest version when the model is not validated, and writes the predictions to a new version
+/// of the output data set.
+async fn schedule_batch_inference(
+    project: &Project,
+    model: &Model,
+    cfg: &BatchInferenceCfg,
+    model_status: &ModelStatus,
+    ctx: &Context,
+    data_sets: &Api<DataSet>,
+    now: DateTime<Utc>,
+) -> Result<Option<BatchInferenceStatus>> {
+    let status = model_status.batch_inference.clone().unwrap_or_default();
+    let last_run = status_time(project, status.last_schedule_time.as_ref(), now)?;
+
+    let Some(instant) = cfg.trigger().due_run(last_run, now)? else {
+        return Ok(None);
+    };
+
+    let registry = ctx.cfg.model_registry()?;
+    let registry_error =
+        |e: ame::custom_resources::Error| AmeError::ModelRegistryError(e.to_string());
+
+    let version = match (
+        &model.validation_task,
+        &model_status.latest_validated_model_version,
+    ) {
+        (Some(_), Some(source)) => registry
+            .list_versions(&model.name)
+            .await
+            .map_err(registry_error)?
+            .into_iter()
+            .find(|v| &v.source == source),
+        (Some(_), None) => None,
+        (None, _) => Some(
+            registry
+                .latest_version(&model.name)
+                .await
+                .map_err(registry_error)?,
+        ),
+    };
+
+    let Some(version) = version else {
+        info!(
+            "skipping batch inference of model {} in project {} scheduled for {} as no version has been validated",
+            model.name,
+            project.name_any(),
+            instant
+        );
+
+        return Ok(Some(BatchInferenceStatus {
+            last_schedule_time: Some(instant.to_rfc3339()),
+            ..status
+        }));
+    };
+
+    let data_set = project
+        .generate_batch_inference_data_set(
+            model,
+            &registry.model_uri(&version),
+            &version.version,
+            instant,
+        )
+        .map_err(|e| {
+            AmeError::ReconcilitationFailure(
+                "project".to_string(),
+                project.name_any(),
+                e.to_string(),
+            )
+        })?;
+
+    info!(
+        "starting batch inference of model {} version {} in project {}",
+        model.name,
+        version.version,
+        project.name_any()
+    );
+
+    data_sets
+        .patch(
+            &data_set.name_any(),
+            &PatchParams::apply(PROJECT_CONTROLLER).force(),
+            &Patch::Apply(&data_set),
+        )
+        .await?;
+
+    Ok(Some(BatchInferenceStatus {
+        last_schedule_time: Some(instant.to_rfc3339()),
+        model_version: Some(version.version),
+        task: data_set.spec.cfg.task.and_then(|task| task.name),
+    }))
+}
+
+/// Collects the report of a model's in flight monitoring task and starts the next scheduled
+/// monitoring run of the deployed version.
+///
+/// The report is checked against the configured thresholds to set the model's `Degraded`
+/// condition. When the model becomes degraded and `retrainOnDegradation` is set a new training
+/// task is started. Returns the updated monitoring status and conditions if they changed.
+async fn monitor_model(
+    project: &Project,
+    model: &Model,
+    cfg: &ModelMonitoringCfg,
+    model_status: &ModelStatus,
+    ctx: &Context,
+    tasks: &Api<Task>,
+    now: DateTime<Utc>,
+) -> Result<Option<(MonitoringStatus, Vec<Condition>)>> {
+    let mut status = model_status.monitoring.clone().unwrap_or_default();
+    let mut conditions = model_status.conditions.clone();
+
+    if let Some(task_name) = status.task.clone() {
+        let phase = match tasks.get_opt(&task_name).await? {
+            Some(task) => task.status.and_then(|status| status.phase),
+            None => {
+                info!(
+                    "monitoring task {} of model {} in project {} no longer exists",
+                    task_name,
+                    model.name,
+                    project.name_any()
+                );
+                status.task = None;
+                None
+            }
+        };
+
+        match phase {
+            Some(Phase::Succeeded(TaskPhaseSucceeded { report, .. })) => {
+                let condition = match report {
+                    Some(ref report) => match cfg.breaches(report) {
+                        Ok(breaches) if breaches.is_empty() => Condition::new(
+                            DEGRADED_CONDITION,
+                            CONDITION_FALSE,
+                            "WithinThresholds",
+                            "all monitored metrics are within their thresholds",
+                        ),
+                        Ok(breaches) => Condition::new(
+                            DEGRADED_CONDITION,
+                            CONDITION_TRUE,
+                            "ThresholdsBreached",
+                            breaches.join(", "),
+                        ),
+                        Err(e) => Condition::new(
+                            DEGRADED_CONDITION,
+                            CONDITION_UNKNOWN,
+                            "InvalidReport",
+                            e.to_string(),
+                        ),
+                    },
+                    None => Condition::new(
+                        DEGRADED_CONDITION,
+                        CONDITION_UNKNOWN,
+                        "MissingReport",
+                        format!("monitoring task {task_name} did not produce a report"),
+                    ),
+                };
+
+                info!(
+                    "monitoring task {} of model {} in project {} finished, degraded: {}",
+                    task_name,
                     model.name,
                     project.name_any(),
-                    e
-                ),
+                    condition.status
+                );
+
+                let degraded = condition.is_true();
+
+                if set_condition(&mut conditions, condition, now)
+                    && degraded
+                    && cfg.retrain_on_degradation.unwrap_or(false)
+                {
+                    retrain_model(project, model, tasks, now).await?;
+                }
+
+                status.report = report;
+                status.report_time = Some(now.to_rfc3339());
+                status.task = None;
             }
+            Some(Phase::Failed(TaskPhaseFailed {
+                failed_step,
+                message,
+                ..
+            })) => {
+                set_condition(
+                    &mut conditions,
+                    Condition::new(
+                        DEGRADED_CONDITION,
+                        CONDITION_UNKNOWN,
+                        "MonitoringFailed",
+                        message
+                            .or(failed_step.map(|step| format!("step {step} failed")))
+                            .unwrap_or("monitoring task failed".to_string()),
+                    ),
+                    now,
+                );
+                status.task = None;
+            }
+            _ if status.task.is_some() => return Ok(None),
+            _ => (),
+        }
+    }
+
+    let last_run = status_time(project, status.last_schedule_time.as_ref(), now)?;
+
+    if status.task.is_none() {
+        if let Some(instant) = cfg.trigger().due_run(last_run, now)? {
+            status.last_schedule_time = Some(instant.to_rfc3339());
+            status.task =
+                start_monitoring_task(project, model, model_status, ctx, tasks, instant).await?;
         }
     }
 
-    Ok(Action::requeue(Duration::from_secs(60)))
+    if Some(&status) == model_status.monitoring.as_ref() && conditions == model_status.conditions {
+        return Ok(None);
+    }
+
+    Ok(Some((status, conditions)))
+}
+
+/// Starts a monitoring task for the deployed version of a model, returning the task's name.
+/// Nothing is started while no version is deployed.
+async fn start_monitoring_task(
+    project: &Project,
+    model: &Model,
+    model_status: &ModelStatus,
+    ctx: &Context,
+    tasks: &Api<Task>,
+    instant: DateTime<Utc>,
+) -> Result<Option<String>> {
+    let Some(ref deployed_version) = model_status.deployed_version else {
+        info!(
+            "skipping monitoring of model {} in project {} scheduled for {} as no version is deployed",
+            model.name,
+            project.name_any(),
+            instant
+        );
+
+        return Ok(None);
+    };
+
+    let registry = ctx.cfg.model_registry()?;
+    let version = registry
+        .find_version(&model.name, deployed_version)
+        .await
+        .map_err(|e| AmeError::ModelRegistryError(e.to_string()))?;
+
+    let task = project
+        .generate_monitoring_task(
+            model,
+            &registry.model_uri(&version),
+            &version.version,
+            instant,
+        )
+        .map_err(|e| {
+            AmeError::ReconcilitationFailure(
+                "project".to_string(),
+                project.name_any(),
+                e.to_string(),
+            )
+        })?;
+
+    info!(
+        "starting monitoring of model {} version {} in project {}",
+        model.name,
+        version.version,
+        project.name_any()
+    );
+
+    tasks
+        .patch(
+            &task.name_any(),
+            &PatchParams::apply(PROJECT_CONTROLLER),
+            &Patch::Apply(&task),
+        )
+        .await?;
+
+    Ok(Some(task.name_any()))
 }
 
-pub async fn cleanup(project: &Project, _projects: &Api<Project>) -> Result<Action> {
+/// Starts a new run of a model's training task, after monitoring found the model degraded.
+async fn retrain_model(
+    project: &Project,
+    model: &Model,
+    tasks: &Api<Task>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if model.training.is_none() {
+        info!(
+            "model {} in project {} is degraded but has no training task to retrain it with",
+            model.name,
+            project.name_any()
+        );
+        return Ok(());
+    }
+
+    let mut task = project
+        .generate_model_training_task(&model.name)
+        .map_err(|e| {
+            AmeError::ReconcilitationFailure(
+                "project".to_string(),
+                project.name_any(),
+                e.to_string(),
+            )
+        })?;
+
+    let task_name = bounded_name(
+        &format!("{}-retrain-{}", task.name_any(), now.timestamp()),
+        MAX_NAME_LEN,
+    );
+    task.metadata.name = Some(task_name.clone());
+
+    info!(
+        "retraining degraded model {} in project {} with task {}",
+        model.name,
+        project.name_any(),
+        task_name
+    );
+
+    tasks
+        .patch(
+            &task_name,
+            &PatchParams::apply(PROJECT_CONTROLLER),
+            &Patch::Apply(&task),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Parses a time recorded in a project's status, projects without a recorded time fall back
+/// to when the project was created.
+fn status_time(
+    project: &Project,
+    time: Option<&String>,
+    now: DateTime<Utc>,
+) -> Result<DateTime<Utc>> {
+    match time {
+        Some(time) => Ok(DateTime::parse_from_rfc3339(time)
+            .map_err(|e| AmeError::ApiError(format!("invalid time {time} in status: {e}")))?
+            .with_timezone(&Utc)),
+        None => Ok(project.creation_timestamp().map(|t| t.0).unwrap_or(now)),
+    }
+}
+
+/// Patches the validation and deployment fields of a model's status.
+///
+/// `model_status` is read at the start of a reconciliation, the remaining fields are written
+/// by monitoring, batch inference and the AME service in the meantime and must not be
+/// overwritten with their stale values.
+async fn patch_model_status(
+    projects: &Api<Project>,
+    project: &Project,
+    model_name: &str,
+    model_status: &ModelStatus,
+) -> Result<()> {
+    let ModelStatus {
+        latest_validated_model_version,
+        rollout,
+        versions,
+        deployed_version,
+        deployment_history,
+        ..
+    } = model_status;
+
+    projects
+        .patch_status(
+            &project.name_any(),
+            &PatchParams::default(),
+            &Patch::Merge(json!({ "status": { "models": { model_name: {
+                "latestValidatedModelVersion": latest_validated_model_version,
+                "rollout": rollout,
+                "versions": versions,
+                "deployedVersion": deployed_version,
+                "deploymentHistory": deployment_history,
+            } } } })),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// APIs for the objects serving a model.
+struct ModelApis {
+    deployments: Api<Deployment>,
+    services: Api<Service>,
+    ingresses: Api<Ingress>,
+    autoscalers: Api<HorizontalPodAutoscaler>,
+}
+
+impl ModelApis {
+    fn namespaced(client: Client, namespace: &str) -> Self {
+        Self {
+            deployments: Api::namespaced(client.clone(), namespace),
+            services: Api::namespaced(client.clone(), namespace),
+            ingresses: Api::namespaced(client.clone(), namespace),
+            autoscalers: Api::namespaced(client, namespace),
+        }
+    }
+
+    /// Deletes the objects serving `model`, including those serving a candidate version.
+    async fn delete_model(&self, model: &Model) -> Result<()> {
+        for name in [model.name.clone(), model.canary().name] {
+            delete_if_present(&self.ingresses, &name).await?;
+            delete_if_present(&self.services, &name).await?;
+            delete_if_present(&self.autoscalers, &name).await?;
+            delete_if_present(&self.deployments, &name).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies the deployment, autoscaler, service and ingress serving the stable model version, and while a
+/// candidate version is rolled out the canary objects serving it.
+///
+/// The canary ingress sends the rollout's share of traffic to the candidate, the canary
+/// objects are removed once the rollout is back to a single stable version.
+async fn deploy_model(
+    model: &Model,
+    rollout: &RolloutStatus,
+    deployment_image: String,
+    registry: &dyn ModelRegistry,
+    ingress_host: String,
+    project_name: String,
+    apis: &ModelApis,
+) -> Result<()> {
+    let ModelApis {
+        deployments,
+        services,
+        ingresses,
+        autoscalers,
+    } = apis;
+
+    let failure = |e: ame::custom_resources::Error| {
+        AmeError::ReconcilitationFailure("project".to_string(), model.name.clone(), e.to_string())
+    };
+
+    info!(
+        "deploying version {} of model {}",
+        rollout.stable_source, model.name
+    );
+
+    let deployment = model
+        .generate_model_deployment(
+            deployment_image.clone(),
+            rollout.stable_source.clone(),
+            registry,
+        )
+        .await
+        .map_err(failure)?;
+    apply_object(deployments, &deployment).await?;
+    apply_autoscaler(model, autoscalers).await?;
+    apply_object(services, &model.generate_model_service().map_err(failure)?).await?;
+
+    // TODO: put some thought into how the project name is used and the path for a model created.
+    let ingress = model
+        .generate_model_ingress(ingress_host.clone(), None, project_name.clone())
+        .map_err(failure)?;
+    apply_object(ingresses, &ingress).await?;
+
+    let canary = model.canary();
+
+    let Some(ref candidate_source) = rollout.candidate_source else {
+        delete_if_present(ingresses, &canary.name).await?;
+        delete_if_present(services, &canary.name).await?;
+        delete_if_present(autoscalers, &canary.name).await?;
+        delete_if_present(deployments, &canary.name).await?;
+
+        return Ok(());
+    };
+
+    info!(
+        "deploying candidate version {} of model {} with {}% of traffic",
+        candidate_source, model.name, rollout.canary_weight
+    );
+
+    let deployment = canary
+        .generate_model_deployment(deployment_image, candidate_source.clone(), registry)
+        .await
+        .map_err(failure)?;
+    apply_object(deployments, &deployment).await?;
+    apply_autoscaler(&canary, autoscalers).await?;
+    apply_object(services, &canary.generate_model_service().map_err(failure)?).await?;
+
+    let ingress = model
+        .generate_canary_ingress(ingress_host, None, project_name, rollout.canary_weight)
+        .map_err(failure)?;
+    apply_object(ingresses, &ingress).await?;
+
+    Ok(())
+}
+
+/// Applies the model's autoscaler, or removes it when the model no longer configures
+/// autoscaling.
+async fn apply_autoscaler(model: &Model, autoscalers: &Api<HorizontalPodAutoscaler>) -> Result<()> {
+    let autoscaler = model.generate_model_autoscaler().map_err(|e| {
+        AmeError::ReconcilitationFailure("project".to_string(), model.name.clone(), e.to_string())
+    })?;
+
+    match autoscaler {
+        Some(autoscaler) => apply_object(autoscalers, &autoscaler).await,
+        None => delete_if_present(autoscalers, &model.name).await,
+    }
+}
+
+/// Applies `object` with the project controller as the field manager.
+async fn apply_object<K>(api: &Api<K>, object: &K) -> Result<()>
+where
+    K: Resource + Clone + Serialize + DeserializeOwned + std::fmt::Debug,
+{
+    let Some(ref name) = object.meta().name else {
+        return Err(AmeError::MissingName);
+    };
+
+    api.patch(
+        name,
+        &PatchParams::apply(PROJECT_CONTROLLER),
+        &Patch::Apply(object),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn delete_if_present<K>(api: &Api<K>, name: &str) -> Result<()>
+where
+    K: Resource + Clone + DeserializeOwned + std::fmt::Debug,
+{
+    match api.delete(name, &DeleteParams::default()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(e)) if e.code == 404 => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Enforces the project's deletion policy before the project is removed.
+///
+/// Tasks are always deleted. Data sets are deleted unless the policy retains data, in which
+/// case they are orphaned so they outlive the project. Model deployments are deleted unless the
+/// policy retains deployments. The project's objects in storage are only deleted when the policy
+/// purges them.
+async fn cleanup(project: &Project, ctx: &Context) -> Result<Action> {
     if !project.deletion_approved() {
         info!(
             "Blocking deletion as it was not approved for {}",
@@ -408,9 +1499,140 @@ pub async fn cleanup(project: &Project, _projects: &Api<Project>) -> Result<Acti
         return Err(AmeError::DeletionNotApproved(project.name_any()));
     }
 
+    let policy = project.deletion_policy()?;
+    let namespace = project.namespace().unwrap();
+    let project_uid = project.uid().unwrap_or_default();
+
+    info!(
+        "cleaning up project {} with deletion policy {:?}",
+        project.name_any(),
+        policy
+    );
+
+    let tasks = Api::<Task>::namespaced(ctx.client.clone(), &namespace);
+    let data_sets = Api::<DataSet>::namespaced(ctx.client.clone(), &namespace);
+
+    let mut owners = vec![project_uid.clone()];
+    let mut task_names = BTreeSet::new();
+
+    for data_set in data_sets.list(&ListParams::default()).await?.items {
+        if !is_owned_by(&data_set, &owners) {
+            continue;
+        }
+
+        if policy.retains_data() {
+            info!("orphaning data set {}", data_set.name_any());
+            orphan(&data_sets, &data_set, &project_uid).await?;
+            continue;
+        }
+
+        owners.extend(data_set.uid());
+        task_names.insert(generate_data_set_task_name(
+            project.name_any(),
+            data_set.spec.cfg.name.clone(),
+        ));
+        approve_and_delete(&data_sets, &data_set.name_any()).await?;
+    }
+
+    for task in tasks.list(&ListParams::default()).await?.items {
+        if !is_owned_by(&task, &owners) {
+            continue;
+        }
+
+        task_names.insert(task.name_any());
+        approve_and_delete(&tasks, &task.name_any()).await?;
+    }
+
+    if !policy.retains_deployments() {
+        let model_apis = ModelApis::namespaced(ctx.client.clone(), &namespace);
+
+        for model in project.spec.cfg.models.iter() {
+            model_apis.delete_model(model).await?;
+        }
+    }
+
+    if policy != DeletionPolicy::Purge {
+        return Ok(Action::await_change());
+    }
+
+    let Some(storage) = ctx.cfg.artifact_storage()? else {
+        info!(
+            "object storage is not configured, keeping the stored objects of project {}",
+            project.name_any()
+        );
+        return Ok(Action::await_change());
+    };
+
+    let mut prefixes: Vec<String> = task_names.iter().map(|name| task_prefix(name)).collect();
+    prefixes.push(task_prefix(&project.name_any()));
+
+    if ctx.cfg.model_registry.as_deref() == Some(OBJECT_STORAGE_REGISTRY) {
+        prefixes.extend(
+            project
+                .spec
+                .cfg
+                .models
+                .iter()
+                .map(|m| model_prefix(&m.name)),
+        );
+    }
+
+    for prefix in prefixes {
+        let deleted = storage
+            .delete_prefix(&prefix)
+            .await
+            .map_err(|e| AmeError::StorageError(e.to_string()))?;
+
+        debug!("deleted {deleted} objects under {prefix}");
+    }
+
     Ok(Action::await_change())
 }
 
+fn is_owned_by<K: Resource>(object: &K, owners: &[String]) -> bool {
+    object
+        .owner_references()
+        .iter()
+        .any(|oref| owners.contains(&oref.uid))
+}
+
+/// Approves the deletion of an object guarded by a deletion approval, and deletes it.
+async fn approve_and_delete<K>(api: &Api<K>, name: &str) -> Result<()>
+where
+    K: Resource + Clone + DeserializeOwned + std::fmt::Debug,
+{
+    api.patch(
+        name,
+        &PatchParams::default(),
+        &Patch::Merge(json!({ "spec": { "deletionApproved": true } })),
+    )
+    .await?;
+
+    delete_if_present(api, name).await
+}
+
+/// Removes the owner reference to `owner_uid` so the object is not garbage collected with its
+/// owner.
+async fn orphan<K>(api: &Api<K>, object: &K, owner_uid: &str) -> Result<()>
+where
+    K: Resource + Clone + DeserializeOwned + std::fmt::Debug,
+{
+    let owner_references: Vec<&OwnerReference> = object
+        .owner_references()
+        .iter()
+        .filter(|oref| oref.uid != owner_uid)
+        .collect();
+
+    api.patch(
+        &object.name_any(),
+        &PatchParams::default(),
+        &Patch::Merge(json!({ "metadata": { "ownerReferences": owner_references } })),
+    )
+    .await?;
+
+    Ok(())
+}
+
 fn error_policy(_project: Arc<Project>, error: &AmeError, _ctx: Arc<Context>) -> Action {
     error!("failed to reconcile: {:?}", error);
     Action::requeue(Duration::from_secs(5 * 60))
@@ -448,7 +1670,10 @@ pub async fn start_project_controller(
 #[cfg(test)]
 mod test {
     use ame::{
-        custom_resources::project::{Project, ProjectSpec},
+        custom_resources::{
+            data_set::{DataSetPhase, DataSetSpec, DataSetStatus},
+            project::{Project, ProjectSpec},
+        },
         grpc::ProjectCfg,
         Result,
     };
@@ -460,6 +1685,55 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn scheduled_run_names_fit_in_a_label() {
+        let project = Project::from_cfg(ProjectCfg {
+            name: "myproject".to_string(),
+            ..ProjectCfg::default()
+        });
+        let task_cfg = TaskCfg {
+            name: Some("a".repeat(80)),
+            ..TaskCfg::default()
+        };
+
+        let task_name = scheduled_task_name(&project, &task_cfg);
+        let run_name = format!("{task_name}-{}", Utc::now().timestamp());
+
+        assert!(run_name.len() <= MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn data_sets_which_do_not_exist_yet_have_no_version() {
+        let missing = || {
+            Err(AmeError::MissingDataSet(
+                "data".to_string(),
+                "other".to_string(),
+            ))
+        };
+
+        assert_eq!(latest_data_set_version(vec![missing()]).unwrap(), None);
+
+        let mut data_set = DataSet::new("data", DataSetSpec::default());
+        data_set.status = Some(DataSetStatus {
+            phase: Some(DataSetPhase::Ready {
+                task_name: "datatask".to_string(),
+            }),
+            ready_time: Some("2023-08-01T00:00:00+00:00".to_string()),
+            ..DataSetStatus::default()
+        });
+        let version = data_set.ready_time();
+
+        assert!(version.is_some());
+        assert_eq!(
+            latest_data_set_version(vec![missing(), Ok(data_set)]).unwrap(),
+            version
+        );
+        assert!(
+            latest_data_set_version(vec![Err(AmeError::ApiError("unavailable".to_string()))])
+                .is_err()
+        );
+    }
+
     #[tokio::test]
     #[ignore = "requires a k8s cluster"]
     async fn can_block_deletion() -> Result<()> {
@@ -485,6 +1759,7 @@ mod test {
                 },
                 deletion_approved: false,
                 enable_triggers: Some(false),
+                deletion_policy: None,
             },
             status: None,
         };
@@ -7,16 +7,22 @@ use ame::{
     ctrl::AmeResource,
     custom_resources::{
         argo::{Workflow, WorkflowPhase},
-        data_set::{DataSet, DataSetPhase, DataSetStatus},
+        data_set::{
+            DataSet, DataSetPhase, DataSetStatus, DATA_SET_MEASURE_STEP, DATA_SET_SIZE_PARAMETER,
+        },
         find_project,
-        new_task::{build_workflow, resolve_task_templates, Task},
-        project::{local_name, project_name, Project},
+        new_task::{
+            build_workflow, resolve_task_templates, BackfillSpec, Task,
+            MONITORING_REPORT_PARAMETER, MONITORING_REPORT_STEP,
+        },
+        project::{local_name, namespace_name, project_name, Project},
         task_ctrl::TaskCtrl,
     },
     error::AmeError,
     grpc::{task_status::Phase, TaskPhaseFailed, TaskPhaseRunning, TaskPhaseSucceeded, TaskStatus},
     Result,
 };
+use chrono::Utc;
 use envconfig::Envconfig;
 use futures::{future::BoxFuture, FutureExt, StreamExt};
 use kube::{
@@ -71,8 +77,6 @@ async fn reconcile(task: Arc<Task>, ctx: Arc<Context>) -> Result<Action> {
     .await?)
 }
 
-// TODO: do not allow nonexistent fields in project.yaml.
-
 async fn apply(task: &Task, ctx: &Context) -> Result<Action> {
     let (tasks, workflows, projects, data_sets) = if let Some(ref namespace) = ctx.cfg.namespace {
         (
@@ -85,7 +89,15 @@ async fn apply(task: &Task, ctx: &Context) -> Result<Action> {
         todo!("we need to handle this case better??");
     };
 
-    let task_ctrl = TaskCtrl::new(data_sets.clone(), projects.clone());
+    let task_ctrl = TaskCtrl::new(ctx.client.clone(), ctx.cfg.namespace.as_ref().unwrap());
+
+    if !backfill_has_capacity(task, &tasks).await? {
+        info!(
+            "waiting for other runs in the backfill before starting {}",
+            task.name_any()
+        );
+        return Ok(Action::requeue(Duration::from_secs(30)));
+    }
 
     debug!("checking datasets for task {:?}", task.name_any());
 
@@ -102,6 +114,17 @@ async fn apply(task: &Task, ctx: &Context) -> Result<Action> {
         debug!("reconciling datasets {:?}", task.spec.cfg.data_sets);
 
         for ds in task.spec.cfg.data_sets.iter() {
+            // Data sets shared from other namespaces are generated by their own projects.
+            if namespace_name(ds.clone()).is_some_and(|ns| Some(&ns) != ctx.cfg.namespace.as_ref())
+            {
+                let data_set = task_ctrl
+                    .resolve_data_set_ref(ds.clone(), project.name_any())
+                    .await?;
+
+                ds_statuses.push(data_set.status);
+                continue;
+            }
+
             let ds_name = local_name(ds.clone());
 
             let ds_project = if let Some(project_name) = project_name(ds.clone()) {
@@ -169,7 +192,9 @@ async fn apply(task: &Task, ctx: &Context) -> Result<Action> {
 
             match stat.phase {
                 Some(DataSetPhase::Ready { .. }) => continue,
-                Some(DataSetPhase::Failed { .. }) => {
+                Some(DataSetPhase::Failed { .. })
+                | Some(DataSetPhase::ValidationFailed { .. })
+                | Some(DataSetPhase::DependencyCycle { .. }) => {
                     error!(
                         "Data set has failed, can not schedule Task {}",
                         task.spec
@@ -226,9 +251,17 @@ async fn apply(task: &Task, ctx: &Context) -> Result<Action> {
             }
             WorkflowPhase::Failed => Phase::Failed(TaskPhaseFailed {
                 workflow_name: workflow.name_any(),
+                failed_step: s.failed_step().map(|node| node.display_name.clone()),
+                message: s.failed_step().and_then(|node| node.message.clone()),
             }),
             WorkflowPhase::Succeeded => Phase::Succeeded(TaskPhaseSucceeded {
                 workflow_name: workflow.name_any(),
+                data_set_size_bytes: s
+                    .output_parameter(DATA_SET_MEASURE_STEP, DATA_SET_SIZE_PARAMETER)
+                    .and_then(|size| size.trim().parse().ok()),
+                report: s
+                    .output_parameter(MONITORING_REPORT_STEP, MONITORING_REPORT_PARAMETER)
+                    .map(String::from),
             }),
         })
         .unwrap_or(Phase::Running(TaskPhaseRunning {
@@ -237,7 +270,13 @@ async fn apply(task: &Task, ctx: &Context) -> Result<Action> {
 
     let mut task = task.clone();
 
-    task.status = Some(TaskStatus { phase: Some(phase) });
+    task.status = Some(
+        TaskStatus {
+            phase: Some(phase),
+            ..TaskStatus::default()
+        }
+        .with_conditions(task.status.as_ref(), Utc::now()),
+    );
     task.metadata.managed_fields = None;
 
     debug!("patching status for task {}", task.name_any());
@@ -252,6 +291,59 @@ async fn apply(task: &Task, ctx: &Context) -> Result<Action> {
     Ok(Action::requeue(std::time::Duration::from_secs(60)))
 }
 
+/// Checks if `task` may start without exceeding the parallelism of the backfill it is part of.
+///
+/// Runs of a backfill are admitted in the order of their names, only the first `parallelism`
+/// unfinished runs may start. Every reconciliation admits the same runs, so runs which are
+/// created together can not all start at once. Tasks which are not part of a backfill, or which
+/// have already started, always have capacity.
+async fn backfill_has_capacity(task: &Task, tasks: &Api<Task>) -> Result<bool> {
+    let Some(BackfillSpec {
+        ref id,
+        parallelism,
+    }) = task.spec.backfill
+    else {
+        return Ok(true);
+    };
+
+    if matches!(
+        task.status,
+        Some(TaskStatus {
+            phase: Some(Phase::Running(_)),
+            ..
+        })
+    ) {
+        return Ok(true);
+    }
+
+    let is_finished = |t: &Task| {
+        matches!(
+            t.status,
+            Some(TaskStatus {
+                phase: Some(Phase::Succeeded(_) | Phase::Failed(_)),
+                ..
+            })
+        )
+    };
+
+    let mut unfinished: Vec<String> = tasks
+        .list(&ListParams::default())
+        .await?
+        .items
+        .iter()
+        .filter(|t| t.spec.backfill.as_ref().map(|b| &b.id) == Some(id))
+        .filter(|t| !is_finished(t))
+        .map(|t| t.name_any())
+        .collect();
+
+    unfinished.sort();
+
+    Ok(unfinished
+        .iter()
+        .take(parallelism as usize)
+        .any(|name| name == &task.name_any()))
+}
+
 pub async fn cleanup(task: &Task, _tasks: &Api<Task>) -> Result<Action> {
     info!("cleanup dataset: {}", task.name_any());
 
@@ -340,6 +432,10 @@ mod test {
             tasks: vec![],
             templates: vec![],
             enable_triggers: None,
+            grants: vec![],
+            defaults: None,
+            include: vec![],
+            vars: Default::default(),
         };
 
         let project = Project::from_cfg(project);
@@ -372,6 +468,10 @@ mod test {
                 }),
                 deletion_approved: false,
                 project: None,
+                data_set_validation: None,
+                data_set_output: None,
+                backfill: None,
+                monitoring_report: None,
             },
             status: None,
         };
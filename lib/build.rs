@@ -3,7 +3,7 @@ use std::io;
 
 fn main() -> io::Result<()> {
     let mut prost_cfg = Config::new();
-    prost_cfg.btree_map(["resources", "ingressAnnotations"]);
+    prost_cfg.btree_map(["resources", "ingressAnnotations", "vars", "commits"]);
 
     tonic_build::configure()
         .build_server(true)
@@ -29,10 +29,38 @@ fn main() -> io::Result<()> {
             "ProjectCfg.templates",
             "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
         )
+        .field_attribute(
+            "ProjectCfg.defaults",
+            "#[serde(skip_serializing_if = \"Option::is_none\")]",
+        )
         .field_attribute(
             "ProjectCfg.tasks",
             "#[serde(default = \"Vec::<TaskCfg>::new\")]",
         )
+        .field_attribute(
+            "ProjectCfg.include",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ProjectCfg.vars",
+            "#[serde(skip_serializing_if = \"std::collections::BTreeMap::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ProjectInclude.models",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ProjectInclude.dataSets",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ProjectInclude.tasks",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ProjectInclude.templates",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
         .field_attribute("paths", "#[serde(default = \"Vec::<String>::new\")]")
         .field_attribute(
             "TaskCfg.secrets",
@@ -63,6 +91,106 @@ fn main() -> io::Result<()> {
             "ingressAnnotations",
             "#[serde(default = \"std::collections::BTreeMap::<String, String>::new\")]",
         )
+        .field_attribute(
+            "DataSetCfg.validation",
+            "#[serde(skip_serializing_if = \"Option::is_none\")]",
+        )
+        .field_attribute("ProjectStatus.schedules", "#[serde(default)]")
+        .type_attribute("RolloutStatus", "#[serde(default)]")
+        .type_attribute("ModelVersionStatus", "#[serde(default)]")
+        .field_attribute(
+            "ModelStatus.versions",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ModelStatus.deploymentHistory",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ModelStatus.pinnedVersion",
+            "#[serde(skip_serializing_if = \"Option::is_none\")]",
+        )
+        .field_attribute(
+            "ModelStatus.batchInference",
+            "#[serde(skip_serializing_if = \"Option::is_none\")]",
+        )
+        .field_attribute(
+            "ModelStatus.monitoring",
+            "#[serde(skip_serializing_if = \"Option::is_none\")]",
+        )
+        .field_attribute(
+            "ModelStatus.conditions",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ProjectStatus.conditions",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "TaskStatus.conditions",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ModelMonitoringCfg.thresholds",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "CustomRuntime.command",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "CustomRuntime.args",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "CustomRuntime.env",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "CanaryRollout.steps",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute("ProjectSourceStatus.commits", "#[serde(default)]")
+        .field_attribute(
+            "GitProjectSource.include",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "GitProjectSource.exclude",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "PushTriggerCfg.branches",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "PushTriggerCfg.paths",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\")]",
+        )
+        .field_attribute(
+            "TriggerCfg.onDataSetUpdate",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "ProjectCfg.grants",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "DataSetGrant.namespaces",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "DataSetGrant.projects",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "DataSetValidation.expectedFiles",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
+        .field_attribute(
+            "DataSetValidation.rowCounts",
+            "#[serde(skip_serializing_if = \"Vec::is_empty\", default)]",
+        )
         .field_attribute("Secret.variant", "#[serde(flatten)]")
         .protoc_arg("--experimental_allow_proto3_optional")
         .compile_with_config(prost_cfg, &["ame.proto"], &["./"])
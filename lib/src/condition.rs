@@ -0,0 +1,210 @@
+use chrono::{DateTime, Utc};
+
+use crate::grpc::Condition;
+
+pub static CONDITION_TRUE: &str = "True";
+pub static CONDITION_FALSE: &str = "False";
+pub static CONDITION_UNKNOWN: &str = "Unknown";
+
+/// The object and everything it manages has been reconciled.
+pub static READY_CONDITION: &str = "Ready";
+
+/// The object is waiting for work in progress, e.g. a running task or a pending validation.
+pub static RECONCILING_CONDITION: &str = "Reconciling";
+
+/// The object, or something it manages, failed and needs attention.
+pub static DEGRADED_CONDITION: &str = "Degraded";
+
+/// Overall state of an object, summarized in its Ready, Reconciling and Degraded conditions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Health {
+    Ready,
+    Reconciling,
+    Degraded,
+}
+
+impl Condition {
+    pub fn new(type_: &str, status: &str, reason: &str, message: impl Into<String>) -> Self {
+        Condition {
+            r#type: type_.to_string(),
+            status: status.to_string(),
+            reason: Some(reason.to_string()),
+            message: Some(message.into()),
+            last_transition_time: None,
+        }
+    }
+
+    pub fn is_true(&self) -> bool {
+        self.status == CONDITION_TRUE
+    }
+}
+
+/// Returns the condition of type `type_`, if it is set.
+pub fn find_condition<'a>(conditions: &'a [Condition], type_: &str) -> Option<&'a Condition> {
+    conditions.iter().find(|c| c.r#type == type_)
+}
+
+/// Sets `condition`, replacing the condition of the same type. The transition time only moves
+/// when the status of the condition changes, `true` is returned in that case.
+pub fn set_condition(
+    conditions: &mut Vec<Condition>,
+    mut condition: Condition,
+    now: DateTime<Utc>,
+) -> bool {
+    let previous = find_condition(conditions, &condition.r#type);
+    let transitioned = previous.map_or(true, |p| p.status != condition.status);
+
+    condition.last_transition_time = match previous {
+        Some(previous) if !transitioned => previous.last_transition_time.clone(),
+        _ => Some(now.to_rfc3339()),
+    };
+
+    conditions.retain(|c| c.r#type != condition.r#type);
+    conditions.push(condition);
+
+    transitioned
+}
+
+/// Sets the Ready, Reconciling and Degraded conditions so exactly the one matching `health` is
+/// true. All three carry `reason` and `message`.
+pub fn set_health(
+    conditions: &mut Vec<Condition>,
+    health: Health,
+    reason: &str,
+    message: impl Into<String>,
+    now: DateTime<Utc>,
+) {
+    let message = message.into();
+
+    for (type_, state) in [
+        (READY_CONDITION, Health::Ready),
+        (RECONCILING_CONDITION, Health::Reconciling),
+        (DEGRADED_CONDITION, Health::Degraded),
+    ] {
+        let status = if state == health {
+            CONDITION_TRUE
+        } else {
+            CONDITION_FALSE
+        };
+
+        set_condition(
+            conditions,
+            Condition::new(type_, status, reason, message.clone()),
+            now,
+        );
+    }
+}
+
+/// Collects the objects which were skipped or failed while reconciling a parent object, so they
+/// can be summarized in the parent's conditions instead of only being logged.
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    entries: Vec<(Health, String, String)>,
+}
+
+impl HealthReport {
+    /// Records an object which is waiting for work in progress.
+    pub fn reconciling(&mut self, reason: &str, message: impl Into<String>) {
+        self.entries
+            .push((Health::Reconciling, reason.to_string(), message.into()));
+    }
+
+    /// Records an object which failed to reconcile.
+    pub fn degraded(&mut self, reason: &str, message: impl Into<String>) {
+        self.entries
+            .push((Health::Degraded, reason.to_string(), message.into()));
+    }
+
+    /// Returns the worst health recorded, with the reason of the first object in that state and
+    /// the messages of all of them.
+    pub fn summary(&self) -> (Health, String, String) {
+        let Some(health) = self.entries.iter().map(|(health, ..)| *health).max() else {
+            return (
+                Health::Ready,
+                "Reconciled".to_string(),
+                "all objects are reconciled".to_string(),
+            );
+        };
+
+        let entries: Vec<&(Health, String, String)> =
+            self.entries.iter().filter(|(h, ..)| *h == health).collect();
+
+        let messages: Vec<&str> = entries
+            .iter()
+            .map(|(_, _, message)| message.as_str())
+            .collect();
+
+        (health, entries[0].1.clone(), messages.join("; "))
+    }
+
+    /// Summarizes the report in `conditions`, see [`set_health`].
+    pub fn set_conditions(&self, conditions: &mut Vec<Condition>, now: DateTime<Utc>) {
+        let (health, reason, message) = self.summary();
+
+        set_health(conditions, health, &reason, message, now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, TimeZone};
+
+    use super::*;
+
+    #[test]
+    fn transition_time_only_moves_when_the_status_changes() {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 10, 0, 0).unwrap();
+        let mut conditions = vec![];
+
+        assert!(set_condition(
+            &mut conditions,
+            Condition::new("Degraded", CONDITION_FALSE, "WithinThresholds", ""),
+            now,
+        ));
+        assert!(!set_condition(
+            &mut conditions,
+            Condition::new("Degraded", CONDITION_FALSE, "WithinThresholds", ""),
+            now + Duration::hours(1),
+        ));
+        assert_eq!(conditions[0].last_transition_time, Some(now.to_rfc3339()));
+
+        assert!(set_condition(
+            &mut conditions,
+            Condition::new("Degraded", CONDITION_TRUE, "ThresholdBreached", "drift"),
+            now + Duration::hours(2),
+        ));
+        assert_eq!(conditions.len(), 1);
+        assert!(find_condition(&conditions, "Degraded").unwrap().is_true());
+    }
+
+    #[test]
+    fn reports_summarize_the_worst_health() {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 10, 0, 0).unwrap();
+        let mut report = HealthReport::default();
+
+        assert_eq!(report.summary().0, Health::Ready);
+
+        report.reconciling("ValidationPending", "model a: validation is running");
+        report.degraded("DeploymentFailed", "model b: missing deployment image");
+        report.degraded("TaskFailed", "task c: invalid schedule");
+
+        let (health, reason, message) = report.summary();
+        assert_eq!(health, Health::Degraded);
+        assert_eq!(reason, "DeploymentFailed");
+        assert_eq!(
+            message,
+            "model b: missing deployment image; task c: invalid schedule"
+        );
+
+        let mut conditions = vec![];
+        report.set_conditions(&mut conditions, now);
+
+        assert_eq!(conditions.len(), 3);
+        assert!(!find_condition(&conditions, READY_CONDITION)
+            .unwrap()
+            .is_true());
+        assert!(find_condition(&conditions, DEGRADED_CONDITION)
+            .unwrap()
+            .is_true());
+    }
+}
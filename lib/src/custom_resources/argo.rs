@@ -21,7 +21,7 @@ use serde_json::json;
 use serde_tuple::*;
 use std::{collections::BTreeMap, default::Default};
 
-use super::new_task::TaskContext;
+use super::new_task::{TaskContext, WORKSPACE_STORAGE_RESOURCE};
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
 #[kube(
@@ -44,6 +44,64 @@ pub struct WorkflowSpec {
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema)]
 pub struct WorkflowStatus {
     pub phase: WorkflowPhase,
+
+    #[serde(default)]
+    pub nodes: BTreeMap<String, WorkflowNodeStatus>,
+}
+
+impl WorkflowStatus {
+    /// Returns the first failed step in the workflow, steps are identified by the name of their template.
+    pub fn failed_step(&self) -> Option<&WorkflowNodeStatus> {
+        self.nodes.values().find(|node| {
+            node.node_type.as_deref() == Some("Pod")
+                && matches!(node.phase.as_deref(), Some("Failed") | Some("Error"))
+        })
+    }
+
+    /// Returns the value of an output parameter produced by the step with the given name.
+    pub fn output_parameter(&self, step: &str, parameter: &str) -> Option<&str> {
+        self.nodes
+            .values()
+            .filter(|node| node.display_name == step)
+            .filter_map(|node| node.outputs.as_ref())
+            .flat_map(|outputs| outputs.parameters.iter())
+            .find(|p| p.name == parameter)
+            .and_then(|p| p.value.as_deref())
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowNodeStatus {
+    pub display_name: String,
+    #[serde(rename = "type")]
+    pub node_type: Option<String>,
+    pub phase: Option<String>,
+    pub message: Option<String>,
+    pub outputs: Option<WorkflowOutputs>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowOutputs {
+    #[serde(default)]
+    pub parameters: Vec<WorkflowParameter>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowParameter {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_from: Option<WorkflowParameterSource>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowParameterSource {
+    pub path: String,
 }
 
 // TODO: How do we handle WorkflowPhase unknown
@@ -65,6 +123,8 @@ pub struct WorkflowTemplate {
     pub security_context: Option<PodSecurityContext>,
     pub script: Option<ArgoScriptTemplate>,
     pub pod_spec_patch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outputs: Option<WorkflowOutputs>,
 }
 
 impl WorkflowTemplate {
@@ -76,6 +136,7 @@ impl WorkflowTemplate {
             security_context: None,
             script: None,
             pod_spec_patch: None,
+            outputs: None,
         }
     }
 
@@ -371,6 +432,7 @@ pub struct WorkflowTemplateBuilder<'a> {
     ctx: &'a TaskContext,
     script: String,
     name: String,
+    output_parameters: Vec<WorkflowParameter>,
 }
 
 impl<'a> WorkflowTemplateBuilder<'a> {
@@ -416,9 +478,20 @@ impl<'a> WorkflowTemplateBuilder<'a> {
             ctx,
             script,
             name,
+            output_parameters: vec![],
         })
     }
 
+    /// Exposes the contents of the file at `path` as an output parameter of the step.
+    pub fn add_output_parameter(&mut self, name: String, path: String) -> &mut Self {
+        self.output_parameters.push(WorkflowParameter {
+            name,
+            value: None,
+            value_from: Some(WorkflowParameterSource { path }),
+        });
+        self
+    }
+
     fn add_env_var(&mut self, var: EnvVar) -> &mut Self {
         self.env.push(var);
         self
@@ -457,6 +530,10 @@ impl<'a> WorkflowTemplateBuilder<'a> {
             }
         }
 
+        // Storage is provided by the workspace volume and is not a valid container resource.
+        let mut resource_limits = task.spec.cfg.resources.clone();
+        resource_limits.remove(WORKSPACE_STORAGE_RESOURCE);
+
         let script_template = ArgoScriptTemplate {
             source: self.script,
             container: serde_json::from_value(json!(
@@ -469,7 +546,7 @@ impl<'a> WorkflowTemplateBuilder<'a> {
                       }],
                       "env": self.env,
                       "resources": {
-                         "limits":  task.spec.cfg.resources,
+                         "limits":  resource_limits,
                     }
                     }
             ))?,
@@ -481,6 +558,9 @@ impl<'a> WorkflowTemplateBuilder<'a> {
             }
             ))?),
             script: Some(script_template),
+            outputs: (!self.output_parameters.is_empty()).then_some(WorkflowOutputs {
+                parameters: self.output_parameters,
+            }),
             ..WorkflowTemplate::new(self.name)
         })
     }
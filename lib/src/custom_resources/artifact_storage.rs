@@ -0,0 +1,57 @@
+use s3::{bucket::Bucket, creds::Credentials, Region};
+
+use crate::custom_resources::{model_registry::MODELS_PREFIX, Result};
+
+/// Prefix under which tasks store their artifacts and projects their files.
+static TASKS_PREFIX: &str = "tasks";
+
+/// The objects AME keeps in object storage for tasks, projects and models.
+pub struct ArtifactStorage {
+    bucket: Bucket,
+}
+
+impl ArtifactStorage {
+    pub fn new(
+        bucket_name: &str,
+        endpoint: String,
+        region: String,
+        access_id: &str,
+        secret: &str,
+    ) -> Result<Self> {
+        let bucket = Bucket::new(
+            bucket_name,
+            Region::Custom { region, endpoint },
+            Credentials::new(Some(access_id), Some(secret), None, None, None)?,
+        )?
+        .with_path_style();
+
+        Ok(Self { bucket })
+    }
+
+    /// Deletes every object under `prefix`, returning the number of objects deleted.
+    pub async fn delete_prefix(&self, prefix: &str) -> Result<usize> {
+        let keys: Vec<String> = self
+            .bucket
+            .list(prefix.to_string(), None)
+            .await?
+            .into_iter()
+            .flat_map(|r| r.contents.into_iter().map(|o| o.key))
+            .collect();
+
+        for key in keys.iter() {
+            self.bucket.delete_object(key).await?;
+        }
+
+        Ok(keys.len())
+    }
+}
+
+/// Prefix of the artifacts stored by the task named `task`.
+pub fn task_prefix(task: &str) -> String {
+    format!("{TASKS_PREFIX}/{task}/")
+}
+
+/// Prefix of the versions stored for `model` by the object storage model registry.
+pub fn model_prefix(model: &str) -> String {
+    format!("{MODELS_PREFIX}/{model}/")
+}
@@ -118,6 +118,43 @@ pub fn kind_is_project(kind: &str) -> bool {
     kind == "Project"
 }
 
+/// Maximum length of names which end up in labels, e.g. through the workflows and pods created
+/// for tasks.
+pub const MAX_NAME_LEN: usize = 63;
+
+/// Shortens `name` to at most `max_len` characters. Names which are too long are truncated and
+/// end in a hash of the full name, so different long names stay distinct.
+pub fn bounded_name(name: &str, max_len: usize) -> String {
+    if name.len() <= max_len {
+        return name.to_string();
+    }
+
+    let prefix: String = name.chars().take(max_len.saturating_sub(9)).collect();
+
+    format!(
+        "{}-{}",
+        prefix.trim_end_matches(['-', '.']),
+        short_hash(name)
+    )
+}
+
+/// Returns a hash of `value` as 8 hexadecimal characters.
+///
+/// The hash is FNV-1a, names derived from it must not change between releases so the hasher in
+/// the standard library is not used.
+pub fn short_hash(value: &str) -> String {
+    let hash = value.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+
+    format!("{hash:08x}")
+}
+
+/// Quotes `value` so a shell treats it as a single word, whatever characters it contains.
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
 /// Prepare a cluster for tests, under the assumptions that the `just setup_cluster` recipe has been run successfully.
 /// This implies that all required custom resource definitions are installed in the cluster.
 /// This function will generate clients and clear all Task and `Workflow` objects in the cluster.
@@ -275,3 +312,27 @@ pub async fn setup_cluster(
 pub fn private_repo_gh_pat() -> Result<String, Box<dyn std::error::Error>> {
     Ok(std::env::var("AME_TEST_GH_TOKEN")?)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn long_names_are_shortened_without_colliding() {
+        let long = "a".repeat(80);
+        let other = format!("{}b", "a".repeat(79));
+
+        assert_eq!(bounded_name("train", MAX_NAME_LEN), "train");
+        assert_eq!(bounded_name(&long, MAX_NAME_LEN).len(), MAX_NAME_LEN);
+        assert_ne!(
+            bounded_name(&long, MAX_NAME_LEN),
+            bounded_name(&other, MAX_NAME_LEN)
+        );
+    }
+
+    #[test]
+    fn quoted_values_are_a_single_shell_word() {
+        assert_eq!(shell_quote("data/train.csv"), "'data/train.csv'");
+        assert_eq!(shell_quote("it's $HOME"), "'it'\\''s $HOME'");
+    }
+}
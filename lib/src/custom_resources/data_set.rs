@@ -1,4 +1,19 @@
-use crate::{custom_resources::new_task::Task, grpc::TaskCfg, AmeError, Result};
+use std::fmt::{self, Display};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    condition::{set_health, Health},
+    custom_resources::{
+        common::{parent_project, shell_quote},
+        new_task::Task,
+    },
+    grpc::{
+        task_cfg::Executor, Condition, DataSetInfo, DataSetValidation, RowCountRule, TaskCfg,
+        TaskPhaseFailed, TaskPhaseSucceeded,
+    },
+    AmeError, Result,
+};
 
 use kube::{core::ObjectMeta, Client, CustomResource, Resource, ResourceExt};
 use schemars::JsonSchema;
@@ -8,6 +23,15 @@ use crate::grpc::{task_status::Phase, DataSetCfg};
 
 use super::new_task::{ProjectSource, TaskSpec};
 
+/// Name of the workflow step which validates a data set after it has been generated.
+pub static DATA_SET_VALIDATION_STEP: &str = "validatedataset";
+
+/// Name of the workflow step which measures the size of a data set after it has been generated.
+pub static DATA_SET_MEASURE_STEP: &str = "measuredataset";
+
+/// Name of the output parameter the measured size, in bytes, is exposed as.
+pub static DATA_SET_SIZE_PARAMETER: &str = "size";
+
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 #[kube(
     kind = "DataSet",
@@ -25,8 +49,114 @@ pub struct DataSetSpec {
 }
 
 #[derive(Debug, Clone, JsonSchema, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct DataSetStatus {
     pub phase: Option<DataSetPhase>,
+
+    /// Size of the data set in bytes as measured after it was generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+
+    /// When the data set last became Ready, identifies the current version of the data set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_time: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
+}
+
+impl DataSetStatus {
+    pub fn from_task(task: Task) -> Self {
+        let size_bytes = match task.status.as_ref().and_then(|s| s.phase.as_ref()) {
+            Some(Phase::Succeeded(TaskPhaseSucceeded {
+                data_set_size_bytes,
+                ..
+            })) => *data_set_size_bytes,
+            _ => None,
+        };
+
+        DataSetStatus {
+            phase: Some(DataSetPhase::from_task(task)),
+            size_bytes,
+            ..DataSetStatus::default()
+        }
+    }
+
+    /// Sets the Ready, Reconciling and Degraded conditions from the phase, keeping the transition
+    /// times of the `previous` status' conditions.
+    pub fn with_conditions(mut self, previous: Option<&DataSetStatus>, now: DateTime<Utc>) -> Self {
+        let Some(ref phase) = self.phase else {
+            return self;
+        };
+
+        let (health, reason, message) = match phase {
+            DataSetPhase::Pending {} => (
+                Health::Reconciling,
+                "Pending",
+                "waiting for the data set task to be created".to_string(),
+            ),
+            DataSetPhase::RunningTask { task_name } => (
+                Health::Reconciling,
+                "TaskRunning",
+                format!("task {task_name} is generating the data set"),
+            ),
+            DataSetPhase::WaitingForDataSets { data_sets } => (
+                Health::Reconciling,
+                "WaitingForDataSets",
+                format!("waiting for data sets {}", data_sets.join(", ")),
+            ),
+            DataSetPhase::Ready { task_name } => (
+                Health::Ready,
+                "DataSetReady",
+                format!("task {task_name} generated the data set"),
+            ),
+            DataSetPhase::Failed { task_name } => (
+                Health::Degraded,
+                "TaskFailed",
+                format!("task {task_name} failed"),
+            ),
+            DataSetPhase::ValidationFailed { task_name, rule } => (
+                Health::Degraded,
+                "ValidationFailed",
+                format!("task {task_name} failed validation rule {rule}"),
+            ),
+            DataSetPhase::DependencyCycle { cycle } => (
+                Health::Degraded,
+                "DependencyCycle",
+                format!("data sets depend on each other: {}", cycle.join(" -> ")),
+            ),
+            DataSetPhase::MissingDataSets { data_sets } => (
+                Health::Degraded,
+                "MissingDataSets",
+                format!("upstream data sets {} do not exist", data_sets.join(", ")),
+            ),
+        };
+
+        self.conditions = previous.map(|p| p.conditions.clone()).unwrap_or_default();
+        set_health(&mut self.conditions, health, reason, message, now);
+
+        self
+    }
+
+    /// Sets the time the data set became Ready, a data set which was already Ready in the
+    /// `previous` status keeps its ready time so the version only changes when the data set
+    /// is generated again.
+    pub fn with_ready_time(mut self, previous: Option<&DataSetStatus>, now: DateTime<Utc>) -> Self {
+        if !matches!(self.phase, Some(DataSetPhase::Ready { .. })) {
+            return self;
+        }
+
+        self.ready_time = match previous {
+            Some(DataSetStatus {
+                phase: Some(DataSetPhase::Ready { .. }),
+                ready_time: Some(ready_time),
+                ..
+            }) => Some(ready_time.clone()),
+            _ => Some(now.to_rfc3339()),
+        };
+
+        self
+    }
 }
 
 #[derive(Debug, Clone, JsonSchema, Serialize, Deserialize)]
@@ -36,6 +166,10 @@ pub enum DataSetPhase {
     RunningTask { task_name: String },
     Ready { task_name: String },
     Failed { task_name: String },
+    ValidationFailed { task_name: String, rule: String },
+    WaitingForDataSets { data_sets: Vec<String> },
+    DependencyCycle { cycle: Vec<String> },
+    MissingDataSets { data_sets: Vec<String> },
 }
 
 impl DataSetPhase {
@@ -50,11 +184,39 @@ impl DataSetPhase {
         match task_phase {
             Phase::Running(_) | Phase::Pending(_) => DataSetPhase::RunningTask { task_name },
             Phase::Succeeded(_) => DataSetPhase::Ready { task_name },
+            Phase::Failed(TaskPhaseFailed {
+                failed_step: Some(ref step),
+                ref message,
+                ..
+            }) if step == DATA_SET_VALIDATION_STEP => DataSetPhase::ValidationFailed {
+                task_name,
+                rule: message
+                    .as_deref()
+                    .map(failed_validation_rule)
+                    .unwrap_or_default(),
+            },
             Phase::Failed(_) => DataSetPhase::Failed { task_name },
         }
     }
 }
 
+impl Display for DataSetPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let output = match self {
+            DataSetPhase::Pending {} => "Pending",
+            DataSetPhase::RunningTask { .. } => "Running",
+            DataSetPhase::Ready { .. } => "Ready",
+            DataSetPhase::Failed { .. } => "Failed",
+            DataSetPhase::ValidationFailed { .. } => "ValidationFailed",
+            DataSetPhase::WaitingForDataSets { .. } => "WaitingForDataSets",
+            DataSetPhase::DependencyCycle { .. } => "DependencyCycle",
+            DataSetPhase::MissingDataSets { .. } => "MissingDataSets",
+        };
+
+        write!(f, "{output}")
+    }
+}
+
 impl Default for DataSetPhase {
     fn default() -> Self {
         DataSetPhase::Pending {}
@@ -95,6 +257,37 @@ impl DataSet {
             .unwrap_or_default()
     }
 
+    /// Summarises the data set for clients, files in object storage are not included.
+    pub fn info(&self) -> Result<DataSetInfo> {
+        let project = match self.spec.project {
+            Some(ref project) => project.clone(),
+            None => parent_project(self.owner_references().to_vec())?,
+        };
+
+        Ok(DataSetInfo {
+            name: self.spec.cfg.name.clone(),
+            project,
+            cfg: Some(self.spec.cfg.clone()),
+            phase: self.phase().to_string(),
+            size_bytes: self.status.as_ref().and_then(|s| s.size_bytes),
+            files: vec![],
+        })
+    }
+
+    /// Returns when the data set last became Ready, `None` is returned if it is not Ready.
+    pub fn ready_time(&self) -> Option<DateTime<Utc>> {
+        match self.status {
+            Some(DataSetStatus {
+                phase: Some(DataSetPhase::Ready { .. }),
+                ready_time: Some(ref ready_time),
+                ..
+            }) => DateTime::parse_from_rfc3339(ready_time)
+                .ok()
+                .map(|t| t.with_timezone(&Utc)),
+            _ => None,
+        }
+    }
+
     pub fn task_cfg(&self) -> &Option<TaskCfg> {
         &self.spec.cfg.task
     }
@@ -119,9 +312,12 @@ impl DataSet {
         let mut spec = TaskSpec::from(task_cfg.clone());
 
         spec.project = self.spec.project.clone();
-        if let Some(repo) = self.annotations().get("gitrepository") {
-            spec.source = Some(ProjectSource::from_public_git_repo(repo.to_string()));
-        }
+        spec.data_set_validation = self.validation_spec();
+        spec.data_set_output = Some(DataSetOutputSpec {
+            path: self.spec.cfg.path.clone(),
+            size: self.spec.cfg.size.clone(),
+        });
+        spec.source = ProjectSource::from_annotations(self.annotations());
 
         let metadata = ObjectMeta {
             name: Some(format!("{}{}", self.name_any(), name)),
@@ -135,4 +331,398 @@ impl DataSet {
             status: None,
         })
     }
+
+    /// Returns the size of the data set in bytes, preferring the measured size over the
+    /// configured one. `None` is returned if neither is known.
+    pub fn size_bytes(&self) -> Result<Option<u64>> {
+        if let Some(size) = self.status.as_ref().and_then(|s| s.size_bytes) {
+            return Ok(Some(size));
+        }
+
+        self.spec
+            .cfg
+            .size
+            .as_deref()
+            .map(quantity_to_bytes)
+            .transpose()
+    }
+
+    fn validation_spec(&self) -> Option<DataSetValidationSpec> {
+        self.spec
+            .cfg
+            .validation
+            .clone()
+            .map(|rules| DataSetValidationSpec {
+                path: self.spec.cfg.path.clone(),
+                rules,
+            })
+    }
+}
+
+/// Describes the data set a task generates, used to size the task's workspace and to
+/// measure the data set once it has been generated.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataSetOutputSpec {
+    pub path: String,
+    pub size: Option<String>,
+}
+
+impl DataSetOutputSpec {
+    /// Generates a script which writes the size of the data set in bytes to `output_path`.
+    pub fn measure_command(&self, output_path: &str) -> String {
+        let path = &self.path;
+        format!("du -sb \"{path}\" | cut -f1 > {output_path}")
+    }
+}
+
+/// The validation rules for a data set together with the path the data set is generated at,
+/// this is all a task needs in order to validate the data set it produces.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DataSetValidationSpec {
+    pub path: String,
+    pub rules: DataSetValidation,
+}
+
+impl DataSetValidationSpec {
+    /// Generates a script which checks every rule in turn, within the environment of the task's
+    /// `executor` so rules and the validation command can use the project's dependencies.
+    ///
+    /// The first rule to fail is written to the container's termination message, so it
+    /// ends up in the message of the failed workflow node and can be surfaced by the
+    /// controllers.
+    pub fn command(&self, executor: Option<&Executor>) -> Result<String> {
+        let mut checks: Vec<String> = executor
+            .map(Executor::environment_command)
+            .into_iter()
+            .collect();
+
+        checks.push(format!(
+            "fail() {{
+                echo \"$1\" > /dev/termination-log
+                echo \"data set validation failed: $1\"
+                exit 1
+            }}
+
+set -o pipefail
+path={}",
+            shell_quote(&self.path)
+        ));
+
+        for file in self.rules.expected_files.iter() {
+            checks.push(format!(
+                "file={}
+test -e \"$path/$file\" || fail \"expectedFiles: $file is missing\"",
+                shell_quote(file)
+            ));
+        }
+
+        if let Some(ref min_size) = self.rules.min_size {
+            let min_bytes = quantity_to_bytes(min_size)?;
+            checks.push(format!(
+                "size=$(du -sb \"$path\" | cut -f1) || fail \"minSize: could not measure $path\"
+                [ \"$size\" -ge {min_bytes} ] || fail \"minSize: $path is $size bytes, expected at least {min_size}\""
+            ));
+        }
+
+        for rule in self.rules.row_counts.iter() {
+            checks.push(rule.command()?);
+        }
+
+        if let Some(ref command) = self.rules.command {
+            checks.push(format!(
+                "(export AME_DATA_SET_PATH=\"$path\"; {command}) || fail \"command: validation command exited with $?\""
+            ));
+        }
+
+        Ok(checks.join("\n\n"))
+    }
+}
+
+impl RowCountRule {
+    /// Generates a script which counts the rows of the rule's file in the data set at `$path`.
+    fn command(&self) -> Result<String> {
+        let file = &self.file;
+        let mut cmd = format!(
+            "file={}
+test -f \"$path/$file\" || fail \"rowCounts: $file is missing\"",
+            shell_quote(file)
+        );
+
+        let count = if file.ends_with(".csv") {
+            "tail -n +2 \"$path/$file\" | wc -l".to_string()
+        } else if file.ends_with(".parquet") {
+            // pyarrow must be one of the project's dependencies, the validation step can not rely
+            // on network access to install it.
+            cmd = format!("{cmd}\npython -c \"import pyarrow\" 2>/dev/null || fail \"rowCounts: counting rows in $file requires pyarrow in the project's environment\"");
+            "python -c \"import sys, pyarrow.parquet as pq; print(pq.ParquetFile(sys.argv[1]).metadata.num_rows)\" \"$path/$file\"".to_string()
+        } else {
+            return Err(AmeError::UnsupportedRowCountFormat(file.clone()));
+        };
+
+        cmd =
+            format!("{cmd}\nrows=$({count}) || fail \"rowCounts: could not count rows in $file\"");
+
+        if let Some(min) = self.min {
+            cmd = format!("{cmd}\n[ \"$rows\" -ge {min} ] || fail \"rowCounts: $file has $rows rows, expected at least {min}\"");
+        }
+
+        if let Some(max) = self.max {
+            cmd = format!("{cmd}\n[ \"$rows\" -le {max} ] || fail \"rowCounts: $file has $rows rows, expected at most {max}\"");
+        }
+
+        Ok(cmd)
+    }
+}
+
+/// Extracts the failing rule from the message Argo attaches to a failed node,
+/// e.g. `Error (exit code 1): expectedFiles: train.csv is missing`.
+fn failed_validation_rule(message: &str) -> String {
+    message
+        .split_once("): ")
+        .map(|(_, rule)| rule)
+        .unwrap_or(message)
+        .trim()
+        .to_string()
+}
+
+/// Converts a Kubernetes style quantity such as `10Gi` or `500M` into bytes.
+pub fn quantity_to_bytes(quantity: &str) -> Result<u64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "k" => 1000,
+        "M" => 1000u64.pow(2),
+        "G" => 1000u64.pow(3),
+        "T" => 1000u64.pow(4),
+        "Ki" => 1024,
+        "Mi" => 1024u64.pow(2),
+        "Gi" => 1024u64.pow(3),
+        "Ti" => 1024u64.pow(4),
+        _ => return Err(AmeError::InvalidQuantity(quantity.to_string())),
+    };
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| AmeError::InvalidQuantity(quantity.to_string()))?;
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grpc::{TaskPhaseFailed, TaskStatus};
+    use assert_fs::prelude::*;
+
+    fn failed_task(failed_step: Option<String>, message: Option<String>) -> Task {
+        Task {
+            metadata: ObjectMeta {
+                name: Some("datatask".to_string()),
+                ..ObjectMeta::default()
+            },
+            spec: TaskSpec::default(),
+            status: Some(TaskStatus {
+                phase: Some(Phase::Failed(TaskPhaseFailed {
+                    workflow_name: "datatask".to_string(),
+                    failed_step,
+                    message,
+                })),
+                ..TaskStatus::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn failed_validation_step_carries_rule() {
+        let task = failed_task(
+            Some(DATA_SET_VALIDATION_STEP.to_string()),
+            Some("Error (exit code 1): expectedFiles: train.csv is missing".to_string()),
+        );
+
+        let DataSetPhase::ValidationFailed { task_name, rule } = DataSetPhase::from_task(task)
+        else {
+            panic!("expected validation failure");
+        };
+
+        assert_eq!(task_name, "datatask");
+        assert_eq!(rule, "expectedFiles: train.csv is missing");
+    }
+
+    #[test]
+    fn failed_task_step_is_not_a_validation_failure() {
+        let task = failed_task(Some("datatask".to_string()), None);
+
+        assert!(matches!(
+            DataSetPhase::from_task(task),
+            DataSetPhase::Failed { .. }
+        ));
+    }
+
+    #[test]
+    fn can_convert_quantities_to_bytes() -> Result<()> {
+        assert_eq!(quantity_to_bytes("512")?, 512);
+        assert_eq!(quantity_to_bytes("10k")?, 10_000);
+        assert_eq!(quantity_to_bytes("1.5Gi")?, 1_610_612_736);
+        assert_eq!(quantity_to_bytes("2Mi")?, 2_097_152);
+        assert!(quantity_to_bytes("2Zi").is_err());
+        assert!(quantity_to_bytes("Mi").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_unsupported_row_count_formats() {
+        let spec = DataSetValidationSpec {
+            path: "data".to_string(),
+            rules: DataSetValidation {
+                row_counts: vec![RowCountRule {
+                    file: "train.json".to_string(),
+                    min: Some(1),
+                    max: None,
+                }],
+                ..DataSetValidation::default()
+            },
+        };
+
+        assert!(spec.command(None).is_err());
+    }
+
+    #[test]
+    fn parquet_row_counts_require_pyarrow_in_the_project_environment() -> Result<()> {
+        let rule = RowCountRule {
+            file: "train.parquet".to_string(),
+            min: Some(10),
+            max: None,
+        };
+
+        let command = rule.command()?;
+        let lines: Vec<&str> = command.lines().collect();
+
+        assert_eq!(lines[0], "file='train.parquet'");
+        assert!(lines[2].starts_with("python -c \"import pyarrow\" 2>/dev/null || fail"));
+        assert!(!command.contains("pip install"));
+        assert!(lines[3].contains("ParquetFile(sys.argv[1])"));
+        assert!(lines[4].contains("[ \"$rows\" -ge 10 ]"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn validation_fails_for_missing_files_and_quotes_paths() -> Result<()> {
+        let dir = assert_fs::TempDir::new().unwrap();
+        dir.child("my data/train.csv")
+            .write_str("a,b\n1,2\n3,4\n")
+            .unwrap();
+
+        let validate = |file: &str, min: u64| {
+            let spec = DataSetValidationSpec {
+                path: "my data".to_string(),
+                rules: DataSetValidation {
+                    row_counts: vec![RowCountRule {
+                        file: file.to_string(),
+                        min: Some(min),
+                        max: None,
+                    }],
+                    ..DataSetValidation::default()
+                },
+            };
+
+            let script = spec
+                .command(None)
+                .unwrap()
+                .replace("/dev/termination-log", "/dev/null");
+
+            std::process::Command::new("bash")
+                .arg("-c")
+                .arg(script)
+                .current_dir(dir.path())
+                .output()
+                .unwrap()
+        };
+
+        assert!(validate("train.csv", 2).status.success());
+        assert!(!validate("train.csv", 3).status.success());
+
+        let missing = validate("it's missing.csv", 0);
+        assert!(!missing.status.success());
+        assert!(String::from_utf8_lossy(&missing.stdout)
+            .contains("rowCounts: it's missing.csv is missing"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn measured_size_takes_precedence_over_configured_size() -> Result<()> {
+        let mut data_set = DataSet::from_cfg(
+            "data",
+            DataSetCfg {
+                size: Some("1Gi".to_string()),
+                ..DataSetCfg::default()
+            },
+        );
+
+        assert_eq!(data_set.size_bytes()?, Some(1024u64.pow(3)));
+
+        data_set.status = Some(DataSetStatus {
+            size_bytes: Some(512),
+            ..DataSetStatus::default()
+        });
+
+        assert_eq!(data_set.size_bytes()?, Some(512));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ready_time_only_changes_when_the_data_set_becomes_ready() {
+        let ready = |ready_time: Option<&str>| DataSetStatus {
+            phase: Some(DataSetPhase::Ready {
+                task_name: "datatask".to_string(),
+            }),
+            ready_time: ready_time.map(String::from),
+            ..DataSetStatus::default()
+        };
+        let now = DateTime::parse_from_rfc3339("2023-08-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let status =
+            ready(None).with_ready_time(Some(&ready(Some("2023-08-01T00:00:00+00:00"))), now);
+        assert_eq!(
+            status.ready_time.as_deref(),
+            Some("2023-08-01T00:00:00+00:00")
+        );
+
+        let status = ready(None).with_ready_time(Some(&DataSetStatus::default()), now);
+        assert_eq!(status.ready_time, Some(now.to_rfc3339()));
+
+        let status = DataSetStatus::default().with_ready_time(None, now);
+        assert_eq!(status.ready_time, None);
+    }
+
+    #[test]
+    fn failed_data_sets_are_degraded() {
+        let now = DateTime::parse_from_rfc3339("2023-08-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let status = DataSetStatus::from_task(failed_task(Some("datatask".to_string()), None))
+            .with_conditions(None, now);
+
+        let degraded = crate::condition::find_condition(
+            &status.conditions,
+            crate::condition::DEGRADED_CONDITION,
+        )
+        .unwrap();
+
+        assert!(degraded.is_true());
+        assert_eq!(degraded.reason.as_deref(), Some("TaskFailed"));
+    }
 }
@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    error::AmeError,
+    grpc::{DataSetCfg, DataSetGraph, DataSetGraphNode, ProjectCfg},
+    Result,
+};
+
+use super::project::{local_name, namespace_name, project_name};
+
+/// Returns the fully qualified name of a data set reference, i.e `project.dataset`.
+///
+/// References without a project are resolved relative to `project`.
+pub fn qualified_data_set_name(project: &str, data_set_ref: &str) -> String {
+    let project = project_name(data_set_ref.to_string()).unwrap_or(project.to_string());
+
+    format!("{}.{}", project, local_name(data_set_ref.to_string()))
+}
+
+/// Returns references to the data sets which have to be generated before this data set.
+///
+/// Data sets shared from other namespaces are generated by their own projects and are left
+/// out of the graph.
+fn data_set_dependencies(project: &ProjectCfg, data_set: &DataSetCfg) -> Vec<String> {
+    let Some(ref task) = data_set.task else {
+        return vec![];
+    };
+
+    let mut dependencies = task.data_sets.clone();
+
+    if let Some(task_cfg) = task
+        .task_ref
+        .as_ref()
+        .and_then(|task_ref| project.get_task_cfg(&task_ref.name))
+    {
+        dependencies.extend(task_cfg.data_sets);
+    }
+
+    dependencies
+        .into_iter()
+        .filter(|ds| namespace_name(ds.clone()).is_none())
+        .collect()
+}
+
+impl DataSetGraph {
+    /// Builds the graph of every data set in `root` and the data sets they depend on,
+    /// following references into the other `projects`.
+    pub fn from_projects(root: &ProjectCfg, projects: &[ProjectCfg]) -> Result<Self> {
+        let pending = root
+            .data_sets
+            .iter()
+            .map(|ds| qualified_data_set_name(&root.name, &ds.name))
+            .collect();
+
+        let (graph, missing) = DataSetGraph::build(root, projects, pending);
+
+        match missing.first() {
+            Some(name) => Err(AmeError::MissingDataSet(
+                local_name(name.clone()),
+                project_name(name.clone()).unwrap_or_default(),
+            )),
+            None => Ok(graph),
+        }
+    }
+
+    /// Builds the graph of the data set `name` in `root` and the data sets upstream of it,
+    /// following references into the other `projects`.
+    ///
+    /// Upstream data sets which do not exist are returned next to the graph, the rest of the
+    /// projects' data sets are not looked at.
+    pub fn from_data_set(
+        root: &ProjectCfg,
+        projects: &[ProjectCfg],
+        name: &str,
+    ) -> (Self, Vec<String>) {
+        DataSetGraph::build(
+            root,
+            projects,
+            vec![qualified_data_set_name(&root.name, name)],
+        )
+    }
+
+    fn build(
+        root: &ProjectCfg,
+        projects: &[ProjectCfg],
+        mut pending: Vec<String>,
+    ) -> (Self, Vec<String>) {
+        let mut nodes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut missing = BTreeSet::new();
+
+        while let Some(name) = pending.pop() {
+            if nodes.contains_key(&name) || missing.contains(&name) {
+                continue;
+            }
+
+            let project_name = project_name(name.clone()).unwrap_or_default();
+            let data_set_name = local_name(name.clone());
+
+            let Some((project, data_set)) = std::iter::once(root)
+                .chain(projects.iter())
+                .find(|p| p.name == project_name)
+                .and_then(|p| {
+                    p.data_sets
+                        .iter()
+                        .find(|ds| ds.name == data_set_name)
+                        .map(|ds| (p, ds))
+                })
+            else {
+                missing.insert(name);
+                continue;
+            };
+
+            let dependencies: Vec<String> = data_set_dependencies(project, data_set)
+                .iter()
+                .map(|ds| qualified_data_set_name(&project.name, ds))
+                .collect();
+
+            pending.extend(dependencies.clone());
+            nodes.insert(name, dependencies);
+        }
+
+        let graph = DataSetGraph {
+            nodes: nodes
+                .into_iter()
+                .map(|(name, dependencies)| DataSetGraphNode { name, dependencies })
+                .collect(),
+        };
+
+        (graph, missing.into_iter().collect())
+    }
+
+    pub fn dependencies(&self, name: &str) -> Option<&[String]> {
+        self.nodes
+            .iter()
+            .find(|n| n.name == name)
+            .map(|n| n.dependencies.as_slice())
+    }
+
+    /// Returns the data sets in the order they have to be generated, upstream data sets
+    /// come before the data sets that depend on them.
+    ///
+    /// Fails with the offending cycle if the data sets can not be ordered.
+    pub fn generation_order(&self) -> Result<Vec<String>> {
+        let mut order = vec![];
+        let mut done = BTreeSet::new();
+
+        for node in self.nodes.iter() {
+            self.visit(&node.name, &mut vec![], &mut done, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    /// Returns every data set `name` depends on, directly or indirectly, in the order they
+    /// have to be generated.
+    pub fn upstream_of(&self, name: &str) -> Result<Vec<String>> {
+        let mut order = vec![];
+
+        self.visit(name, &mut vec![], &mut BTreeSet::new(), &mut order)?;
+        order.pop();
+
+        Ok(order)
+    }
+
+    fn visit(
+        &self,
+        name: &str,
+        path: &mut Vec<String>,
+        done: &mut BTreeSet<String>,
+        order: &mut Vec<String>,
+    ) -> Result<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+
+        if let Some(start) = path.iter().position(|n| n == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(AmeError::DataSetCycle(cycle));
+        }
+
+        path.push(name.to_string());
+
+        for dependency in self.dependencies(name).unwrap_or_default() {
+            self.visit(dependency, path, done, order)?;
+        }
+
+        path.pop();
+        done.insert(name.to_string());
+        order.push(name.to_string());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grpc::TaskCfg;
+
+    fn data_set(name: &str, dependencies: &[&str]) -> DataSetCfg {
+        DataSetCfg {
+            name: name.to_string(),
+            task: Some(TaskCfg {
+                data_sets: dependencies.iter().map(|d| d.to_string()).collect(),
+                ..TaskCfg::default()
+            }),
+            ..DataSetCfg::default()
+        }
+    }
+
+    fn project(name: &str, data_sets: Vec<DataSetCfg>) -> ProjectCfg {
+        ProjectCfg {
+            name: name.to_string(),
+            data_sets,
+            ..ProjectCfg::default()
+        }
+    }
+
+    #[test]
+    fn orders_upstream_data_sets_first() -> Result<()> {
+        let root = project(
+            "features",
+            vec![
+                data_set("joined", &["cleaned", "raw.events"]),
+                data_set("cleaned", &["raw.events"]),
+            ],
+        );
+        let raw = project("raw", vec![data_set("events", &[])]);
+
+        let graph = DataSetGraph::from_projects(&root, &[raw])?;
+
+        assert_eq!(
+            graph.dependencies("features.joined"),
+            Some(["features.cleaned".to_string(), "raw.events".to_string()].as_slice())
+        );
+        assert_eq!(
+            graph.generation_order()?,
+            vec!["raw.events", "features.cleaned", "features.joined"]
+        );
+        assert_eq!(
+            graph.upstream_of("features.joined")?,
+            vec!["raw.events", "features.cleaned"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn detects_cycles() -> Result<()> {
+        let root = project(
+            "features",
+            vec![
+                data_set("a", &["b"]),
+                data_set("b", &["c"]),
+                data_set("c", &["a"]),
+            ],
+        );
+
+        let graph = DataSetGraph::from_projects(&root, &[])?;
+
+        let Err(AmeError::DataSetCycle(cycle)) = graph.generation_order() else {
+            panic!("expected a cycle");
+        };
+
+        assert_eq!(
+            cycle,
+            vec!["features.a", "features.b", "features.c", "features.a"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_upstream_data_set_is_an_error() {
+        let root = project("features", vec![data_set("a", &["raw.events"])]);
+
+        assert!(matches!(
+            DataSetGraph::from_projects(&root, &[]),
+            Err(AmeError::MissingDataSet(..))
+        ));
+    }
+
+    #[test]
+    fn data_set_graph_only_follows_its_upstream_data_sets() {
+        let root = project(
+            "features",
+            vec![
+                data_set("joined", &["cleaned"]),
+                data_set("cleaned", &["raw.events"]),
+                data_set("broken", &["raw.missing"]),
+            ],
+        );
+        let raw = project("raw", vec![data_set("events", &[])]);
+
+        let (graph, missing) =
+            DataSetGraph::from_data_set(&root, std::slice::from_ref(&raw), "joined");
+
+        assert!(missing.is_empty());
+        assert_eq!(graph.dependencies("features.broken"), None);
+        assert_eq!(
+            graph.upstream_of("features.joined").unwrap(),
+            vec!["raw.events", "features.cleaned"]
+        );
+
+        let (_, missing) = DataSetGraph::from_data_set(&root, &[raw], "broken");
+
+        assert_eq!(missing, vec!["raw.missing"]);
+    }
+
+    #[test]
+    fn data_sets_from_other_namespaces_are_not_part_of_the_graph() -> Result<()> {
+        let root = project("features", vec![data_set("a", &["team-b/raw.events"])]);
+
+        let graph = DataSetGraph::from_projects(&root, &[])?;
+
+        assert_eq!(graph.dependencies("features.a"), Some([].as_slice()));
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@ use crate::grpc::*;
 use k8s_openapi::chrono::{OutOfRangeError, ParseError};
 use kube::{api::ListParams, core::ObjectMeta, Api, ResourceExt};
 use new_task::Task;
+use s3::{creds::error::CredentialsError, error::S3Error};
 use secrets::SecretError;
 use std::env::VarError;
 use thiserror::Error;
@@ -10,8 +11,11 @@ use self::project_source::{ProjectSource, ProjectSourceSpec};
 use project::Project;
 
 pub mod argo;
+pub mod artifact_storage;
 pub mod common;
 pub mod data_set;
+pub mod data_set_graph;
+pub mod model_registry;
 pub mod new_task;
 pub mod project;
 pub mod project_source;
@@ -58,12 +62,33 @@ pub enum Error {
     #[error("No model deployment was found")]
     MissingDeployment(),
 
+    #[error("Invalid autoscaling configuration for model {0}: {1}")]
+    InvalidAutoscaling(String, String),
+
+    #[error("Invalid serving runtime for model {0}: {1}")]
+    InvalidServingRuntime(String, String),
+
+    #[error("Invalid batch inference configuration for model {0}: {1}")]
+    InvalidBatchInference(String, String),
+
+    #[error("Invalid monitoring configuration for model {0}: {1}")]
+    InvalidMonitoring(String, String),
+
     #[error("No matching template found was found: {0} {1}")]
     MissingTemplate(String, String),
 
     #[error("No Mlfow URL was found")]
     MissingMlflowUrl(),
 
+    #[error("failed to find a registered version of model: {0}")]
+    MissingModelVersion(String),
+
+    #[error("got error from object storage: {0}")]
+    ObjectStorageError(#[from] S3Error),
+
+    #[error("got error from object storage credentials: {0}")]
+    ObjectStorageCredentialError(#[from] CredentialsError),
+
     #[error("Request error: {0}")]
     RequestError(#[from] reqwest::Error),
 
@@ -100,6 +125,9 @@ pub enum Error {
     #[error("failed to find AME file project source with name : {0}")]
     MissingAmeFile(String),
 
+    #[error("Invalid project file:\n{0}")]
+    InvalidProjectFile(#[from] crate::validation::Diagnostics),
+
     #[error("Task {0} is missing an executor")]
     MissingExecutor(String),
 
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use k8s_openapi::api::core::v1::EnvVar;
+use s3::{bucket::Bucket, creds::Credentials, error::S3Error, Region};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::custom_resources::{Error, Result};
+
+/// Tracking URI model deployments use to reach the in-cluster MLflow server.
+pub static DEFAULT_MLFLOW_TRACKING_URI: &str = "http://mlflow.default.svc.cluster.local:5000";
+
+/// Endpoint model deployments use to reach the in-cluster object storage.
+pub static DEFAULT_OBJECT_STORAGE_ENDPOINT: &str =
+    "http://ame-minio.ame-system.svc.cluster.local:9000";
+
+/// Prefix under which the object storage registry keeps models.
+pub(crate) static MODELS_PREFIX: &str = "models";
+
+/// Name of the object listing the versions of a model in the object storage registry.
+static VERSIONS_FILE: &str = "versions.json";
+
+/// A registered version of a model, the fields follow MLflow's model version representation.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+pub struct ModelVersion {
+    pub name: String,
+    pub version: String,
+
+    #[serde(default)]
+    pub current_stage: String,
+
+    #[serde(default)]
+    pub creation_timestamp: i64,
+
+    pub source: String,
+
+    #[serde(default)]
+    pub run_id: String,
+}
+
+/// A registry keeping track of model versions and where their artifacts are stored.
+#[async_trait]
+pub trait ModelRegistry: Send + Sync {
+    /// Returns the most recently created version of `model`.
+    async fn latest_version(&self, model: &str) -> Result<ModelVersion> {
+        self.list_versions(model)
+            .await?
+            .into_iter()
+            .max_by_key(|v| v.creation_timestamp)
+            .ok_or(Error::MissingModelVersion(model.to_string()))
+    }
+
+    /// Returns a specific version of `model`, e.g. a version the model is pinned to.
+    async fn find_version(&self, model: &str, version: &str) -> Result<ModelVersion> {
+        self.list_versions(model)
+            .await?
+            .into_iter()
+            .find(|v| v.version == version)
+            .ok_or(Error::MissingModelVersion(format!("{model} {version}")))
+    }
+
+    async fn list_versions(&self, model: &str) -> Result<Vec<ModelVersion>>;
+
+    /// Moves a version of `model` to `stage`, e.g. Staging or Production.
+    async fn transition_stage(&self, model: &str, version: &str, stage: &str) -> Result<()>;
+
+    /// Returns the URI model servers load `version` from.
+    fn model_uri(&self, version: &ModelVersion) -> String {
+        version.source.clone()
+    }
+
+    /// Returns the environment model servers need to load models from this registry.
+    fn serving_env(&self) -> Vec<EnvVar>;
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
+struct MlflowModelVersionsRes {
+    #[serde(default)]
+    model_versions: Vec<ModelVersion>,
+}
+
+/// Registry backed by an MLflow tracking server's REST API.
+#[derive(Clone, Debug)]
+pub struct MlflowRegistry {
+    url: String,
+    tracking_uri: String,
+}
+
+impl MlflowRegistry {
+    /// `url` is where the MLflow server is reached from the controller, model deployments use
+    /// the in-cluster tracking URI.
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            tracking_uri: DEFAULT_MLFLOW_TRACKING_URI.to_string(),
+        }
+    }
+
+    pub fn with_tracking_uri(mut self, tracking_uri: String) -> Self {
+        self.tracking_uri = tracking_uri;
+        self
+    }
+}
+
+#[async_trait]
+impl ModelRegistry for MlflowRegistry {
+    async fn latest_version(&self, model: &str) -> Result<ModelVersion> {
+        let mut body = HashMap::new();
+        body.insert("name", model.to_string());
+
+        let MlflowModelVersionsRes { model_versions } = reqwest::Client::new()
+            .post(format!(
+                "{}/api/2.0/mlflow/registered-models/get-latest-versions",
+                self.url
+            ))
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        model_versions
+            .into_iter()
+            .max_by_key(|v| v.creation_timestamp)
+            .ok_or(Error::MissingModelVersion(model.to_string()))
+    }
+
+    async fn list_versions(&self, model: &str) -> Result<Vec<ModelVersion>> {
+        let MlflowModelVersionsRes { model_versions } = reqwest::Client::new()
+            .get(format!("{}/api/2.0/mlflow/model-versions/search", self.url))
+            .query(&[("filter", format!("name='{model}'"))])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(model_versions)
+    }
+
+    async fn transition_stage(&self, model: &str, version: &str, stage: &str) -> Result<()> {
+        reqwest::Client::new()
+            .post(format!(
+                "{}/api/2.0/mlflow/model-versions/transition-stage",
+                self.url
+            ))
+            .json(&json!({
+                "name": model,
+                "version": version,
+                "stage": stage,
+                "archive_existing_versions": false,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn serving_env(&self) -> Vec<EnvVar> {
+        vec![EnvVar {
+            name: "MLFLOW_TRACKING_URI".to_string(),
+            value: Some(self.tracking_uri.clone()),
+            ..EnvVar::default()
+        }]
+    }
+}
+
+/// Registry for clusters without MLflow, model artifacts and the list of versions are kept in
+/// object storage.
+///
+/// The versions of a model are listed in `models/<model>/versions.json` and the artifacts of a
+/// version are stored under `models/<model>/<version>/`.
+#[derive(Clone, Debug)]
+pub struct ObjectStorageRegistry {
+    bucket: Bucket,
+    serving_endpoint: String,
+}
+
+impl ObjectStorageRegistry {
+    pub fn new(
+        bucket_name: &str,
+        endpoint: String,
+        region: String,
+        access_id: &str,
+        secret: &str,
+    ) -> Result<Self> {
+        let bucket = Bucket::new(
+            bucket_name,
+            Region::Custom { region, endpoint },
+            Credentials::new(Some(access_id), Some(secret), None, None, None)?,
+        )?
+        .with_path_style();
+
+        Ok(Self {
+            bucket,
+            serving_endpoint: DEFAULT_OBJECT_STORAGE_ENDPOINT.to_string(),
+        })
+    }
+
+    /// Sets the endpoint model deployments reach the object storage through.
+    pub fn with_serving_endpoint(mut self, serving_endpoint: String) -> Self {
+        self.serving_endpoint = serving_endpoint;
+        self
+    }
+
+    fn versions_key(model: &str) -> String {
+        format!("{MODELS_PREFIX}/{model}/{VERSIONS_FILE}")
+    }
+
+    /// Records a new version of `model`, the artifacts are expected under
+    /// `models/<model>/<version>/` unless the version has a source.
+    pub async fn register_version(&self, mut version: ModelVersion) -> Result<ModelVersion> {
+        let mut versions = self.list_versions(&version.name).await?;
+
+        if version.source.is_empty() {
+            version.source = format!(
+                "s3://{}/{MODELS_PREFIX}/{}/{}",
+                self.bucket.name, version.name, version.version
+            );
+        }
+
+        versions.retain(|v| v.version != version.version);
+        versions.push(version.clone());
+
+        self.write_versions(&version.name, &versions).await?;
+
+        Ok(version)
+    }
+
+    async fn write_versions(&self, model: &str, versions: &[ModelVersion]) -> Result<()> {
+        self.bucket
+            .put_object(
+                Self::versions_key(model),
+                serde_json::to_vec(versions)?.as_slice(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ModelRegistry for ObjectStorageRegistry {
+    /// Lists the versions of `model`, a model without a versions file has no versions yet.
+    async fn list_versions(&self, model: &str) -> Result<Vec<ModelVersion>> {
+        let response = match self.bucket.get_object(Self::versions_key(model)).await {
+            Ok(response) => response,
+            Err(S3Error::Http(404, _)) => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(serde_json::from_slice(response.bytes())?)
+    }
+
+    async fn transition_stage(&self, model: &str, version: &str, stage: &str) -> Result<()> {
+        let mut versions = self.list_versions(model).await?;
+
+        let Some(model_version) = versions.iter_mut().find(|v| v.version == version) else {
+            return Err(Error::MissingModelVersion(format!("{model} {version}")));
+        };
+
+        model_version.current_stage = stage.to_string();
+
+        self.write_versions(model, &versions).await
+    }
+
+    fn serving_env(&self) -> Vec<EnvVar> {
+        let mut env: Vec<EnvVar> = serde_json::from_value(json!([
+            {
+                "name": "AWS_ACCESS_KEY_ID",
+                "valueFrom": {
+                    "secretKeyRef": {
+                        "key": "MINIO_ROOT_USER",
+                        "name": "ame-minio-secret",
+                        "optional": false,
+                    }
+                },
+            },
+            {
+                "name": "AWS_SECRET_ACCESS_KEY",
+                "valueFrom": {
+                    "secretKeyRef": {
+                        "key": "MINIO_ROOT_PASSWORD",
+                        "name": "ame-minio-secret",
+                        "optional": false,
+                    }
+                },
+            },
+        ]))
+        .unwrap_or_default();
+
+        env.push(EnvVar {
+            name: "MLFLOW_S3_ENDPOINT_URL".to_string(),
+            value: Some(self.serving_endpoint.clone()),
+            ..EnvVar::default()
+        });
+
+        env
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+
+    /// A received request line and body.
+    type Requests = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Starts an HTTP stand-in which answers every request with `status` and `body`, returning
+    /// its URL and the requests it receives.
+    async fn stand_in(status: u16, body: String) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Requests::default();
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = vec![];
+                let mut chunk = [0; 4096];
+
+                let (head, mut req_body) = loop {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+
+                    let req = String::from_utf8_lossy(&buf).to_string();
+                    if let Some((head, body)) = req.split_once("\r\n\r\n") {
+                        break (head.to_string(), body.to_string());
+                    }
+                };
+
+                let content_length = head
+                    .lines()
+                    .find_map(|l| {
+                        l.to_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+
+                while req_body.len() < content_length {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    req_body.push_str(&String::from_utf8_lossy(&chunk[..n]));
+                }
+
+                received.lock().unwrap().push((
+                    head.lines().next().unwrap_or_default().to_string(),
+                    req_body,
+                ));
+
+                let response = format!(
+                    "HTTP/1.1 {status} OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn version(version: &str, creation_timestamp: i64) -> ModelVersion {
+        ModelVersion {
+            name: "mymodel".to_string(),
+            version: version.to_string(),
+            creation_timestamp,
+            source: format!("s3://ame/models/mymodel/{version}"),
+            ..ModelVersion::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn mlflow_registry_returns_the_newest_version() -> Result<()> {
+        let (url, requests) = stand_in(
+            200,
+            json!({ "model_versions": [version("1", 10), version("2", 20)] }).to_string(),
+        )
+        .await;
+
+        let latest = MlflowRegistry::new(url).latest_version("mymodel").await?;
+
+        assert_eq!(latest, version("2", 20));
+        assert!(requests.lock().unwrap()[0]
+            .0
+            .starts_with("POST /api/2.0/mlflow/registered-models/get-latest-versions"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mlflow_registry_transitions_stages() -> Result<()> {
+        let (url, requests) = stand_in(200, "{}".to_string()).await;
+
+        MlflowRegistry::new(url)
+            .transition_stage("mymodel", "2", "Production")
+            .await?;
+
+        let requests = requests.lock().unwrap();
+        let body: serde_json::Value = serde_json::from_str(&requests[0].1)?;
+
+        assert_eq!(body["stage"], "Production");
+        assert_eq!(body["version"], "2");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn object_storage_registry_reads_versions_from_the_bucket() -> Result<()> {
+        let (url, requests) =
+            stand_in(200, json!([version("1", 10), version("2", 20)]).to_string()).await;
+
+        let registry =
+            ObjectStorageRegistry::new("ame", url, "eu-central-1".to_string(), "id", "secret")?;
+
+        assert_eq!(registry.latest_version("mymodel").await?, version("2", 20));
+        assert_eq!(
+            registry.model_uri(&version("2", 20)),
+            "s3://ame/models/mymodel/2"
+        );
+        assert!(requests.lock().unwrap()[0]
+            .0
+            .starts_with("GET /ame/models/mymodel/versions.json"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn object_storage_registry_starts_without_versions() -> Result<()> {
+        let (url, _) = stand_in(404, "".to_string()).await;
+
+        let registry =
+            ObjectStorageRegistry::new("ame", url, "eu-central-1".to_string(), "id", "secret")?;
+
+        assert_eq!(registry.list_versions("mymodel").await?, vec![]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn registering_does_not_overwrite_versions_which_failed_to_load() -> Result<()> {
+        let (url, requests) = stand_in(500, "".to_string()).await;
+
+        let registry =
+            ObjectStorageRegistry::new("ame", url, "eu-central-1".to_string(), "id", "secret")?;
+
+        assert!(registry.register_version(version("3", 30)).await.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_versions_are_reported() -> Result<()> {
+        let (url, _) = stand_in(200, json!({}).to_string()).await;
+
+        assert!(matches!(
+            MlflowRegistry::new(url).latest_version("mymodel").await,
+            Err(Error::MissingModelVersion(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -3,25 +3,51 @@ use std::collections::BTreeMap;
 use super::{
     argo::{Workflow, WorkflowBuilder, WorkflowTemplateBuilder},
     common::parent_project,
-    data_set::DataSet,
+    data_set::{
+        quantity_to_bytes, DataSet, DataSetOutputSpec, DataSetValidationSpec,
+        DATA_SET_MEASURE_STEP, DATA_SET_SIZE_PARAMETER, DATA_SET_VALIDATION_STEP,
+    },
     project::{add_owner_reference, Project},
+    project_source::{GIT_PATH_ANNOTATION, GIT_REPOSITORY_ANNOTATION},
     secrets::SecretReference,
 };
 use crate::{
+    condition::{set_health, Health},
     custom_resources::{find_project, task_ctrl::resolve_data_set_path},
     error::AmeError,
-    grpc::{task_status, ArtifactCfg, TemplateRef},
+    grpc::{
+        task_status, ArtifactCfg, TaskPhaseFailed, TaskPhaseRunning, TaskPhaseSucceeded,
+        TemplateRef,
+    },
+    task_defaults::merge_task_defaults,
     Result,
 };
+use chrono::{DateTime, Utc};
 use k8s_openapi::apimachinery::pkg::{api::resource::Quantity, apis::meta::v1::OwnerReference};
 use kube::{core::ObjectMeta, Api, CustomResource, Resource, ResourceExt};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_merge::omerge;
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::grpc::{TaskCfg, TaskPhasePending, TaskStatus};
 
+/// Name of the task resource which sets the size of the task's workspace volume.
+pub static WORKSPACE_STORAGE_RESOURCE: &str = "storage";
+
+/// Size of the workspace volume when neither the task nor its data sets require more.
+pub static DEFAULT_WORKSPACE_SIZE: &str = "50Gi";
+
+static DATA_SET_SIZE_OUTPUT_PATH: &str = "/tmp/ame-data-set-size";
+
+static MONITORING_REPORT_OUTPUT_PATH: &str = "/tmp/ame-monitoring-report";
+
+/// Name of the workflow step which collects the report written by a monitoring task.
+pub static MONITORING_REPORT_STEP: &str = "collectreport";
+
+/// Name of the output parameter the collected monitoring report is exposed as.
+pub static MONITORING_REPORT_PARAMETER: &str = "report";
+
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 #[kube(
     kind = "Task",
@@ -37,14 +63,96 @@ pub struct TaskSpec {
     pub deletion_approved: bool,
     pub source: Option<ProjectSource>,
     pub project: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_set_validation: Option<DataSetValidationSpec>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_set_output: Option<DataSetOutputSpec>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backfill: Option<BackfillSpec>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitoring_report: Option<MonitoringReportSpec>,
+}
+
+/// Groups the runs created by a backfill, at most `parallelism` of them run at the same time.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillSpec {
+    pub id: String,
+    pub parallelism: u32,
+}
+
+/// Describes the report a monitoring task writes, the report is collected once the task has run
+/// so the project controller can check it against the model's thresholds.
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitoringReportSpec {
+    pub path: String,
+}
+
+impl MonitoringReportSpec {
+    /// Generates a script which copies the report to `output_path`.
+    pub fn collect_command(&self, output_path: &str) -> String {
+        let path = &self.path;
+        format!("cp \"{path}\" {output_path}")
+    }
 }
 
 impl TaskStatus {
     pub fn pending() -> Self {
         TaskStatus {
             phase: Some(task_status::Phase::pending()),
+            ..TaskStatus::default()
         }
     }
+
+    /// Sets the Ready, Reconciling and Degraded conditions from the phase, keeping the transition
+    /// times of the `previous` status' conditions.
+    pub fn with_conditions(mut self, previous: Option<&TaskStatus>, now: DateTime<Utc>) -> Self {
+        let Some(ref phase) = self.phase else {
+            return self;
+        };
+
+        let (health, reason, message) = match phase {
+            task_status::Phase::Pending(_) => (
+                Health::Reconciling,
+                "TaskPending",
+                "waiting for the task's workflow to start".to_string(),
+            ),
+            task_status::Phase::Running(TaskPhaseRunning { workflow_name }) => (
+                Health::Reconciling,
+                "TaskRunning",
+                format!("workflow {workflow_name} is running"),
+            ),
+            task_status::Phase::Succeeded(TaskPhaseSucceeded { workflow_name, .. }) => (
+                Health::Ready,
+                "TaskSucceeded",
+                format!("workflow {workflow_name} succeeded"),
+            ),
+            task_status::Phase::Failed(TaskPhaseFailed {
+                workflow_name,
+                failed_step,
+                message,
+            }) => (
+                Health::Degraded,
+                "TaskFailed",
+                message
+                    .clone()
+                    .or(failed_step
+                        .as_ref()
+                        .map(|step| format!("step {step} failed")))
+                    .unwrap_or(format!("workflow {workflow_name} failed")),
+            ),
+        };
+
+        self.conditions = previous.map(|p| p.conditions.clone()).unwrap_or_default();
+        set_health(&mut self.conditions, health, reason, message, now);
+
+        self
+    }
 }
 
 impl task_status::Phase {
@@ -61,6 +169,11 @@ pub enum ProjectSource {
         reference: String,
         user_name: String,
         secret: Option<SecretReference>,
+
+        /// Directory of the project within the repository, its contents become the task's
+        /// working directory. The root of the repository is used when it is not set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
     },
     Ame {
         path: String,
@@ -74,17 +187,45 @@ impl ProjectSource {
             reference: "".to_string(),
             user_name: "".to_string(),
             secret: None,
+            path: None,
         }
     }
 
+    /// Returns the source recorded in the `gitrepository` and `gitpath` annotations of a project
+    /// or data set, if there is one.
+    pub fn from_annotations(annotations: &BTreeMap<String, String>) -> Option<Self> {
+        let repository = annotations.get(GIT_REPOSITORY_ANNOTATION)?;
+
+        Some(ProjectSource::Git {
+            repository: repository.to_string(),
+            reference: "".to_string(),
+            user_name: "".to_string(),
+            secret: None,
+            path: annotations.get(GIT_PATH_ANNOTATION).cloned(),
+        })
+    }
+
     fn command(&self) -> String {
         match self {
             ProjectSource::Git {
                 repository,
-                reference: _,
+                reference,
                 user_name: _,
                 secret: None,
+                path,
             } => {
+                // An empty reference leaves the default branch checked out.
+                let checkout = if reference.is_empty() {
+                    "".to_string()
+                } else {
+                    format!("git checkout {reference}")
+                };
+
+                let project_dir = match path {
+                    Some(path) => format!("repo/{}", path.trim_matches('/')),
+                    None => "repo".to_string(),
+                };
+
                 format!(
                     "
                     
@@ -94,11 +235,11 @@ impl ProjectSource {
 
                 git fetch origin
 
-                git checkout origin/reference
+                {checkout}
 
                 cd ..
 
-                cp -r repo/* .
+                cp -r {project_dir}/* .
 
                 rm -rf repo
 
@@ -119,7 +260,7 @@ pub fn build_workflow(task: Task, ctx: TaskContext) -> Result<Workflow> {
     let mut wf_builder = WorkflowBuilder::new(task.name_any(), ctx.service_account.clone());
 
     let mut volume_resource_requirements = BTreeMap::new();
-    volume_resource_requirements.insert("storage".to_string(), Quantity("50Gi".to_string()));
+    volume_resource_requirements.insert("storage".to_string(), task.workspace_size(&ctx)?);
 
     wf_builder.add_volume(task.name_any(), volume_resource_requirements);
 
@@ -137,6 +278,42 @@ pub fn build_workflow(task: Task, ctx: TaskContext) -> Result<Workflow> {
         WorkflowTemplateBuilder::new(&ctx, task.exec_command()?, task.name_any())?.build(&task)?;
     wf_builder.add_template(main_template);
 
+    if let Some(ref validation) = task.spec.data_set_validation {
+        let validation_template = WorkflowTemplateBuilder::new(
+            &ctx,
+            validation.command(task.spec.cfg.executor.as_ref())?,
+            DATA_SET_VALIDATION_STEP.to_string(),
+        )?
+        .build(&task)?;
+        wf_builder.add_template(validation_template);
+    }
+
+    if let Some(ref output) = task.spec.data_set_output {
+        let mut measure_template = WorkflowTemplateBuilder::new(
+            &ctx,
+            output.measure_command(DATA_SET_SIZE_OUTPUT_PATH),
+            DATA_SET_MEASURE_STEP.to_string(),
+        )?;
+        measure_template.add_output_parameter(
+            DATA_SET_SIZE_PARAMETER.to_string(),
+            DATA_SET_SIZE_OUTPUT_PATH.to_string(),
+        );
+        wf_builder.add_template(measure_template.build(&task)?);
+    }
+
+    if let Some(ref report) = task.spec.monitoring_report {
+        let mut report_template = WorkflowTemplateBuilder::new(
+            &ctx,
+            report.collect_command(MONITORING_REPORT_OUTPUT_PATH),
+            MONITORING_REPORT_STEP.to_string(),
+        )?;
+        report_template.add_output_parameter(
+            MONITORING_REPORT_PARAMETER.to_string(),
+            MONITORING_REPORT_OUTPUT_PATH.to_string(),
+        );
+        wf_builder.add_template(report_template.build(&task)?);
+    }
+
     if task.should_save_artifacts() {
         let artifact_save_template = WorkflowTemplateBuilder::new(
             &ctx,
@@ -166,6 +343,55 @@ impl Task {
         parent_project(self.owner_references().to_vec())
     }
 
+    /// Determines the size of the workspace volume from the data sets the task downloads and
+    /// generates.
+    ///
+    /// If the task sets the storage resource explicitly it is used as is and the task is
+    /// rejected when its data sets do not fit. Otherwise the workspace grows beyond the
+    /// default size when the data sets require it.
+    fn workspace_size(&self, ctx: &TaskContext) -> Result<Quantity> {
+        let mut required_bytes = 0;
+
+        for ds in ctx.required_data_sets.iter() {
+            match ds.size_bytes()? {
+                Some(size) => required_bytes += size,
+                None => warn!(
+                    "data set {} required by task {} has no known size, the workspace may be too small",
+                    ds.name_any(),
+                    self.name_any()
+                ),
+            }
+        }
+
+        if let Some(DataSetOutputSpec {
+            size: Some(ref size),
+            ..
+        }) = self.spec.data_set_output
+        {
+            required_bytes += quantity_to_bytes(size)?;
+        }
+
+        if let Some(storage) = self.spec.cfg.resources.get(WORKSPACE_STORAGE_RESOURCE) {
+            let available_bytes = quantity_to_bytes(storage)?;
+
+            if required_bytes > available_bytes {
+                return Err(AmeError::InsufficientWorkspace(
+                    self.name_any(),
+                    required_bytes,
+                    available_bytes,
+                ));
+            }
+
+            return Ok(Quantity(storage.clone()));
+        }
+
+        if required_bytes > quantity_to_bytes(DEFAULT_WORKSPACE_SIZE)? {
+            Ok(Quantity(required_bytes.to_string()))
+        } else {
+            Ok(Quantity(DEFAULT_WORKSPACE_SIZE.to_string()))
+        }
+    }
+
     fn should_save_artifacts(&self) -> bool {
         self.spec.cfg.artifact_cfg.is_some()
     }
@@ -346,10 +572,14 @@ pub async fn resolve_task_templates(
 
     debug!("found template {:?}", template);
 
-    let task_spec = TaskSpec {
-        cfg: omerge(template, task.spec.cfg)?,
-        ..task.spec
-    };
+    let mut cfg: TaskCfg = omerge(template, task.spec.cfg)?;
+
+    // Defaults are merged after the template so the template takes precedence over them.
+    if let Some(ref defaults) = project.spec.cfg.defaults {
+        cfg = merge_task_defaults(defaults, cfg);
+    }
+
+    let task_spec = TaskSpec { cfg, ..task.spec };
 
     Ok(Task {
         spec: task_spec,
@@ -371,8 +601,8 @@ mod test {
     use kube::core::ObjectMeta;
 
     use crate::grpc::{
-        secret::Variant, task_cfg::Executor, AmeSecretVariant, EnvVar, PoetryExecutor, Secret,
-        TaskPhaseRunning, TaskRef,
+        secret::Variant, task_cfg::Executor, AmeSecretVariant, DataSetValidation, EnvVar,
+        PoetryExecutor, RowCountRule, Secret, TaskPhaseRunning, TaskRef,
     };
 
     use super::*;
@@ -417,11 +647,16 @@ mod test {
                 }),
                 deletion_approved: false,
                 project: None,
+                data_set_validation: None,
+                data_set_output: None,
+                backfill: None,
+                monitoring_report: None,
             },
             status: Some(TaskStatus {
                 phase: Some(task_status::Phase::Running(TaskPhaseRunning {
                     workflow_name: "someinfo".to_string(),
                 })),
+                ..TaskStatus::default()
             }),
         };
 
@@ -483,11 +718,16 @@ mod test {
                 }),
                 deletion_approved: false,
                 project: None,
+                data_set_validation: None,
+                data_set_output: None,
+                backfill: None,
+                monitoring_report: None,
             },
             status: Some(TaskStatus {
                 phase: Some(task_status::Phase::Running(TaskPhaseRunning {
                     workflow_name: "someinfo".to_string(),
                 })),
+                ..TaskStatus::default()
             }),
         };
 
@@ -504,4 +744,161 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn snap_shot_workflow_with_data_set_validation() -> Result<()> {
+        let task = Task {
+            metadata: ObjectMeta {
+                name: Some("datatask".to_string()),
+                owner_references: Some(vec![OwnerReference {
+                    block_owner_deletion: None,
+                    api_version: "sfsdd".to_string(),
+                    controller: None,
+                    kind: "Project".to_string(),
+                    name: "parentproject343".to_string(),
+                    uid: "sdsfdsf".to_string(),
+                }]),
+                ..ObjectMeta::default()
+            },
+            spec: TaskSpec {
+                cfg: TaskCfg {
+                    name: Some("datatask".to_string()),
+                    executor: Some(Executor::Poetry(PoetryExecutor {
+                        python_version: "3.11".to_string(),
+                        command: "python prepare.py".to_string(),
+                    })),
+                    artifact_cfg: Some(ArtifactCfg {
+                        save_changed_files: true,
+                        paths: vec![],
+                    }),
+                    ..TaskCfg::default()
+                },
+                source: Some(ProjectSource::Ame {
+                    path: "test".to_string(),
+                }),
+                data_set_validation: Some(DataSetValidationSpec {
+                    path: "data".to_string(),
+                    rules: DataSetValidation {
+                        expected_files: vec!["train.csv".to_string()],
+                        min_size: Some("1Mi".to_string()),
+                        row_counts: vec![
+                            RowCountRule {
+                                file: "train.csv".to_string(),
+                                min: Some(100),
+                                max: Some(10000),
+                            },
+                            RowCountRule {
+                                file: "test.parquet".to_string(),
+                                min: Some(10),
+                                max: None,
+                            },
+                        ],
+                        command: Some("python validate.py".to_string()),
+                    },
+                }),
+                ..TaskSpec::default()
+            },
+            status: None,
+        };
+
+        let task_ctx = TaskContext {
+            executor_image: "myimage".to_string(),
+            task_volume: "myvolume".to_string(),
+            required_data_sets: vec![],
+            service_account: "ame-task".to_string(),
+        };
+
+        insta::assert_yaml_snapshot!(build_workflow(task, task_ctx)?);
+
+        Ok(())
+    }
+
+    fn data_task(resources: BTreeMap<String, String>, size: &str) -> Task {
+        Task {
+            metadata: ObjectMeta {
+                name: Some("datatask".to_string()),
+                owner_references: Some(vec![OwnerReference {
+                    kind: "Project".to_string(),
+                    name: "parentproject343".to_string(),
+                    ..OwnerReference::default()
+                }]),
+                ..ObjectMeta::default()
+            },
+            spec: TaskSpec {
+                cfg: TaskCfg {
+                    name: Some("datatask".to_string()),
+                    executor: Some(Executor::Poetry(PoetryExecutor {
+                        python_version: "3.11".to_string(),
+                        command: "python prepare.py".to_string(),
+                    })),
+                    resources,
+                    ..TaskCfg::default()
+                },
+                data_set_output: Some(DataSetOutputSpec {
+                    path: "data".to_string(),
+                    size: Some(size.to_string()),
+                }),
+                ..TaskSpec::default()
+            },
+            status: None,
+        }
+    }
+
+    #[test]
+    fn workspace_grows_to_fit_data_sets() -> Result<()> {
+        let task_ctx = TaskContext {
+            executor_image: "myimage".to_string(),
+            task_volume: "myvolume".to_string(),
+            required_data_sets: vec![],
+            service_account: "ame-task".to_string(),
+        };
+
+        let workflow = build_workflow(data_task(BTreeMap::new(), "80Gi"), task_ctx.clone())?;
+        let claims = workflow.spec.volume_claim_templates.unwrap();
+        let requests = claims[0]
+            .spec
+            .as_ref()
+            .and_then(|s| s.resources.as_ref())
+            .and_then(|r| r.requests.as_ref())
+            .unwrap();
+
+        assert_eq!(
+            requests.get("storage"),
+            Some(&Quantity((80 * 1024u64.pow(3)).to_string()))
+        );
+
+        let workflow = build_workflow(data_task(BTreeMap::new(), "1Gi"), task_ctx)?;
+        let claims = workflow.spec.volume_claim_templates.unwrap();
+        let requests = claims[0]
+            .spec
+            .as_ref()
+            .and_then(|s| s.resources.as_ref())
+            .and_then(|r| r.requests.as_ref())
+            .unwrap();
+
+        assert_eq!(
+            requests.get("storage"),
+            Some(&Quantity(DEFAULT_WORKSPACE_SIZE.to_string()))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_data_sets_larger_than_workspace() {
+        let task_ctx = TaskContext {
+            executor_image: "myimage".to_string(),
+            task_volume: "myvolume".to_string(),
+            required_data_sets: vec![],
+            service_account: "ame-task".to_string(),
+        };
+
+        let mut resources = BTreeMap::new();
+        resources.insert("storage".to_string(), "10Gi".to_string());
+
+        assert!(matches!(
+            build_workflow(data_task(resources, "20Gi"), task_ctx),
+            Err(AmeError::InsufficientWorkspace(..))
+        ));
+    }
 }
@@ -1,20 +1,33 @@
-use std::{
-    collections::{BTreeMap, HashMap},
-    default::Default,
-};
+use std::{collections::BTreeMap, default::Default, str::FromStr};
+
+use chrono::{DateTime, Utc};
 
 use crate::{
     ctrl::AmeResource,
     custom_resources::{data_set::DataSet, Error, Result},
     error::AmeError,
-    grpc::{resource_map_conv, DataSetCfg, Model, ProjectCfg, ProjectStatus, TaskCfg, TaskRef},
+    grpc::{
+        batch_inference_cfg::Scoring, model_deployment_cfg::Runtime, resource_map_conv,
+        task_cfg::Executor, CustomExecutor, CustomMetricTarget, CustomRuntime, DataSetCfg,
+        DataSetGrant, Model, ProjectCfg, ProjectStatus, TaskCfg, TaskRef,
+    },
+    monitoring::REPORT_PATH_ENV,
 };
 
-use super::new_task::{ProjectSource, Task, TaskBuilder};
+use super::{
+    common::shell_quote,
+    model_registry::{ModelRegistry, ModelVersion},
+    new_task::{MonitoringReportSpec, ProjectSource, Task, TaskBuilder},
+    project_source::{GIT_PATH_ANNOTATION, GIT_REPOSITORY_ANNOTATION},
+};
 
 use k8s_openapi::{
     api::{
         apps::v1::{Deployment, DeploymentSpec},
+        autoscaling::v2::{
+            CrossVersionObjectReference, HorizontalPodAutoscaler, HorizontalPodAutoscalerSpec,
+            MetricIdentifier, MetricSpec, MetricTarget, PodsMetricSource, ResourceMetricSource,
+        },
         core::v1::{
             Container, ContainerPort, EnvVar, HTTPGetAction, PodSpec, PodTemplateSpec, Probe,
             ResourceRequirements, Service, ServicePort, ServiceSpec,
@@ -25,6 +38,7 @@ use k8s_openapi::{
         },
     },
     apimachinery::pkg::{
+        api::resource::Quantity,
         apis::meta::v1::{LabelSelector, OwnerReference},
         util::intstr::IntOrString,
     },
@@ -36,6 +50,30 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::debug;
 
+/// CPU utilisation autoscaled model deployments target, unless they configure a target.
+pub const DEFAULT_TARGET_CPU_UTILIZATION: i32 = 80;
+
+/// Port model servers listen on, unless a custom runtime configures another port.
+pub const DEFAULT_SERVING_PORT: i32 = 5000;
+
+/// Path model servers report their health on, unless a custom runtime configures another path.
+pub static DEFAULT_HEALTH_PATH: &str = "/health";
+
+/// Environment variable custom model servers receive the URI of the model to serve through.
+pub static MODEL_URI_ENV: &str = "AME_MODEL_URI";
+
+/// Environment variable batch inference tasks receive the version of the model they score with.
+pub static MODEL_VERSION_ENV: &str = "AME_MODEL_VERSION";
+
+/// Environment variable batch inference tasks receive the path to write predictions to through.
+pub static OUTPUT_PATH_ENV: &str = "AME_OUTPUT_PATH";
+
+/// Python version batch inference tasks scoring with the MLflow runtime run with.
+static MLFLOW_SCORING_PYTHON_VERSION: &str = "3.11";
+
+/// Suffix of the objects serving a candidate model version during a rollout.
+pub static CANARY_SUFFIX: &str = "-canary";
+
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 #[kube(
     kind = "Project",
@@ -51,11 +89,63 @@ pub struct ProjectSpec {
     pub cfg: ProjectCfg,
     pub deletion_approved: bool,
 
+    /// Whether the triggers of the project's tasks are active. Batch inference and monitoring
+    /// of its models run either way.
     #[serde(default)]
     pub enable_triggers: Option<bool>,
+
+    /// What happens to the project's objects when it is deleted, see [`DeletionPolicy`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletion_policy: Option<String>,
+}
+
+/// Determines what happens to the objects a project manages when the project is deleted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeletionPolicy {
+    /// Delete tasks, data sets and model deployments, objects in storage are kept.
+    #[default]
+    Cascade,
+
+    /// Delete tasks, data sets, model deployments and the project's objects in storage.
+    ///
+    /// Objects are stored under the names of tasks and models, so only use this when those names
+    /// are not reused by projects in other namespaces.
+    Purge,
+
+    /// Keep data sets and the artifacts in storage, everything else is deleted.
+    RetainData,
+
+    /// Keep model deployments serving, everything else is deleted.
+    RetainDeployments,
+}
+
+impl DeletionPolicy {
+    pub fn retains_data(&self) -> bool {
+        *self == DeletionPolicy::RetainData
+    }
+
+    pub fn retains_deployments(&self) -> bool {
+        *self == DeletionPolicy::RetainDeployments
+    }
+}
+
+impl FromStr for DeletionPolicy {
+    type Err = AmeError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        match s {
+            "Cascade" => Ok(DeletionPolicy::Cascade),
+            "Purge" => Ok(DeletionPolicy::Purge),
+            "RetainData" => Ok(DeletionPolicy::RetainData),
+            "RetainDeployments" => Ok(DeletionPolicy::RetainDeployments),
+            _ => Err(AmeError::InvalidDeletionPolicy(s.to_string())),
+        }
+    }
 }
 
 pub fn local_name(name: String) -> String {
+    let name = without_namespace(name);
+
     if name.contains('.') {
         name.split('.')
             .map(String::from)
@@ -69,6 +159,8 @@ pub fn local_name(name: String) -> String {
 }
 
 pub fn project_name(name: String) -> Option<String> {
+    let name = without_namespace(name);
+
     if !name.contains('.') {
         return None;
     }
@@ -82,16 +174,84 @@ pub fn project_name(name: String) -> Option<String> {
     None
 }
 
+/// Returns the namespace of a data set reference on the form `namespace/project.dataset`,
+/// references without a namespace resolve in the namespace of the referring project.
+pub fn namespace_name(name: String) -> Option<String> {
+    name.split_once('/')
+        .map(|(namespace, _)| namespace.to_string())
+}
+
+fn without_namespace(name: String) -> String {
+    match name.split_once('/') {
+        Some((_, name)) => name.to_string(),
+        None => name,
+    }
+}
+
+impl DataSetGrant {
+    /// Checks if this grant allows `project` in `namespace` to use the data set `data_set`.
+    ///
+    /// `*` matches any data set, namespace or project. A grant without projects allows every
+    /// project in the listed namespaces.
+    pub fn allows(&self, data_set: &str, namespace: &str, project: &str) -> bool {
+        let matches = |pattern: &String, value: &str| pattern == "*" || pattern == value;
+
+        matches(&self.data_set, data_set)
+            && self.namespaces.iter().any(|ns| matches(ns, namespace))
+            && (self.projects.is_empty() || self.projects.iter().any(|p| matches(p, project)))
+    }
+}
+
+/// Checks if `owner` grants `project` in `namespace` access to the data set `data_set`.
+pub fn is_data_set_shared(
+    owner: &ProjectCfg,
+    data_set: &str,
+    namespace: &str,
+    project: &str,
+) -> bool {
+    owner
+        .grants
+        .iter()
+        .any(|grant| grant.allows(data_set, namespace, project))
+}
+
 impl From<ProjectCfg> for ProjectSpec {
     fn from(cfg: ProjectCfg) -> Self {
         Self {
             cfg,
             deletion_approved: false,
             enable_triggers: Some(false),
+            deletion_policy: None,
         }
     }
 }
 
+/// Generates a script scoring the data set at `input_path` with the MLflow model at
+/// `$AME_MODEL_URI`.
+///
+/// A data set is usually a directory, every file in it is scored and the predictions are
+/// written to `output_path` under the file's path within the data set.
+fn mlflow_scoring_command(input_path: &str, output_path: &str) -> String {
+    let input_path = shell_quote(input_path);
+    let output_path = shell_quote(output_path);
+
+    format!(
+        r#"export PATH=$HOME/.pyenv/bin:$PATH
+set -eo pipefail
+input_path={input_path}
+output_path={output_path}
+scored=0
+while IFS= read -r -d '' input; do
+    name=${{input#"$input_path"/}}
+    [ "$name" != "$input" ] || name=$(basename "$input")
+    mkdir -p "$output_path/$(dirname "$name")"
+    mlflow models predict -m "${MODEL_URI_ENV}" -i "$input" -o "$output_path/${{name%.*}}.csv" -t csv
+    scored=$((scored + 1))
+done < <(find "$input_path" -type f ! -name '.*' -print0 | sort -z)
+[ "$scored" -gt 0 ] || {{ echo "there are no files to score in $input_path"; exit 1; }}"#
+    )
+}
+
 pub fn generate_task_name(project_name: String, task_name: String) -> String {
     format!("{project_name}{task_name}")
 }
@@ -121,6 +281,14 @@ impl Project {
         self.spec.deletion_approved = true;
     }
 
+    /// Returns the project's deletion policy, projects without a policy cascade.
+    pub fn deletion_policy(&self) -> crate::Result<DeletionPolicy> {
+        self.spec
+            .deletion_policy
+            .as_deref()
+            .map_or(Ok(DeletionPolicy::default()), DeletionPolicy::from_str)
+    }
+
     pub fn get_data_set(&self, data_set_name: String) -> Option<DataSetCfg> {
         let data_set_name = local_name(data_set_name);
 
@@ -134,20 +302,24 @@ impl Project {
 
     // TODO: documentat that this assumes a local data set.
     pub fn generate_data_set(&self, data_set_name: String) -> crate::Result<DataSet> {
-        let Some(cfg) = self.get_data_set(data_set_name.clone()) else {
+        let Some(mut cfg) = self.get_data_set(data_set_name.clone()) else {
             debug!("failed to get data set {}", data_set_name);
             return Err(AmeError::MissingDataSet(data_set_name, self.name_any()));
         };
 
+        cfg.task = cfg.task.map(|task| self.spec.cfg.with_task_defaults(task));
+
         let mut data_set = DataSet::from_cfg(
             &format!("dataset{}{}", self.spec.cfg.name, data_set_name),
             cfg,
         );
 
-        if let Some(repo) = self.annotations().get("gitrepository") {
-            data_set
-                .annotations_mut()
-                .insert("gitrepository".to_string(), repo.to_string());
+        for annotation in [GIT_REPOSITORY_ANNOTATION, GIT_PATH_ANNOTATION] {
+            if let Some(val) = self.annotations().get(annotation) {
+                data_set
+                    .annotations_mut()
+                    .insert(annotation.to_string(), val.to_string());
+            }
         }
 
         data_set.spec.project = Some(self.spec.cfg.name.clone());
@@ -166,7 +338,7 @@ impl Project {
             return None;
         };
 
-        let mut task_builder = TaskBuilder::from_cfg(task);
+        let mut task_builder = TaskBuilder::from_cfg(self.spec.cfg.with_task_defaults(task));
 
         task_builder.set_name(generate_data_set_task_name(
             self.spec.cfg.name.clone(),
@@ -174,8 +346,8 @@ impl Project {
         ));
         task_builder.set_project(self.spec.cfg.name.clone());
 
-        if let Some(repo) = self.annotations().get("gitrepository") {
-            task_builder.set_project_src(ProjectSource::from_public_git_repo(repo.to_string()));
+        if let Some(project_src) = ProjectSource::from_annotations(self.annotations()) {
+            task_builder.set_project_src(project_src);
         }
 
         Some(task_builder.build())
@@ -196,10 +368,10 @@ impl Project {
             return Err(Error::MissingValidationTask(model.name.clone()));
         };
 
-        let mut task_builder = TaskBuilder::from_cfg(cfg);
+        let mut task_builder = TaskBuilder::from_cfg(self.spec.cfg.with_task_defaults(cfg));
 
-        if let Some(repo) = self.annotations().get("gitrepository") {
-            task_builder.set_project_src(ProjectSource::from_public_git_repo(repo.to_string()));
+        if let Some(project_src) = ProjectSource::from_annotations(self.annotations()) {
+            task_builder.set_project_src(project_src);
         }
 
         task_builder.add_owner_reference(
@@ -223,6 +395,184 @@ impl Project {
         Ok(validation_task)
     }
 
+    /// Generates the output data set of a model's batch inference, generated by a task scoring
+    /// the input data set with the model version stored at `model_uri`.
+    ///
+    /// Every run gets a task of its own, so the output data set gets a new version once the
+    /// run's task succeeds. The output data set must be declared in the project without a task
+    /// of its own.
+    pub fn generate_batch_inference_data_set(
+        &self,
+        model: &Model,
+        model_uri: &str,
+        model_version: &str,
+        instant: DateTime<Utc>,
+    ) -> Result<DataSet> {
+        let Some(ref cfg) = model.batch_inference else {
+            return Err(Error::InvalidBatchInference(
+                model.name.clone(),
+                "batch inference is not configured".to_string(),
+            ));
+        };
+
+        let mut data_set = self.generate_data_set(cfg.output_data_set.clone())?;
+
+        if data_set.spec.cfg.task.is_some() {
+            return Err(Error::InvalidBatchInference(
+                model.name.clone(),
+                format!(
+                    "output data set {} is generated by a task of its own",
+                    cfg.output_data_set
+                ),
+            ));
+        }
+
+        let is_local = namespace_name(cfg.input_data_set.clone()).is_none()
+            && project_name(cfg.input_data_set.clone()).is_none();
+        let input = is_local
+            .then(|| self.get_data_set(cfg.input_data_set.clone()))
+            .flatten();
+        let output_path = data_set.spec.cfg.path.clone();
+
+        let mut task = match cfg.scoring {
+            Some(Scoring::Task(ref task)) => match task.task_ref {
+                Some(ref task_ref) => {
+                    self.find_task_cfg(&task_ref.name)
+                        .ok_or(Error::MissingTaskCfg(
+                            task_ref.name.clone(),
+                            self.spec.cfg.name.clone(),
+                        ))?
+                }
+                None => task.clone(),
+            },
+            Some(Scoring::Mlflow(_)) | None => {
+                let Some(input_path) = input.as_ref().map(|ds| ds.path.clone()) else {
+                    return Err(Error::InvalidBatchInference(
+                        model.name.clone(),
+                        format!(
+                            "the MLflow runtime can only score data sets in project {}, got {}",
+                            self.spec.cfg.name, cfg.input_data_set
+                        ),
+                    ));
+                };
+
+                TaskCfg {
+                    executor: Some(Executor::Custom(CustomExecutor {
+                        python_version: MLFLOW_SCORING_PYTHON_VERSION.to_string(),
+                        command: mlflow_scoring_command(&input_path, &output_path),
+                    })),
+                    ..TaskCfg::default()
+                }
+            }
+        };
+
+        task.name = Some(format!("score-{}-{}", model.name, instant.timestamp()));
+        task.triggers = None;
+
+        if !task.data_sets.contains(&cfg.input_data_set) {
+            task.data_sets.push(cfg.input_data_set.clone());
+        }
+
+        for (key, val) in [
+            (MODEL_URI_ENV, model_uri),
+            (MODEL_VERSION_ENV, model_version),
+            (OUTPUT_PATH_ENV, &output_path),
+        ] {
+            task.env.retain(|var| var.key != key);
+            task.env.push(crate::grpc::EnvVar {
+                key: key.to_string(),
+                val: val.to_string(),
+            });
+        }
+
+        task.set_logical_time(instant);
+
+        data_set.spec.cfg.task = Some(self.spec.cfg.with_task_defaults(task));
+
+        Ok(data_set)
+    }
+
+    /// Generates a task monitoring the model version stored at `model_uri` against the
+    /// reference data set, the task writes a JSON report which is collected once it has run.
+    pub fn generate_monitoring_task(
+        &self,
+        model: &Model,
+        model_uri: &str,
+        model_version: &str,
+        instant: DateTime<Utc>,
+    ) -> Result<Task> {
+        let Some(ref cfg) = model.monitoring else {
+            return Err(Error::InvalidMonitoring(
+                model.name.clone(),
+                "monitoring is not configured".to_string(),
+            ));
+        };
+
+        let mut task_cfg = match cfg.task {
+            Some(TaskCfg {
+                task_ref: Some(ref task_ref),
+                ..
+            }) => self
+                .find_task_cfg(&task_ref.name)
+                .ok_or(Error::MissingTaskCfg(
+                    task_ref.name.clone(),
+                    self.spec.cfg.name.clone(),
+                ))?,
+            Some(ref task_cfg) => task_cfg.clone(),
+            None => {
+                return Err(Error::InvalidMonitoring(
+                    model.name.clone(),
+                    "a monitoring task is required".to_string(),
+                ))
+            }
+        };
+
+        let report_path = cfg.report_path_or_default();
+
+        task_cfg.triggers = None;
+
+        if !task_cfg.data_sets.contains(&cfg.reference_data_set) {
+            task_cfg.data_sets.push(cfg.reference_data_set.clone());
+        }
+
+        for (key, val) in [
+            (MODEL_URI_ENV, model_uri),
+            (MODEL_VERSION_ENV, model_version),
+            (REPORT_PATH_ENV, &report_path),
+        ] {
+            task_cfg.env.retain(|var| var.key != key);
+            task_cfg.env.push(crate::grpc::EnvVar {
+                key: key.to_string(),
+                val: val.to_string(),
+            });
+        }
+
+        task_cfg.set_logical_time(instant);
+
+        let mut task_builder = TaskBuilder::from_cfg(self.spec.cfg.with_task_defaults(task_cfg));
+
+        if let Some(project_src) = ProjectSource::from_annotations(self.annotations()) {
+            task_builder.set_project_src(project_src);
+        }
+
+        task_builder
+            .set_name(format!("monitor-{}-{}", model.name, instant.timestamp()))
+            .set_project(self.spec.cfg.name.clone())
+            .add_owner_reference(
+                self.controller_owner_ref(&())
+                    .ok_or(AmeError::FailedToCreateOref(self.name_any()))?,
+            );
+
+        let mut task = task_builder.build();
+        task.spec.monitoring_report = Some(MonitoringReportSpec { path: report_path });
+
+        if task.spec.cfg.executor.is_none() {
+            return Err(Error::MissingExecutor(task.name_any()));
+        }
+
+        Ok(task)
+    }
+
     pub fn add_owner_reference(&mut self, owner_reference: OwnerReference) -> &mut Project {
         match &mut self.metadata.owner_references {
             Some(refs) => refs.push(owner_reference),
@@ -297,19 +647,18 @@ impl Project {
     }
 
     pub fn generate_model_training_task(&self, name: &str) -> Result<Task> {
-        let task_cfg = self.get_model_training_cfg(name)?;
+        let task_cfg = self
+            .spec
+            .cfg
+            .with_task_defaults(self.get_model_training_cfg(name)?);
 
         let mut task_builder = TaskBuilder::from_cfg(task_cfg.clone());
 
-        let medata = self.metadata.clone();
-
-        let annotations = medata.annotations.unwrap();
-
-        let repo = annotations.get("gitrepository").unwrap();
+        let project_src = ProjectSource::from_annotations(self.annotations()).unwrap();
         // NOTE: how is the data set repo set as src here?
 
         let training_task = task_builder
-            .set_project_src(ProjectSource::from_public_git_repo(repo.to_string()))
+            .set_project_src(project_src)
             .set_name(format!(
                 "{}{}{}{}",
                 self.name_any(),
@@ -340,34 +689,6 @@ pub fn add_owner_reference(
     metadata
 }
 
-pub async fn get_latest_model_version(
-    model: &Model,
-    mlflow_url: String,
-) -> Result<MlflowModelVersion> {
-    let Some(model_version) = ({
-        let mut body = HashMap::new();
-        body.insert("name", model.name.clone());
-        let client = reqwest::Client::new();
-        let MlflowModelVersionsRes { model_versions } = client
-            .post(format!(
-                "{mlflow_url}/api/2.0/mlflow/registered-models/get-latest-versions"
-            ))
-            .json(&body)
-            .send()
-            .await?
-            .json()
-            .await?;
-
-        model_versions
-            .into_iter()
-            .max_by_key(|v| v.creation_timestamp)
-    }) else {
-        return Err(Error::MissingMlflowUrl());
-    };
-
-    Ok(model_version)
-}
-
 impl Model {
     fn get_training_task_cfg(&self) -> Option<TaskCfg> {
         self.training.as_ref().and_then(|t| t.task.clone())
@@ -441,7 +762,7 @@ impl Model {
                                 service: Some(IngressServiceBackend {
                                     name: self.name.clone(),
                                     port: Some(ServiceBackendPort {
-                                        number: Some(5000),
+                                        number: Some(self.serving_port()),
                                         name: None,
                                     }),
                                 }),
@@ -462,6 +783,70 @@ impl Model {
         })
     }
 
+    /// Returns the model deployed alongside this model while a new version is rolled out, its
+    /// deployment, service and ingress are named `<model>-canary`.
+    pub fn canary(&self) -> Model {
+        Model {
+            name: format!("{}{CANARY_SUFFIX}", self.name),
+            ..self.clone()
+        }
+    }
+
+    /// Generates an nginx canary ingress sending `weight` percent of the model's traffic to
+    /// the canary service.
+    pub fn generate_canary_ingress(
+        &self,
+        ingress_host: String,
+        ingress_annotations: Option<BTreeMap<String, String>>,
+        project_name: String,
+        weight: u32,
+    ) -> Result<Ingress> {
+        let canary = self.canary();
+
+        let mut ingress =
+            self.generate_model_ingress(ingress_host, ingress_annotations, project_name)?;
+
+        ingress.metadata.name = Some(canary.name.clone());
+        ingress.metadata.labels = Some(canary.labels());
+
+        let annotations = ingress
+            .metadata
+            .annotations
+            .get_or_insert_with(BTreeMap::new);
+        annotations.insert(
+            "nginx.ingress.kubernetes.io/canary".to_string(),
+            "true".to_string(),
+        );
+        annotations.insert(
+            "nginx.ingress.kubernetes.io/canary-weight".to_string(),
+            weight.to_string(),
+        );
+
+        let paths = ingress
+            .spec
+            .iter_mut()
+            .flat_map(|spec| spec.rules.iter_mut().flatten())
+            .flat_map(|rule| rule.http.iter_mut().flat_map(|http| http.paths.iter_mut()));
+
+        for path in paths {
+            if let Some(ref mut service) = path.backend.service {
+                service.name = canary.name.clone();
+            }
+        }
+
+        Ok(ingress)
+    }
+
+    /// Returns the port the model's server listens on.
+    pub fn serving_port(&self) -> i32 {
+        match self.deployment.as_ref().and_then(|d| d.runtime.as_ref()) {
+            Some(Runtime::Custom(CustomRuntime {
+                port: Some(port), ..
+            })) => *port,
+            _ => DEFAULT_SERVING_PORT,
+        }
+    }
+
     pub fn generate_model_service(&self) -> Result<Service> {
         let Some(_model_deployment) = self.deployment.clone() else {
             return Err(Error::MissingDeployment());
@@ -472,7 +857,7 @@ impl Model {
             spec: Some(ServiceSpec {
                 selector: Some(self.labels()),
                 ports: Some(vec![ServicePort {
-                    port: 5000,
+                    port: self.serving_port(),
                     ..ServicePort::default()
                 }]),
                 ..ServiceSpec::default()
@@ -481,18 +866,102 @@ impl Model {
         })
     }
 
+    /// Generates a horizontal pod autoscaler for the model's deployment, `None` is returned when
+    /// the deployment does not configure autoscaling.
+    ///
+    /// The autoscaler targets the custom metric if one is configured and CPU utilisation
+    /// otherwise, defaulting to 80% utilisation.
+    pub fn generate_model_autoscaler(&self) -> Result<Option<HorizontalPodAutoscaler>> {
+        let Some(model_deployment) = self.deployment.clone() else {
+            return Err(Error::MissingDeployment());
+        };
+
+        let Some(autoscaling) = model_deployment.autoscaling else {
+            return Ok(None);
+        };
+
+        if autoscaling.min_replicas < 1 || autoscaling.min_replicas > autoscaling.max_replicas {
+            return Err(Error::InvalidAutoscaling(
+                self.name.clone(),
+                format!(
+                    "expected at least 1 minimum replica and no more than the {} maximum replicas, got {}",
+                    autoscaling.max_replicas, autoscaling.min_replicas
+                ),
+            ));
+        }
+
+        let metric = match autoscaling.custom_metric {
+            Some(CustomMetricTarget {
+                name,
+                average_value,
+            }) => MetricSpec {
+                type_: "Pods".to_string(),
+                pods: Some(PodsMetricSource {
+                    metric: MetricIdentifier {
+                        name,
+                        selector: None,
+                    },
+                    target: MetricTarget {
+                        type_: "AverageValue".to_string(),
+                        average_value: Some(Quantity(average_value)),
+                        ..MetricTarget::default()
+                    },
+                }),
+                ..MetricSpec::default()
+            },
+            None => MetricSpec {
+                type_: "Resource".to_string(),
+                resource: Some(ResourceMetricSource {
+                    name: "cpu".to_string(),
+                    target: MetricTarget {
+                        type_: "Utilization".to_string(),
+                        average_utilization: Some(
+                            autoscaling
+                                .target_cpu_utilization
+                                .unwrap_or(DEFAULT_TARGET_CPU_UTILIZATION),
+                        ),
+                        ..MetricTarget::default()
+                    },
+                }),
+                ..MetricSpec::default()
+            },
+        };
+
+        Ok(Some(HorizontalPodAutoscaler {
+            metadata: self.object_metadata(),
+            spec: Some(HorizontalPodAutoscalerSpec {
+                scale_target_ref: CrossVersionObjectReference {
+                    api_version: Some("apps/v1".to_string()),
+                    kind: "Deployment".to_string(),
+                    name: self.name.clone(),
+                },
+                min_replicas: Some(autoscaling.min_replicas),
+                max_replicas: autoscaling.max_replicas,
+                metrics: Some(vec![metric]),
+                behavior: None,
+            }),
+            status: None,
+        }))
+    }
+
     pub async fn get_model_version(
         &self,
         _ctrl_cfg: &ProjectCtrlCfg,
         _version: &str,
-    ) -> Result<MlflowModelVersion> {
+    ) -> Result<ModelVersion> {
         todo!();
     }
 
+    /// Generates a deployment serving `model_source` with the model's serving runtime, the model
+    /// is loaded from `registry`.
+    ///
+    /// The MLflow runtime runs `mlflow models serve` in the deployment image, custom runtimes run
+    /// their own image and receive the model's URI through `AME_MODEL_URI`.
     pub async fn generate_model_deployment(
         &self,
         deployment_image: String,
         model_source: String,
+        registry: &dyn ModelRegistry,
     ) -> Result<Deployment> {
         let Some(model_deployment) = self.deployment.clone() else {
             return Err(Error::MissingDeployment());
@@ -500,7 +969,57 @@ impl Model {
 
         let labels = self.labels();
 
-        let server_port = 5000;
+        let server_port = self.serving_port();
+
+        let (image, command, args, health_path, env) = match model_deployment.runtime {
+            Some(Runtime::Custom(runtime)) => {
+                if runtime.image.is_empty() {
+                    return Err(Error::InvalidServingRuntime(
+                        self.name.clone(),
+                        "a custom runtime requires an image".to_string(),
+                    ));
+                }
+
+                if !(1..=65535).contains(&server_port) {
+                    return Err(Error::InvalidServingRuntime(
+                        self.name.clone(),
+                        format!("{server_port} is not a valid port"),
+                    ));
+                }
+
+                let mut env = registry.serving_env();
+                env.push(EnvVar {
+                    name: MODEL_URI_ENV.to_string(),
+                    value: Some(model_source),
+                    ..EnvVar::default()
+                });
+                env.extend(runtime.env.into_iter().map(|var| EnvVar {
+                    name: var.key,
+                    value: Some(var.val),
+                    ..EnvVar::default()
+                }));
+
+                (
+                    runtime.image,
+                    Some(runtime.command).filter(|c| !c.is_empty()),
+                    Some(runtime.args).filter(|a| !a.is_empty()),
+                    runtime
+                        .health_path
+                        .unwrap_or(DEFAULT_HEALTH_PATH.to_string()),
+                    env,
+                )
+            }
+            Some(Runtime::Mlflow(_)) | None => (
+                model_deployment.image.unwrap_or(deployment_image),
+                Some(vec!["/bin/bash".to_string()]),
+                Some(vec![
+                    "-c".to_string(),
+                    format!("export PATH=$HOME/.pyenv/bin:$PATH; mlflow models serve -m {model_source} --host 0.0.0.0"),
+                ]),
+                DEFAULT_HEALTH_PATH.to_string(),
+                registry.serving_env(),
+            ),
+        };
 
         Ok(Deployment {
             metadata: ObjectMeta {
@@ -513,7 +1032,11 @@ impl Model {
                     match_labels: Some(labels.clone()),
                     ..LabelSelector::default()
                 },
-                replicas: Some(model_deployment.replicas.unwrap_or(1)),
+                // Autoscaled deployments leave the replica count to the autoscaler.
+                replicas: match model_deployment.autoscaling {
+                    Some(_) => None,
+                    None => Some(model_deployment.replicas.unwrap_or(1)),
+                },
                 template: PodTemplateSpec {
                     metadata: Some(ObjectMeta {
                         labels: Some(labels.clone()),
@@ -526,27 +1049,14 @@ impl Model {
                             "fsGroup": 2000
                             }))?),
                             name: "main".to_string(),
-                            image: Some(
-                                model_deployment
-                                    .image
-                                    .unwrap_or(deployment_image),
-                            ),
-                            command: Some(vec!["/bin/bash".to_string()]),
-                            args: Some(vec![
-                                "-c".to_string(),
-                                format!("export PATH=$HOME/.pyenv/bin:$PATH; mlflow models serve -m {model_source} --host 0.0.0.0"),
-                            ]),
-                            resources: Some(ResourceRequirements{
+                            image: Some(image),
+                            command,
+                            args,
+                            resources: Some(ResourceRequirements {
                                 limits: Some(resource_map_conv(model_deployment.resources)),
                                 requests: None,
                             }),
-                            env: Some(vec![EnvVar {
-                                name: "MLFLOW_TRACKING_URI".to_string(),
-                                value: Some(
-                                    "http://mlflow.default.svc.cluster.local:5000".to_string(),
-                                ),
-                                ..EnvVar::default()
-                            }]),
+                            env: Some(env),
                             ports: Some(vec![ContainerPort {
                                 container_port: server_port,
                                 ..ContainerPort::default()
@@ -554,7 +1064,7 @@ impl Model {
                             readiness_probe: Some(Probe {
                                 http_get: Some(HTTPGetAction {
                                     port: IntOrString::Int(server_port),
-                                    path: Some("/health".to_string()),
+                                    path: Some(health_path),
                                     ..HTTPGetAction::default()
                                 }),
                                 ..Probe::default()
@@ -602,33 +1112,24 @@ impl ProjectCtrlCfg {
     }
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
-struct MlflowModelVersionsRes {
-    model_versions: Vec<MlflowModelVersion>,
-}
-
-#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
-struct MlflowModelVersionResponse {
-    model_version: MlflowModelVersion,
-}
-
-#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Default)]
-pub struct MlflowModelVersion {
-    name: String,
-    pub version: String,
-    current_stage: String,
-    creation_timestamp: i64,
-    pub source: String,
-    run_id: String,
-}
-
 #[cfg(test)]
 mod test {
-    use std::time::Duration;
+    use std::{os::unix::fs::PermissionsExt, time::Duration};
 
+    use assert_fs::prelude::*;
+    use chrono::{TimeZone, Utc};
     use serde_json::json;
 
-    use super::{Project, ProjectCtrlCfg, Result};
+    use super::{
+        super::model_registry::MlflowRegistry, is_data_set_shared, local_name,
+        mlflow_scoring_command, namespace_name, project_name, DeletionPolicy, Project,
+        ProjectCtrlCfg, Result, TaskCfg, MODEL_URI_ENV,
+    };
+    use crate::grpc::{
+        model_deployment_cfg::Runtime, task_cfg::Executor, AutoscalingCfg, BatchInferenceCfg,
+        CustomExecutor, CustomRuntime, DataSetCfg, DataSetGrant, EnvVar, ModelMonitoringCfg,
+        ProjectCfg,
+    };
     use serial_test::serial;
 
     fn test_project() -> Result<Project> {
@@ -666,6 +1167,22 @@ mod test {
         }))?)
     }
 
+    /// Returns the test project with the uid it gets once it has been created, which generated
+    /// objects reference as their owner.
+    fn created_test_project() -> Result<Project> {
+        let mut project = test_project()?;
+        project.metadata.uid = Some("projectuid".to_string());
+
+        Ok(project)
+    }
+
+    fn env_var(task: &TaskCfg, key: &str) -> Option<String> {
+        task.env
+            .iter()
+            .find(|var| var.key == key)
+            .map(|var| var.val.clone())
+    }
+
     #[tokio::test]
     #[serial]
     async fn produces_valid_deployment() -> Result<()> {
@@ -682,7 +1199,11 @@ mod test {
 
         insta::assert_yaml_snapshot!(
             &project.spec.cfg.models.clone()[0]
-                .generate_model_deployment(ctrl_cfg.deployment_image, "model_source".to_string())
+                .generate_model_deployment(
+                    ctrl_cfg.deployment_image,
+                    "model_source".to_string(),
+                    &MlflowRegistry::new("mlflowurl".to_string())
+                )
                 .await?
         );
 
@@ -715,6 +1236,276 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn canary_ingress_routes_a_share_of_traffic_to_the_canary() -> Result<()> {
+        let project = test_project()?;
+        let model = &project.spec.cfg.models[0];
+
+        let ingress = model.generate_canary_ingress(
+            "testhost".to_string(),
+            None,
+            "projectname".to_string(),
+            20,
+        )?;
+
+        let annotations = ingress.metadata.annotations.clone().unwrap_or_default();
+
+        assert_eq!(ingress.metadata.name.as_deref(), Some("test-canary"));
+        assert_eq!(
+            annotations
+                .get("nginx.ingress.kubernetes.io/canary-weight")
+                .map(String::as_str),
+            Some("20")
+        );
+
+        let backend = ingress.spec.unwrap().rules.unwrap()[0]
+            .http
+            .clone()
+            .unwrap()
+            .paths[0]
+            .backend
+            .service
+            .clone()
+            .unwrap();
+
+        assert_eq!(backend.name, "test-canary");
+
+        Ok(())
+    }
+
+    #[test]
+    fn autoscaled_deployments_get_an_autoscaler() -> Result<()> {
+        let mut project = test_project()?;
+        let model = &mut project.spec.cfg.models[0];
+
+        assert!(model.generate_model_autoscaler()?.is_none());
+
+        model.deployment.as_mut().unwrap().autoscaling = Some(AutoscalingCfg {
+            min_replicas: 2,
+            max_replicas: 5,
+            ..AutoscalingCfg::default()
+        });
+
+        let spec = model.generate_model_autoscaler()?.unwrap().spec.unwrap();
+
+        assert_eq!(spec.scale_target_ref.name, "test");
+        assert_eq!((spec.min_replicas, spec.max_replicas), (Some(2), 5));
+        assert_eq!(
+            spec.metrics.unwrap()[0]
+                .resource
+                .as_ref()
+                .unwrap()
+                .target
+                .average_utilization,
+            Some(80)
+        );
+
+        model.deployment.as_mut().unwrap().autoscaling = Some(AutoscalingCfg {
+            min_replicas: 6,
+            max_replicas: 5,
+            ..AutoscalingCfg::default()
+        });
+
+        assert!(model.generate_model_autoscaler().is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn custom_runtimes_serve_their_own_image() -> Result<()> {
+        let mut project = test_project()?;
+        let model = &mut project.spec.cfg.models[0];
+
+        model.deployment.as_mut().unwrap().runtime = Some(Runtime::Custom(CustomRuntime {
+            image: "myserver".to_string(),
+            command: vec!["uvicorn".to_string(), "main:app".to_string()],
+            port: Some(8080),
+            health_path: Some("/ready".to_string()),
+            env: vec![EnvVar {
+                key: "WORKERS".to_string(),
+                val: "2".to_string(),
+            }],
+            ..CustomRuntime::default()
+        }));
+
+        let deployment = model
+            .generate_model_deployment(
+                "test_img".to_string(),
+                "model_source".to_string(),
+                &MlflowRegistry::new("mlflowurl".to_string()),
+            )
+            .await?;
+        let container = &deployment.spec.unwrap().template.spec.unwrap().containers[0];
+
+        assert_eq!(container.image.as_deref(), Some("myserver"));
+        assert_eq!(container.args, None);
+        assert_eq!(container.ports.as_ref().unwrap()[0].container_port, 8080);
+
+        let probe = container.readiness_probe.clone().unwrap().http_get.unwrap();
+        assert_eq!(probe.path.as_deref(), Some("/ready"));
+
+        let env = container.env.clone().unwrap();
+        assert!(env
+            .iter()
+            .any(|v| v.name == "AME_MODEL_URI" && v.value.as_deref() == Some("model_source")));
+        assert!(env.iter().any(|v| v.name == "WORKERS"));
+
+        let service = model.generate_model_service()?;
+        assert_eq!(service.spec.unwrap().ports.unwrap()[0].port, 8080);
+
+        model.deployment.as_mut().unwrap().runtime = Some(Runtime::Custom(CustomRuntime {
+            image: "".to_string(),
+            ..CustomRuntime::default()
+        }));
+
+        assert!(model
+            .generate_model_deployment(
+                "test_img".to_string(),
+                "model_source".to_string(),
+                &MlflowRegistry::new("mlflowurl".to_string()),
+            )
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn batch_inference_writes_a_new_output_data_set_version() -> Result<()> {
+        let mut project = created_test_project()?;
+        project.spec.cfg.data_sets = vec![
+            DataSetCfg {
+                name: "features".to_string(),
+                path: "data/features.csv".to_string(),
+                ..DataSetCfg::default()
+            },
+            DataSetCfg {
+                name: "predictions".to_string(),
+                path: "data/predictions".to_string(),
+                ..DataSetCfg::default()
+            },
+        ];
+
+        let model = &mut project.spec.cfg.models[0];
+        model.batch_inference = Some(BatchInferenceCfg {
+            input_data_set: "features".to_string(),
+            output_data_set: "predictions".to_string(),
+            schedule: "0 2 * * *".to_string(),
+            ..BatchInferenceCfg::default()
+        });
+        let model = model.clone();
+
+        let instant = Utc.with_ymd_and_hms(2023, 8, 1, 2, 0, 0).unwrap();
+        let data_set =
+            project.generate_batch_inference_data_set(&model, "s3://models/3", "3", instant)?;
+
+        let task = data_set.spec.cfg.task.unwrap();
+        let env = |key: &str| env_var(&task, key);
+
+        assert_eq!(
+            task.name,
+            Some(format!("score-test-{}", instant.timestamp()))
+        );
+        assert_eq!(task.data_sets, vec!["features".to_string()]);
+        assert_eq!(env("AME_MODEL_URI").as_deref(), Some("s3://models/3"));
+        assert_eq!(env("AME_OUTPUT_PATH").as_deref(), Some("data/predictions"));
+        assert!(task
+            .executor
+            .unwrap()
+            .command()
+            .contains("input_path='data/features.csv'"));
+
+        project.spec.cfg.data_sets[1].task = Some(TaskCfg::default());
+
+        assert!(project
+            .generate_batch_inference_data_set(&model, "s3://models/3", "3", instant)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn mlflow_scoring_scores_every_file_in_a_data_set() -> Result<()> {
+        let dir = assert_fs::TempDir::new().unwrap();
+        dir.child("data/features/2023-07.csv").touch().unwrap();
+        dir.child("data/features/2023-08/eu.csv").touch().unwrap();
+        dir.child("data/features/.done").touch().unwrap();
+
+        // Stands in for MLflow, copying its input to the output path.
+        let bin = dir.child("bin/mlflow");
+        bin.write_str("#!/bin/sh\ncp \"$6\" \"$8\"\n").unwrap();
+        std::fs::set_permissions(bin.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let run = |input_path: &str| {
+            std::process::Command::new("bash")
+                .arg("-c")
+                .arg(mlflow_scoring_command(input_path, "data/predictions"))
+                .current_dir(dir.path())
+                .env(
+                    "PATH",
+                    format!("{}:/usr/bin:/bin", dir.child("bin").display()),
+                )
+                .env(MODEL_URI_ENV, "s3://models/3")
+                .status()
+                .unwrap()
+        };
+
+        assert!(run("data/features").success());
+        dir.child("data/predictions/2023-07.csv")
+            .assert(predicates::path::exists());
+        dir.child("data/predictions/2023-08/eu.csv")
+            .assert(predicates::path::exists());
+        dir.child("data/predictions/.done.csv")
+            .assert(predicates::path::missing());
+
+        assert!(run("data/features/2023-07.csv").success());
+        assert!(!run("data/missing").success());
+
+        Ok(())
+    }
+
+    #[test]
+    fn monitoring_tasks_collect_a_report_for_the_deployed_version() -> Result<()> {
+        let mut project = created_test_project()?;
+
+        let model = &mut project.spec.cfg.models[0];
+        model.monitoring = Some(ModelMonitoringCfg {
+            schedule: "0 * * * *".to_string(),
+            reference_data_set: "training".to_string(),
+            task: Some(TaskCfg {
+                executor: Some(Executor::Custom(CustomExecutor {
+                    python_version: "3.11".to_string(),
+                    command: "python drift.py".to_string(),
+                })),
+                ..TaskCfg::default()
+            }),
+            ..ModelMonitoringCfg::default()
+        });
+        let model = model.clone();
+
+        let instant = Utc.with_ymd_and_hms(2023, 8, 1, 2, 0, 0).unwrap();
+        let task = project.generate_monitoring_task(&model, "s3://models/3", "3", instant)?;
+
+        let env = |key: &str| env_var(&task.spec.cfg, key);
+
+        assert_eq!(
+            task.metadata.name,
+            Some(format!("monitor-test-{}", instant.timestamp()))
+        );
+        assert_eq!(task.spec.cfg.data_sets, vec!["training".to_string()]);
+        assert_eq!(env("AME_MODEL_URI").as_deref(), Some("s3://models/3"));
+        assert_eq!(
+            env("AME_REPORT_PATH").as_deref(),
+            Some("monitoring-report.json")
+        );
+        assert_eq!(
+            task.spec.monitoring_report.map(|report| report.path),
+            Some("monitoring-report.json".to_string())
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn produces_valid_ingress() -> Result<()> {
@@ -736,4 +1527,132 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn generated_tasks_receive_the_project_defaults() -> Result<()> {
+        let mut project = created_test_project()?;
+        project.spec.cfg.defaults = Some(TaskCfg {
+            executor: Some(Executor::Custom(CustomExecutor {
+                python_version: "3.11".to_string(),
+                command: "python main.py".to_string(),
+            })),
+            resources: [("memory".to_string(), "4Gi".to_string())].into(),
+            env: vec![EnvVar {
+                key: "STAGE".to_string(),
+                val: "prod".to_string(),
+            }],
+            ..TaskCfg::default()
+        });
+        project.spec.cfg.data_sets = vec![DataSetCfg {
+            name: "features".to_string(),
+            path: "data/features.csv".to_string(),
+            task: Some(TaskCfg {
+                name: Some("prepare".to_string()),
+                ..TaskCfg::default()
+            }),
+            ..DataSetCfg::default()
+        }];
+
+        let training_task = project.generate_model_training_task("test")?;
+        assert!(matches!(
+            training_task.spec.cfg.executor,
+            Some(Executor::PipEnv(_))
+        ));
+        assert_eq!(
+            training_task.spec.cfg.resources.get("memory").unwrap(),
+            "4Gi"
+        );
+        assert_eq!(training_task.spec.cfg.env[0].key, "STAGE");
+
+        let data_set_task = project
+            .generate_data_set_task("features".to_string())
+            .unwrap();
+        assert!(matches!(
+            data_set_task.spec.cfg.executor,
+            Some(Executor::Custom(_))
+        ));
+
+        let data_set = project.generate_data_set("features".to_string())?;
+        assert_eq!(data_set.spec.cfg.task.unwrap().env[0].val, "prod");
+
+        Ok(())
+    }
+
+    #[test]
+    fn projects_without_a_deletion_policy_cascade() -> Result<()> {
+        let mut project = test_project()?;
+
+        assert_eq!(project.deletion_policy()?, DeletionPolicy::Cascade);
+
+        project.spec.deletion_policy = Some("Purge".to_string());
+        assert_eq!(project.deletion_policy()?, DeletionPolicy::Purge);
+
+        project.spec.deletion_policy = Some("RetainData".to_string());
+        assert!(project.deletion_policy()?.retains_data());
+
+        project.spec.deletion_policy = Some("KeepEverything".to_string());
+        assert!(project.deletion_policy().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parses_namespaced_data_set_references() {
+        let reference = "team-a/sales.transactions".to_string();
+
+        assert_eq!(
+            namespace_name(reference.clone()),
+            Some("team-a".to_string())
+        );
+        assert_eq!(project_name(reference.clone()), Some("sales".to_string()));
+        assert_eq!(local_name(reference), "transactions");
+
+        assert_eq!(namespace_name("sales.transactions".to_string()), None);
+    }
+
+    #[test]
+    fn data_sets_are_only_shared_through_grants() {
+        let owner = ProjectCfg {
+            name: "sales".to_string(),
+            grants: vec![
+                DataSetGrant {
+                    data_set: "transactions".to_string(),
+                    namespaces: vec!["team-b".to_string()],
+                    projects: vec!["forecasting".to_string()],
+                },
+                DataSetGrant {
+                    data_set: "*".to_string(),
+                    namespaces: vec!["team-c".to_string()],
+                    projects: vec![],
+                },
+            ],
+            ..ProjectCfg::default()
+        };
+
+        assert!(is_data_set_shared(
+            &owner,
+            "transactions",
+            "team-b",
+            "forecasting"
+        ));
+        assert!(!is_data_set_shared(
+            &owner,
+            "transactions",
+            "team-b",
+            "churn"
+        ));
+        assert!(!is_data_set_shared(
+            &owner,
+            "customers",
+            "team-b",
+            "forecasting"
+        ));
+        assert!(is_data_set_shared(&owner, "customers", "team-c", "churn"));
+        assert!(!is_data_set_shared(
+            &owner,
+            "transactions",
+            "team-d",
+            "forecasting"
+        ));
+    }
 }
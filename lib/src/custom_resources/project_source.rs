@@ -1,15 +1,27 @@
 use crate::{
+    ctrl::AmeResource,
     custom_resources::{
-        project::{Project, ProjectSpec},
+        common::{bounded_name, short_hash, MAX_NAME_LEN},
+        new_task::{self, Task, TaskBuilder},
+        project::{generate_task_name, Project, ProjectSpec},
         secrets::SecretCtrl,
         Error, Result,
     },
-    grpc::{GitProjectSource, ProjectCfg, ProjectSourceState, ProjectSourceStatus},
+    grpc::{
+        GitProjectSource, ProjectCfg, ProjectSourceIssue, ProjectSourceIssueType,
+        ProjectSourceState, ProjectSourceStatus,
+    },
+    interpolation::InterpolationContext,
+    validation::Diagnostics,
 };
 use duration_string::DurationString;
 use envconfig::Envconfig;
 use futures::{future::BoxFuture, FutureExt, StreamExt};
-use git2::{build::RepoBuilder, Cred, FetchOptions};
+use git2::{
+    build::RepoBuilder, BranchType, Cred, FetchOptions, ObjectType, Oid, Repository, TreeWalkMode,
+    TreeWalkResult,
+};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use k8s_openapi::{
     api::core::v1::Secret, apimachinery::pkg::apis::meta::v1::OwnerReference, chrono::DateTime,
 };
@@ -24,12 +36,13 @@ use kube::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fs,
     path::Path,
     sync::Arc,
     time::{Duration, SystemTime},
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 #[kube(
@@ -46,6 +59,74 @@ pub struct ProjectSourceSpec {
     pub cfg: crate::grpc::ProjectSourceCfg,
 }
 
+/// Label holding the commit a run started by a push trigger is pinned to.
+pub static GIT_COMMIT_LABEL: &str = "ame.teainspace.com/git-commit";
+
+/// Annotation holding the Git repository a project was synchronised from.
+pub static GIT_REPOSITORY_ANNOTATION: &str = "gitrepository";
+
+/// Annotation holding the directory of a project within its Git repository, it is only set for
+/// projects outside the root of the repository.
+pub static GIT_PATH_ANNOTATION: &str = "gitpath";
+
+/// Files a project is read from, `ame.yaml` is used if a directory has both.
+static AME_FILES: [&str; 2] = ["ame.yaml", "ame.yml"];
+
+/// A new commit on a branch of a project source's repository.
+#[derive(Clone, Debug, PartialEq)]
+struct GitPush {
+    branch: String,
+    commit: String,
+    changed_paths: Vec<String>,
+}
+
+impl GitPush {
+    /// Returns the push as seen by the project in directory `path`, with the changed paths made
+    /// relative to the directory. Pushes which change nothing in the directory are dropped.
+    fn relative_to(&self, path: &str) -> Option<GitPush> {
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+
+        let changed_paths: Vec<String> = self
+            .changed_paths
+            .iter()
+            .filter_map(|changed_path| changed_path.strip_prefix(&prefix))
+            .map(str::to_string)
+            .collect();
+
+        if changed_paths.is_empty() {
+            return None;
+        }
+
+        Some(GitPush {
+            changed_paths,
+            ..self.clone()
+        })
+    }
+}
+
+/// A project found in a project source's repository.
+struct SourceProject {
+    /// Directory of the project within the repository, empty for the root of the repository.
+    path: String,
+    spec: ProjectSpec,
+}
+
+/// A project file in a project source's repository which could not be read.
+struct InvalidProjectFile {
+    /// Directory of the project within the repository, empty for the root of the repository.
+    path: String,
+    diagnostics: Diagnostics,
+}
+
+/// The projects found while synchronising a project source and the commits pushed since the
+/// previous synchronisation.
+struct SourceSync {
+    projects: Vec<SourceProject>,
+    invalid: Vec<InvalidProjectFile>,
+    commits: BTreeMap<String, String>,
+    pushes: Vec<GitPush>,
+}
+
 #[derive(Envconfig, Clone)]
 pub struct ProjectSrcCtrlCfg {
     #[envconfig(from = "NAMESPACE", default = "ame-system")]
@@ -53,6 +134,46 @@ pub struct ProjectSrcCtrlCfg {
 }
 
 impl ProjectSource {
+    /// Returns an issue for every project created from this source which is not in `synced`,
+    /// i.e. whose project file has been removed from the repository.
+    ///
+    /// Removed projects are not deleted, as deleting a project must be approved. They are
+    /// reported in the status of the source until they are deleted.
+    async fn removed_projects(
+        &self,
+        projects: &Api<Project>,
+        synced: &BTreeSet<String>,
+    ) -> Result<Vec<ProjectSourceIssue>> {
+        let Some(uid) = self.uid() else {
+            return Ok(vec![]);
+        };
+
+        let mut issues = vec![];
+
+        for project in projects.list(&ListParams::default()).await?.items {
+            let name = project.name_any();
+
+            if synced.contains(&name) || !project.owner_references().iter().any(|o| o.uid == uid) {
+                continue;
+            }
+
+            info!(
+                "the project file of project {} was removed from source {}",
+                name,
+                self.name_any()
+            );
+
+            issues.push(ProjectSourceIssue {
+                issue_type: ProjectSourceIssueType::ProjectRemoved.into(),
+                explanation: Some(format!(
+                    "the project file of project {name} was removed from the repository, approve the deletion of the project and delete it to remove it"
+                )),
+            });
+        }
+
+        Ok(issues)
+    }
+
     async fn git_secret(&self, secrets: Api<Secret>) -> Result<Option<String>> {
         let Some(GitProjectSource {
             secret: Some(secret_name),
@@ -66,7 +187,7 @@ impl ProjectSource {
         ))
     }
 
-    async fn extract_projects(&self, secrets: Api<Secret>) -> Result<Vec<ProjectSpec>> {
+    async fn extract_projects(&self, secrets: Api<Secret>) -> Result<SourceSync> {
         debug!(
             "Attempting to extract project file from repository: {:?}",
             self.spec.cfg.git
@@ -81,7 +202,7 @@ impl ProjectSource {
             return Err(Error::MissingProjectSrc("git".to_string()));
         };
 
-        let _ = fs::remove_dir_all("/tmp/".to_string() + &self.name_any());
+        let _ = fs::remove_dir_all(format!("/tmp/{}", self.name_any()));
 
         let git_secret = self.git_secret(secrets).await?;
 
@@ -109,36 +230,244 @@ impl ProjectSource {
 
         // TODO: ensure that cloning never clashes with other directories.
         // TODO: How will we handle large repositories?
-        let _repo = builder.clone(
+        let repo = builder.clone(
             &repository,
             Path::new(&format!("/tmp/{}", &self.name_any())),
         )?;
 
-        let ame_file = if let Ok(ame_file) =
-            fs::read_to_string(format!("/tmp/{}/ame.yaml", self.name_any()))
-        {
-            ame_file
-        } else if let Ok(ame_file) = fs::read_to_string(format!("/tmp/{}/ame.yml", self.name_any()))
-        {
-            ame_file
-        } else {
+        let project_files = self.project_files(&repository_files(&repo)?)?;
+
+        if project_files.is_empty() {
             return Err(Error::MissingAmeFile(self.name_any()));
+        }
+
+        let (projects, invalid) = read_projects(
+            Path::new(&format!("/tmp/{}", self.name_any())),
+            project_files,
+            git_secret.is_some(),
+        )?;
+
+        let (commits, pushes) = self.detect_pushes(&repo)?;
+
+        fs::remove_dir_all(format!("/tmp/{}", self.name_any()))?;
+
+        Ok(SourceSync {
+            projects,
+            invalid,
+            commits,
+            pushes,
+        })
+    }
+
+    /// Selects the project files among the files of the repository, returning the directory
+    /// and path of each one.
+    ///
+    /// Every directory with an `ame.yaml` or `ame.yml` file holds a project. Directories are
+    /// matched against the include and exclude globs of the source, the root of the repository
+    /// is matched as `.`. Without include globs every directory is included.
+    fn project_files(&self, files: &[String]) -> Result<Vec<(String, String)>> {
+        let (include, exclude) = match self.spec.cfg.git {
+            Some(ref git) => (git.include.clone(), git.exclude.clone()),
+            None => (vec![], vec![]),
         };
 
-        debug!("Reading ame.yaml: {}", ame_file);
+        let include_set = glob_set(&include)?;
+        let exclude_set = glob_set(&exclude)?;
 
-        let project: ProjectCfg = serde_yaml::from_str(&ame_file)?;
-        let project = ProjectSpec {
-            cfg: project,
-            deletion_approved: false,
-            enable_triggers: Some(false),
+        let mut project_files = BTreeMap::new();
+
+        for file in files {
+            let path = Path::new(file);
+
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            if !AME_FILES.contains(&name) {
+                continue;
+            }
+
+            let dir = path
+                .parent()
+                .map(|dir| dir.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let glob_path = if dir.is_empty() { "." } else { dir.as_str() };
+
+            if (!include.is_empty() && !include_set.is_match(glob_path))
+                || exclude_set.is_match(glob_path)
+            {
+                continue;
+            }
+
+            if name == AME_FILES[0] || !project_files.contains_key(&dir) {
+                project_files.insert(dir, file.clone());
+            }
+        }
+
+        Ok(project_files.into_iter().collect())
+    }
+
+    /// Compares the head of every branch in the repository with the commits recorded at the
+    /// previous synchronisation, returning the current heads and the branches which moved.
+    ///
+    /// Branches seen for the first time are only recorded, otherwise every branch would count
+    /// as pushed when a project source is created.
+    fn detect_pushes(&self, repo: &Repository) -> Result<(BTreeMap<String, String>, Vec<GitPush>)> {
+        let previous_commits = self
+            .status
+            .as_ref()
+            .map(|status| status.commits.clone())
+            .unwrap_or_default();
+
+        let mut commits = BTreeMap::new();
+        let mut pushes = vec![];
+
+        for branch in repo.branches(Some(BranchType::Remote))? {
+            let (branch, _) = branch?;
+
+            let Some(name) = branch
+                .name()?
+                .and_then(|name| name.strip_prefix("origin/"))
+                .filter(|name| *name != "HEAD")
+                .map(str::to_string)
+            else {
+                continue;
+            };
+
+            let commit = branch.get().peel_to_commit()?;
+            commits.insert(name.clone(), commit.id().to_string());
+
+            let Some(previous_commit) = previous_commits.get(&name) else {
+                continue;
+            };
+
+            if previous_commit == &commit.id().to_string() {
+                continue;
+            }
+
+            // If the previous commit is gone, e.g. after a force push, every file counts as changed.
+            let previous_tree = Oid::from_str(previous_commit)
+                .and_then(|id| repo.find_commit(id))
+                .and_then(|commit| commit.tree())
+                .ok();
+
+            let diff =
+                repo.diff_tree_to_tree(previous_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+            let changed_paths: BTreeSet<String> = diff
+                .deltas()
+                .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+                .flatten()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect();
+
+            pushes.push(GitPush {
+                branch: name,
+                commit: commit.id().to_string(),
+                changed_paths: changed_paths.into_iter().collect(),
+            });
+        }
+
+        Ok((commits, pushes))
+    }
+
+    /// Starts a run of every task with a push trigger matching one of `pushes`, each run is
+    /// pinned to the pushed commit.
+    ///
+    /// Projects outside the root of the repository only see the paths changed in their own
+    /// directory, relative to that directory.
+    async fn start_push_runs(
+        &self,
+        project: &Project,
+        pushes: &[GitPush],
+        tasks: &Api<Task>,
+    ) -> Result<()> {
+        let Some(GitProjectSource {
+            ref repository,
+            ref username,
+            ref secret,
+            ..
+        }) = self.spec.cfg.git
+        else {
+            return Ok(());
         };
 
-        debug!("Successfully extracted project: {:?}", project);
+        let Some(oref) = project.gen_owner_ref() else {
+            return Err(Error::MissingProject(project.name_any()));
+        };
 
-        fs::remove_dir_all("/tmp/".to_string() + &self.name_any())?;
+        let path = project.annotations().get(GIT_PATH_ANNOTATION).cloned();
+        let pushes: Vec<GitPush> = match path {
+            Some(ref path) => pushes
+                .iter()
+                .filter_map(|push| push.relative_to(path))
+                .collect(),
+            None => pushes.to_vec(),
+        };
+
+        for task_cfg in project.spec.cfg.tasks.iter() {
+            let Some(push_trigger) = task_cfg
+                .triggers
+                .as_ref()
+                .and_then(|trigger| trigger.on_push.as_ref())
+            else {
+                continue;
+            };
+
+            for push in pushes
+                .iter()
+                .filter(|push| push_trigger.matches(&push.branch, &push.changed_paths))
+            {
+                // Projects in private repositories with push triggers are rejected when they
+                // are read, see `private_repository_problems`.
+                if secret.is_some() {
+                    warn!(
+                        "skipping push triggered run of {:?}, runs can not be pinned to commits in private repositories yet",
+                        task_cfg.name
+                    );
+                    continue;
+                }
 
-        Ok(vec![project])
+                let task_name = generate_task_name(
+                    project.name_any(),
+                    task_cfg.name.clone().unwrap_or_default(),
+                );
+
+                let mut task_builder =
+                    TaskBuilder::from_cfg(project.spec.cfg.with_task_defaults(task_cfg.clone()));
+                task_builder.add_owner_reference(oref.clone());
+                task_builder.set_name(format!("{task_name}-{}", &push.commit[..8]));
+                task_builder.set_project(project.spec.cfg.name.clone());
+                task_builder.set_project_src(new_task::ProjectSource::Git {
+                    repository: repository.clone(),
+                    reference: push.commit.clone(),
+                    user_name: username.clone().unwrap_or_default(),
+                    secret: None,
+                    path: path.clone(),
+                });
+
+                let mut task = task_builder.build();
+                task.labels_mut()
+                    .insert(GIT_COMMIT_LABEL.to_string(), push.commit.clone());
+
+                info!(
+                    "starting run {} for commit {} on branch {}",
+                    task.name_any(),
+                    push.commit,
+                    push.branch
+                );
+
+                tasks
+                    .patch(
+                        &task.name_any(),
+                        &PatchParams::apply("ame-controller"),
+                        &kube::api::Patch::Apply(task),
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
     }
 
     fn sync_interval(&self) -> Result<Duration> {
@@ -171,6 +500,147 @@ impl ProjectSource {
     }
 }
 
+/// Lists the files in the checked out commit of `repo`, relative to the root of the repository.
+fn repository_files(repo: &Repository) -> Result<Vec<String>> {
+    let mut files = vec![];
+
+    repo.head()?
+        .peel_to_tree()?
+        .walk(TreeWalkMode::PreOrder, |dir, entry| {
+            if let (Some(ObjectType::Blob), Some(name)) = (entry.kind(), entry.name()) {
+                files.push(format!("{dir}{name}"));
+            }
+
+            TreeWalkResult::Ok
+        })?;
+
+    Ok(files)
+}
+
+/// Reads the project files found in a repository checked out at `root`.
+///
+/// Every file is read on its own, a file with problems does not stop the remaining projects
+/// from being read. `env` expressions are not available, and projects in `private`
+/// repositories are also checked for features which are not supported in them.
+fn read_projects(
+    root: &Path,
+    project_files: Vec<(String, String)>,
+    private: bool,
+) -> Result<(Vec<SourceProject>, Vec<InvalidProjectFile>)> {
+    let mut projects = vec![];
+    let mut invalid = vec![];
+    let context = InterpolationContext::repository(root);
+
+    for (path, file) in project_files {
+        let ame_file = fs::read_to_string(root.join(&file))?;
+
+        debug!("Reading {}: {}", file, ame_file);
+
+        let checks = |cfg: &ProjectCfg| {
+            if private {
+                private_repository_problems(cfg)
+            } else {
+                vec![]
+            }
+        };
+
+        let project = match ProjectCfg::from_yaml_checked(root, &file, &ame_file, &context, checks)
+        {
+            Ok(project) => project,
+            Err(diagnostics) => {
+                warn!("skipping invalid project file {}: {}", file, diagnostics);
+                invalid.push(InvalidProjectFile { path, diagnostics });
+                continue;
+            }
+        };
+
+        let project = ProjectSpec {
+            cfg: project,
+            deletion_approved: false,
+            enable_triggers: Some(false),
+            deletion_policy: None,
+        };
+
+        debug!("Successfully extracted project: {:?}", project);
+
+        projects.push(SourceProject {
+            path,
+            spec: project,
+        });
+    }
+
+    Ok((projects, invalid))
+}
+
+/// Returns the path and a description of every push trigger in `cfg`, push triggered runs are
+/// pinned to the pushed commit and that is not supported in private repositories yet.
+fn private_repository_problems(cfg: &ProjectCfg) -> Vec<(String, String)> {
+    cfg.tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, task)| {
+            task.triggers
+                .as_ref()
+                .and_then(|trigger| trigger.on_push.as_ref())
+                .is_some()
+        })
+        .map(|(i, _)| {
+            (
+                format!("tasks[{i}].triggers.onPush"),
+                "push triggers are not supported in private repositories".to_string(),
+            )
+        })
+        .collect()
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        builder.add(
+            Glob::new(pattern)
+                .map_err(|e| Error::InvalidProjectSrc(format!("invalid glob {pattern}: {e}")))?,
+        );
+    }
+
+    builder
+        .build()
+        .map_err(|e| Error::InvalidProjectSrc(e.to_string()))
+}
+
+/// Returns the name of the Project for the project in directory `path` of a source's repository,
+/// the project at the root of the repository is named after the source.
+///
+/// Paths which are not valid in a name, e.g. `models/churn`, end in a hash of the path so they do
+/// not collide with paths they are converted to, e.g. `models-churn`. Names are shortened to fit
+/// in a label.
+fn source_project_name(src_name: &str, path: &str) -> String {
+    if path.is_empty() {
+        return src_name.to_string();
+    }
+
+    let sanitized: String = path
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+
+    let sanitized = sanitized.trim_matches('-');
+
+    let name = if sanitized == path {
+        format!("{src_name}-{sanitized}")
+    } else {
+        format!("{src_name}-{sanitized}-{}", short_hash(path))
+    };
+
+    bounded_name(&name, MAX_NAME_LEN)
+}
+
 struct Context {
     client: Client,
     config: ProjectSrcCtrlCfg,
@@ -188,14 +658,15 @@ async fn reconcile(src: Arc<ProjectSource>, ctx: Arc<Context>) -> Result<Action>
     };
 
     let secrets = Api::<Secret>::namespaced(ctx.client.clone(), &ctx.config.namespace);
+    let tasks = Api::<Task>::namespaced(ctx.client.clone(), &ctx.config.namespace);
 
     let mut patch: ProjectSource = _srcs.get_status(&src.name_any()).await?;
     patch.metadata.managed_fields = None;
 
     if src.requires_sync()? {
         info!("Synchronizing project source {}", src.name_any());
-        let project_specs = match src.extract_projects(secrets).await {
-            Ok(specs) => specs,
+        let sync = match src.extract_projects(secrets).await {
+            Ok(sync) => sync,
             Err(e) => {
                 error!(
                     "Failed to find AME project in source {}: {}",
@@ -221,52 +692,95 @@ async fn reconcile(src: Arc<ProjectSource>, ctx: Arc<Context>) -> Result<Action>
             }
         };
 
-        if project_specs.is_empty() {
+        if sync.projects.is_empty() {
             debug!("no project specs were found");
-            return Ok(Action::requeue(Duration::from_secs(50)));
         }
 
-        info!("Patch project {}", project_specs[0].cfg.name);
+        // Projects with an invalid project file are left as they are, rather than reported as
+        // removed.
+        let mut synced: BTreeSet<String> = sync
+            .invalid
+            .iter()
+            .map(|invalid| source_project_name(&src.name_any(), &invalid.path))
+            .collect();
+
+        for source_project in sync.projects {
+            info!("Patch project {}", source_project.spec.cfg.name);
+
+            let mut project = Project {
+                metadata: ObjectMeta {
+                    name: Some(source_project_name(&src.name_any(), &source_project.path)),
+                    ..ObjectMeta::default()
+                },
+                spec: source_project.spec,
+                status: None,
+            };
+
+            if let Some(GitProjectSource { ref repository, .. }) = src.spec.cfg.git {
+                project.add_annotation(
+                    GIT_REPOSITORY_ANNOTATION.to_string(),
+                    repository.to_string(),
+                );
+            }
 
-        let mut project = Project {
-            metadata: ObjectMeta {
-                name: Some(src.name_any()),
-                ..ObjectMeta::default()
-            },
-            spec: project_specs[0].clone(),
-            status: None,
-        };
+            if !source_project.path.is_empty() {
+                project.add_annotation(GIT_PATH_ANNOTATION.to_string(), source_project.path);
+            }
 
-        if let Some(GitProjectSource { ref repository, .. }) = src.spec.cfg.git {
-            project.add_annotation("gitrepository".to_string(), repository.to_string());
-        }
+            let project = project.add_owner_reference(oref.clone());
 
-        let project = project.add_owner_reference(oref);
+            debug!("Patch project: {:?}", project);
 
-        debug!("Patch project: {:?}", project);
+            let project = projects
+                .patch(
+                    &project.name_any(),
+                    &PatchParams::apply("ame-controller"),
+                    &kube::api::Patch::Apply(project),
+                )
+                .await?;
 
-        projects
-            .patch(
-                &project.name_any(),
-                &PatchParams::apply("ame-controller"),
-                &kube::api::Patch::Apply(project),
+            synced.insert(project.name_any());
+
+            src.start_push_runs(&project, &sync.pushes, &tasks).await?;
+        }
+
+        let mut issues: Vec<ProjectSourceIssue> = sync
+            .invalid
+            .iter()
+            .map(|invalid| ProjectSourceIssue {
+                issue_type: ProjectSourceIssueType::InvalidProjectFile.into(),
+                explanation: Some(invalid.diagnostics.to_string()),
+            })
+            .collect();
+        issues.extend(src.removed_projects(&projects, &synced).await?);
+
+        let reason = if sync.invalid.is_empty() {
+            "project has been synced".to_string()
+        } else {
+            format!(
+                "projects have been synced, {} project files are invalid",
+                sync.invalid.len()
             )
-            .await?;
+        };
+
         let last_synced = Some(humantime::format_rfc3339(SystemTime::now()).to_string());
         let mut patch: ProjectSource = _srcs.get_status(&src.name_any()).await?;
         patch.metadata.managed_fields = None;
 
         if let Some(mut status) = patch.clone().status {
             status.last_synced = last_synced;
-            status.reason = Some("project has been synced".to_string());
+            status.commits = sync.commits;
+            status.reason = Some(reason);
             status.state = ProjectSourceState::Synchronized.into();
+            status.issues = issues;
             patch.status = Some(status);
         } else {
             patch.status = Some(ProjectSourceStatus {
                 last_synced,
-                reason: Some("project has been synced".to_string()),
+                reason: Some(reason),
                 state: ProjectSourceState::Synchronized.into(),
-                ..ProjectSourceStatus::default()
+                commits: sync.commits,
+                issues,
             })
         }
 
@@ -322,9 +836,11 @@ mod test {
     use assert_fs::prelude::*;
     use futures::{StreamExt, TryStreamExt};
     use k8s_openapi::api::core::v1::Secret;
-    use kube::api::DeleteParams;
 
-    use kube::{api::PostParams, core::WatchEvent};
+    use kube::{
+        api::{DeleteParams, PostParams},
+        core::WatchEvent,
+    };
     use serde_json::json;
     use serial_test::serial;
 
@@ -378,7 +894,13 @@ mod test {
 
         let client = Client::try_default().await?;
         let secrets = Api::<Secret>::default_namespaced(client);
-        let projects = project_src.extract_projects(secrets).await?;
+        let projects: Vec<ProjectSpec> = project_src
+            .extract_projects(secrets)
+            .await?
+            .projects
+            .into_iter()
+            .map(|project| project.spec)
+            .collect();
         insta::assert_yaml_snapshot!(&projects);
 
         test_dir
@@ -480,6 +1002,192 @@ mod test {
         panic!("failed to create project ",);
     }
 
+    #[test]
+    fn discovers_every_project_in_a_repository() -> Result<()> {
+        let mut project_src = test_project_src()?;
+        let files: Vec<String> = [
+            "ame.yaml",
+            "README.md",
+            "models/churn/ame.yml",
+            "models/churn/train.py",
+            "models/forecast/ame.yaml",
+            "models/forecast/ame.yml",
+            "experiments/ame.yaml",
+        ]
+        .iter()
+        .map(|file| file.to_string())
+        .collect();
+
+        assert_eq!(
+            project_src.project_files(&files)?,
+            vec![
+                ("".to_string(), "ame.yaml".to_string()),
+                (
+                    "experiments".to_string(),
+                    "experiments/ame.yaml".to_string()
+                ),
+                (
+                    "models/churn".to_string(),
+                    "models/churn/ame.yml".to_string()
+                ),
+                (
+                    "models/forecast".to_string(),
+                    "models/forecast/ame.yaml".to_string()
+                ),
+            ]
+        );
+
+        let git = project_src.spec.cfg.git.as_mut().unwrap();
+        git.include = vec!["models/*".to_string(), ".".to_string()];
+        git.exclude = vec!["models/forecast".to_string()];
+
+        assert_eq!(
+            project_src.project_files(&files)?,
+            vec![
+                ("".to_string(), "ame.yaml".to_string()),
+                (
+                    "models/churn".to_string(),
+                    "models/churn/ame.yml".to_string()
+                ),
+            ]
+        );
+
+        project_src.spec.cfg.git.as_mut().unwrap().exclude = vec!["models/[".to_string()];
+        assert!(project_src.project_files(&files).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_project_files_do_not_stop_other_projects_from_being_read() -> Result<()> {
+        let root = assert_fs::TempDir::new().unwrap();
+        root.child("ame.yaml")
+            .write_str("name: myproject\n")
+            .unwrap();
+        root.child("models/churn/ame.yaml")
+            .write_str("name: churn\nmodles: []\n")
+            .unwrap();
+
+        let (projects, invalid) = read_projects(
+            root.path(),
+            vec![
+                ("".to_string(), "ame.yaml".to_string()),
+                (
+                    "models/churn".to_string(),
+                    "models/churn/ame.yaml".to_string(),
+                ),
+            ],
+            false,
+        )?;
+
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].spec.cfg.name, "myproject");
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].path, "models/churn");
+        assert!(invalid[0]
+            .diagnostics
+            .to_string()
+            .starts_with("models/churn/ame.yaml:2:1:"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn push_triggers_are_rejected_in_private_repositories() -> Result<()> {
+        let root = assert_fs::TempDir::new().unwrap();
+        root.child("ame.yaml")
+            .write_str(
+                "name: myproject
+tasks:
+  - name: train
+    executor: !poetry
+      pythonVersion: \"3.11\"
+      command: python train.py
+    triggers:
+      onPush:
+        branches: [main]
+",
+            )
+            .unwrap();
+
+        let files = vec![("".to_string(), "ame.yaml".to_string())];
+
+        let (projects, invalid) = read_projects(root.path(), files.clone(), false)?;
+        assert_eq!((projects.len(), invalid.len()), (1, 0));
+
+        let (projects, invalid) = read_projects(root.path(), files, true)?;
+        assert_eq!((projects.len(), invalid.len()), (0, 1));
+        assert_eq!(
+            invalid[0].diagnostics.to_string(),
+            "ame.yaml:8:7: push triggers are not supported in private repositories"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn env_expressions_are_rejected_in_synced_projects() -> Result<()> {
+        let root = assert_fs::TempDir::new().unwrap();
+        root.child("ame.yaml")
+            .write_str(
+                "name: myproject
+vars:
+  bucket: ${{ env.AME_VAR_BUCKET }}
+",
+            )
+            .unwrap();
+
+        let files = vec![("".to_string(), "ame.yaml".to_string())];
+
+        let (projects, invalid) = read_projects(root.path(), files, false)?;
+        assert_eq!((projects.len(), invalid.len()), (0, 1));
+        assert_eq!(
+            invalid[0].diagnostics.to_string(),
+            "ame.yaml:3:3: `env.AME_VAR_BUCKET` is not available in projects synced from a project source, use vars instead"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn projects_are_named_after_their_directory() {
+        assert_eq!(source_project_name("mysrc", ""), "mysrc");
+        assert_eq!(
+            source_project_name("mysrc", "models-churn"),
+            "mysrc-models-churn"
+        );
+        assert!(
+            source_project_name("mysrc", "models/Churn_v2").starts_with("mysrc-models-churn-v2-")
+        );
+    }
+
+    #[test]
+    fn project_names_do_not_collide_and_fit_in_a_label() {
+        assert_ne!(
+            source_project_name("mysrc", "models/churn"),
+            source_project_name("mysrc", "models-churn")
+        );
+        assert!(source_project_name("mysrc", &"nested/".repeat(20)).len() <= MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn pushes_are_relative_to_the_project_directory() {
+        let push = GitPush {
+            branch: "main".to_string(),
+            commit: "abc".to_string(),
+            changed_paths: vec![
+                "models/churn/train.py".to_string(),
+                "models/forecast/train.py".to_string(),
+            ],
+        };
+
+        assert_eq!(
+            push.relative_to("models/churn").unwrap().changed_paths,
+            vec!["train.py".to_string()]
+        );
+        assert!(push.relative_to("experiments").is_none());
+    }
+
     //TODO test that sync interval can be overridden
     //TODO test that sync works
 }
@@ -1,7 +1,7 @@
 use futures::future::join_all;
 use kube::{
     api::{ListParams, Patch, PatchParams},
-    Api, ResourceExt,
+    Api, Client, ResourceExt,
 };
 use tracing::debug;
 
@@ -12,9 +12,11 @@ use super::{
     project::{generate_data_set_task_name, Project},
 };
 
-use crate::custom_resources::project::{local_name, project_name};
+use crate::custom_resources::project::{
+    is_data_set_shared, local_name, namespace_name, project_name,
+};
 
-use crate::Result;
+use crate::{error::AmeError, Result};
 
 pub async fn approve_deletion(tasks: &Api<Task>, name: &str) -> Result<()> {
     let patch: Task = Task::approve_deletion_patch();
@@ -31,15 +33,19 @@ pub async fn approve_deletion(tasks: &Api<Task>, name: &str) -> Result<()> {
 }
 
 pub struct TaskCtrl {
+    client: Client,
+    namespace: String,
     data_sets: Api<DataSet>,
     projects: Api<Project>,
 }
 
 impl TaskCtrl {
-    pub fn new(data_sets: Api<DataSet>, projects: Api<Project>) -> Self {
+    pub fn new(client: Client, namespace: &str) -> Self {
         Self {
-            data_sets,
-            projects,
+            data_sets: Api::namespaced(client.clone(), namespace),
+            projects: Api::namespaced(client.clone(), namespace),
+            client,
+            namespace: namespace.to_string(),
         }
     }
 
@@ -70,19 +76,52 @@ impl TaskCtrl {
         })
     }
 
-    async fn resolve_data_set_ref(&self, ds_ref: String, root_project: String) -> Result<DataSet> {
-        let project_name = if let Some(project_name) = project_name(ds_ref.clone()) {
-            project_name
-        } else {
-            self.projects.get(&root_project).await?.spec.cfg.name
-        };
+    /// Resolves a data set reference made by a task in `root_project`.
+    ///
+    /// References on the form `namespace/project.dataset` resolve in another namespace and are
+    /// refused unless the owning project grants `root_project` access to the data set.
+    pub async fn resolve_data_set_ref(
+        &self,
+        ds_ref: String,
+        root_project: String,
+    ) -> Result<DataSet> {
+        let root_project = self.projects.get(&root_project).await?.spec.cfg.name;
 
-        let project_objs = self.projects.list(&ListParams::default()).await?;
+        let project_name = project_name(ds_ref.clone()).unwrap_or(root_project.clone());
 
         let local_name = local_name(ds_ref.clone());
 
-        let potential_ds: Vec<DataSet> = self
-            .data_sets
+        let foreign_namespace = namespace_name(ds_ref.clone()).filter(|ns| ns != &self.namespace);
+
+        let (projects, data_sets) = match foreign_namespace {
+            Some(ref namespace) => (
+                Api::<Project>::namespaced(self.client.clone(), namespace),
+                Api::<DataSet>::namespaced(self.client.clone(), namespace),
+            ),
+            None => (self.projects.clone(), self.data_sets.clone()),
+        };
+
+        let project_objs = projects.list(&ListParams::default()).await?;
+
+        if foreign_namespace.is_some() {
+            let shared = project_objs
+                .items
+                .iter()
+                .filter(|po| po.spec.cfg.name == project_name)
+                .any(|po| {
+                    is_data_set_shared(&po.spec.cfg, &local_name, &self.namespace, &root_project)
+                });
+
+            if !shared {
+                return Err(AmeError::DataSetAccessDenied(
+                    ds_ref,
+                    root_project,
+                    self.namespace.clone(),
+                ));
+            }
+        }
+
+        let potential_ds: Vec<DataSet> = data_sets
             .list(&ListParams::default())
             .await?
             .items
@@ -100,7 +139,7 @@ impl TaskCtrl {
             .collect();
 
         if potential_ds.len() != 1 {
-            todo!("len: {} {ds_ref}", potential_ds.len())
+            return Err(AmeError::MissingDataSet(local_name, project_name));
         }
 
         Ok(potential_ds[0].clone())
@@ -112,6 +112,26 @@ pub enum AmeError {
     #[error("Failed to parse cron schedule: {0} ")]
     CronError(#[from] cron_parser::ParseError),
 
+    #[error("Invalid cron schedule {0}: {1}")]
+    InvalidCronSchedule(String, String),
+
+    #[error("Unknown timezone: {0}")]
+    InvalidTimezone(String),
+
+    #[error("Unknown concurrency policy {0}, expected one of Allow, Forbid or Replace")]
+    InvalidConcurrencyPolicy(String),
+
+    #[error(
+        "Unknown deletion policy {0}, expected one of Cascade, Purge, RetainData or RetainDeployments"
+    )]
+    InvalidDeletionPolicy(String),
+
+    #[error("Invalid time {0}, expected an RFC 3339 timestamp or a date such as 2023-08-01")]
+    InvalidTime(String),
+
+    #[error("Invalid backfill: {0}")]
+    InvalidBackfill(String),
+
     #[error("Could not generate an owner reference for {0}")]
     FailedToCreateOref(String),
 
@@ -123,6 +143,44 @@ pub enum AmeError {
 
     #[error("Missing model training cfg {0} {1}")]
     MissingModelTrainingTaskCfg(String, String),
+
+    #[error("Invalid quantity: {0}")]
+    InvalidQuantity(String),
+
+    #[error("Row counts can only be validated for CSV and Parquet files, got: {0}")]
+    UnsupportedRowCountFormat(String),
+
+    #[error(
+        "Task {0} requires {1} bytes of data sets but its workspace only has room for {2} bytes"
+    )]
+    InsufficientWorkspace(String, u64, u64),
+
+    #[error("Data sets depend on each other in a cycle: {}", .0.join(" -> "))]
+    DataSetCycle(Vec<String>),
+
+    #[error("Data set {0} is not shared with project {1} in namespace {2}")]
+    DataSetAccessDenied(String, String, String),
+
+    #[error("Unknown model registry {0}, expected mlflow or object-storage")]
+    UnknownModelRegistry(String),
+
+    #[error("The model registry is missing configuration: {0}")]
+    MissingModelRegistryCfg(String),
+
+    #[error("Model registry failed: {0}")]
+    ModelRegistryError(String),
+
+    #[error("Object storage failed: {0}")]
+    StorageError(String),
+
+    #[error("Model {0} has no failed validation to retry for {1}")]
+    NoFailedModelValidation(String, String),
+
+    #[error("Model {0} has no previously deployed version to roll back to")]
+    NoPreviousModelVersion(String),
+
+    #[error("Invalid monitoring report: {0}")]
+    InvalidMonitoringReport(String),
 }
 
 impl From<Status> for AmeError {
@@ -0,0 +1,352 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use globset::GlobBuilder;
+
+use crate::{
+    grpc::{ProjectCfg, ProjectInclude},
+    validation::{parse, Diagnostic, SourceFile},
+};
+
+/// Records the file, and the index in that file, of every task, template, data set and model in
+/// a project with includes, so problems found in the merged project point at the right file.
+#[derive(Debug, Default)]
+pub(crate) struct Origins {
+    sections: BTreeMap<&'static str, Vec<(usize, usize)>>,
+}
+
+impl Origins {
+    fn record(&mut self, section: &'static str, file: usize, count: usize) {
+        self.sections
+            .entry(section)
+            .or_default()
+            .extend((0..count).map(|index| (file, index)));
+    }
+
+    fn record_include(&mut self, file: usize, include: &ProjectInclude) {
+        self.record("models", file, include.models.len());
+        self.record("dataSets", file, include.data_sets.len());
+        self.record("tasks", file, include.tasks.len());
+        self.record("templates", file, include.templates.len());
+    }
+
+    /// Returns the index of the file a path in the merged project points into, with the path in
+    /// that file.
+    pub(crate) fn locate(&self, path: &str) -> (usize, String) {
+        let origin = path.split_once('[').and_then(|(section, rest)| {
+            let (index, rest) = rest.split_once(']')?;
+            let (file, index) = self
+                .sections
+                .get(section)?
+                .get(index.parse::<usize>().ok()?)?;
+
+            Some((*file, format!("{section}[{index}]{rest}")))
+        });
+
+        origin.unwrap_or((0, path.to_string()))
+    }
+}
+
+/// Merges the tasks, templates, data sets and models of the files included by the project in
+/// `files[0]` into `cfg`.
+///
+/// Included files are relative to the directory of the project file, either as paths or as globs,
+/// and are themselves relative to `root` in `files`. Returns false if an included file could not
+/// be parsed, problems are reported in `diagnostics`.
+pub(crate) fn resolve_includes(
+    root: &Path,
+    cfg: &mut ProjectCfg,
+    files: &mut Vec<SourceFile>,
+    origins: &mut Origins,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> bool {
+    origins.record("models", 0, cfg.models.len());
+    origins.record("dataSets", 0, cfg.data_sets.len());
+    origins.record("tasks", 0, cfg.tasks.len());
+    origins.record("templates", 0, cfg.templates.len());
+
+    let dir = Path::new(&files[0].file)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+
+    let mut included = BTreeSet::from([PathBuf::from(&files[0].file)]);
+    let mut complete = true;
+
+    for (i, pattern) in std::mem::take(&mut cfg.include).iter().enumerate() {
+        let path = format!("include[{i}]");
+
+        let matches = match include_matches(&root.join(&dir), pattern) {
+            Ok(matches) if matches.is_empty() => {
+                diagnostics.push(
+                    files[0].diagnostic(&path, format!("`{pattern}` does not match any files")),
+                );
+                continue;
+            }
+            Ok(matches) => matches,
+            Err(message) => {
+                diagnostics.push(files[0].diagnostic(&path, message));
+                continue;
+            }
+        };
+
+        for file in matches {
+            let file = dir.join(file);
+
+            if !included.insert(file.clone()) {
+                continue;
+            }
+
+            let name = file.to_string_lossy().to_string();
+
+            let source = match read_inside(root, &file) {
+                Ok(source) => source,
+                Err(e) => {
+                    diagnostics
+                        .push(files[0].diagnostic(&path, format!("could not read {name}: {e}")));
+                    complete = false;
+                    continue;
+                }
+            };
+
+            let (include, include_diagnostics, source_file) =
+                parse::<ProjectInclude>(&name, &source);
+
+            diagnostics.extend(include_diagnostics);
+            files.push(source_file);
+
+            let Some(include) = include else {
+                complete = false;
+                continue;
+            };
+
+            origins.record_include(files.len() - 1, &include);
+
+            cfg.models.extend(include.models);
+            cfg.data_sets.extend(include.data_sets);
+            cfg.tasks.extend(include.tasks);
+            cfg.templates.extend(include.templates);
+        }
+    }
+
+    complete
+}
+
+/// Reads `file` relative to `root`, files which resolve to a path outside of `root`, e.g. through
+/// a symlink, are not read so a project can not include files from outside its repository.
+fn read_inside(root: &Path, file: &Path) -> Result<String, String> {
+    let root = root.canonicalize().map_err(|e| e.to_string())?;
+    let path = root.join(file).canonicalize().map_err(|e| e.to_string())?;
+
+    if !path.starts_with(&root) {
+        return Err("the file is outside of the project".to_string());
+    }
+
+    fs::read_to_string(path).map_err(|e| e.to_string())
+}
+
+/// Returns the files in `dir` matching the include `pattern`, relative to `dir`.
+fn include_matches(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let components = Path::new(pattern).components();
+
+    if !components
+        .into_iter()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(format!(
+            "`{pattern}` must be a relative path inside the project directory"
+        ));
+    }
+
+    if !pattern.contains(['*', '?', '[', '{']) {
+        return Ok(if dir.join(pattern).is_file() {
+            vec![PathBuf::from(pattern)]
+        } else {
+            vec![]
+        });
+    }
+
+    let glob = GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| format!("`{pattern}` is not a valid glob: {e}"))?
+        .compile_matcher();
+
+    let mut matches: Vec<PathBuf> = files_in(dir, Path::new(""))
+        .into_iter()
+        .filter(|file| glob.is_match(file))
+        .collect();
+
+    matches.sort();
+
+    Ok(matches)
+}
+
+/// Returns the files below `dir` relative to it, hidden directories such as `.git` are skipped.
+fn files_in(dir: &Path, relative: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir.join(relative)) else {
+        return vec![];
+    };
+
+    let mut files = vec![];
+
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let path = relative.join(&name);
+
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                if !name.to_string_lossy().starts_with('.') {
+                    files.extend(files_in(dir, &path));
+                }
+            }
+            Ok(file_type) if file_type.is_file() => files.push(path),
+            _ => (),
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod test {
+    use assert_fs::{prelude::*, TempDir};
+
+    use crate::validation::Diagnostics;
+
+    use super::*;
+
+    fn load(dir: &TempDir) -> Result<ProjectCfg, Diagnostics> {
+        let source = fs::read_to_string(dir.path().join("ame.yaml")).unwrap();
+
+        ProjectCfg::from_yaml(dir.path(), "ame.yaml", &source)
+    }
+
+    #[test]
+    fn merges_included_files() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+
+        dir.child("ame.yaml").write_str(
+            r#"name: myproject
+include:
+  - data.yaml
+  - tasks/*.yaml
+tasks:
+  - name: prepare
+    dataSets:
+      - features
+"#,
+        )?;
+        dir.child("data.yaml").write_str(
+            r#"dataSets:
+  - name: features
+    path: data
+"#,
+        )?;
+        dir.child("tasks/train.yaml").write_str(
+            r#"tasks:
+  - name: train
+    taskRef:
+      name: prepare
+"#,
+        )?;
+        dir.child("tasks/nested/ignored.yaml")
+            .write_str("tasks:\n  - name: ignored\n")?;
+
+        let cfg = load(&dir)?;
+
+        let names: Vec<_> = cfg.tasks.iter().filter_map(|t| t.name.as_deref()).collect();
+
+        assert_eq!(names, vec!["prepare", "train"]);
+        assert_eq!(cfg.data_sets[0].name, "features");
+        assert!(cfg.include.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn problems_point_at_the_included_file() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+
+        dir.child("ame.yaml").write_str(
+            r#"name: myproject
+include:
+  - tasks.yaml
+  - missing/*.yaml
+tasks:
+  - name: train
+"#,
+        )?;
+        dir.child("tasks.yaml").write_str(
+            r#"templates:
+  - name: gpu
+tasks:
+  - name: train
+    fromTemplate:
+      name: cpu
+"#,
+        )?;
+
+        let messages: Vec<String> = load(&dir)
+            .unwrap_err()
+            .0
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                "ame.yaml:4:5: `missing/*.yaml` does not match any files",
+                "tasks.yaml:4:5: duplicate task name `train`",
+                "tasks.yaml:6:7: template `cpu` does not exist in the project",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn files_outside_the_project_are_not_included() -> anyhow::Result<()> {
+        let dir = TempDir::new()?;
+        let outside = TempDir::new()?;
+
+        outside.child("token").write_str("secret")?;
+        dir.child("project/ame.yaml").write_str(
+            r#"name: myproject
+include:
+  - ../outside.yaml
+  - link.yaml
+"#,
+        )?;
+        dir.child("outside.yaml").write_str("tasks: []\n")?;
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(
+            outside.path().join("token"),
+            dir.path().join("project/link.yaml"),
+        )?;
+
+        let source = fs::read_to_string(dir.path().join("project/ame.yaml"))?;
+        let diagnostics = ProjectCfg::from_yaml(&dir.path().join("project"), "ame.yaml", &source)
+            .unwrap_err()
+            .0;
+
+        assert!(diagnostics[0]
+            .message
+            .contains("must be a relative path inside the project directory"));
+
+        #[cfg(unix)]
+        assert_eq!(
+            diagnostics[1].message,
+            "could not read link.yaml: the file is outside of the project"
+        );
+
+        assert!(diagnostics.iter().all(|d| !d.message.contains("secret")));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+use std::{collections::BTreeMap, path::Path};
+
+use git2::Repository;
+use serde_yaml::Value;
+
+use crate::{grpc::ProjectCfg, validation::join};
+
+/// Prefix of the environment variables available to `${{ env.X }}` expressions. Other variables
+/// are never exposed, so unrelated configuration and credentials stay out of projects.
+pub static ENV_VAR_PREFIX: &str = "AME_VAR_";
+
+/// Values available to `${{ env.X }}` and `${{ git.sha }}` expressions, `${{ vars.x }}`
+/// expressions are resolved from the project itself.
+///
+/// Without `env`, `${{ env.X }}` expressions are rejected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterpolationContext {
+    pub env: Option<BTreeMap<String, String>>,
+    pub git_sha: Option<String>,
+}
+
+impl InterpolationContext {
+    /// Returns the `AME_VAR_` environment variables of the current process and the commit checked
+    /// out in the git repository containing `dir`, the CLI loads projects with this context.
+    pub fn current(dir: &Path) -> Self {
+        InterpolationContext {
+            env: Some(
+                std::env::vars()
+                    .filter(|(key, _)| key.starts_with(ENV_VAR_PREFIX))
+                    .collect(),
+            ),
+            ..InterpolationContext::repository(dir)
+        }
+    }
+
+    /// Returns the commit checked out in the git repository containing `dir`. The project source
+    /// controller loads projects with this context, the controller's environment is not the one
+    /// projects are written against so `env` expressions are not available.
+    pub fn repository(dir: &Path) -> Self {
+        let git_sha = Repository::discover(dir)
+            .ok()
+            .and_then(|repo| Some(repo.head().ok()?.peel_to_commit().ok()?.id().to_string()));
+
+        InterpolationContext { env: None, git_sha }
+    }
+
+    fn resolve(&self, vars: &BTreeMap<String, String>, expression: &str) -> Result<String, String> {
+        match expression.split_once('.') {
+            Some(("vars", name)) => vars
+                .get(name)
+                .cloned()
+                .ok_or(format!("unknown variable `{expression}`")),
+            Some(("env", name)) if !name.starts_with(ENV_VAR_PREFIX) => Err(format!(
+                "`{expression}` is not available, environment variables must start with {ENV_VAR_PREFIX}"
+            )),
+            Some(("env", _)) if self.env.is_none() => Err(format!(
+                "`{expression}` is not available in projects synced from a project source, use vars instead"
+            )),
+            Some(("env", name)) => self
+                .env
+                .as_ref()
+                .and_then(|env| env.get(name))
+                .cloned()
+                .ok_or(format!("environment variable {name} is not set")),
+            Some(("git", "sha")) => self
+                .git_sha
+                .clone()
+                .ok_or("`git.sha` is only available in a git repository".to_string()),
+            _ => Err(format!("unknown variable `{expression}`")),
+        }
+    }
+}
+
+/// Replaces every `${{ expression }}` in `text` with the value `resolve` returns for it.
+pub fn interpolate(
+    text: &str,
+    resolve: impl Fn(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${{") {
+        let Some(end) = rest[start..].find("}}") else {
+            return Err(format!("`{}` is missing the closing }}}}", &rest[start..]));
+        };
+
+        result.push_str(&rest[..start]);
+        result.push_str(&resolve(rest[start + 3..start + end].trim())?);
+        rest = &rest[start + end + 2..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+impl ProjectCfg {
+    /// Resolves the `${{ ... }}` expressions in every string of the project, returning the path
+    /// and a description of every expression which could not be resolved.
+    ///
+    /// Values in `vars` may use `env` and `git` expressions but not other variables.
+    pub fn interpolate(&mut self, context: &InterpolationContext) -> Vec<(String, String)> {
+        let mut problems = vec![];
+        let mut vars = BTreeMap::new();
+
+        for (name, value) in std::mem::take(&mut self.vars) {
+            let resolved = interpolate(&value, |expression| {
+                if expression.starts_with("vars.") {
+                    Err(format!("`{expression}` can not be used in vars"))
+                } else {
+                    context.resolve(&BTreeMap::new(), expression)
+                }
+            });
+
+            match resolved {
+                Ok(resolved) => vars.insert(name, resolved),
+                Err(message) => {
+                    problems.push((format!("vars.{name}"), message));
+                    vars.insert(name, value)
+                }
+            };
+        }
+
+        let mut value = match serde_yaml::to_value(&*self) {
+            Ok(value) => value,
+            Err(e) => {
+                problems.push((String::new(), e.to_string()));
+                return problems;
+            }
+        };
+
+        interpolate_value(
+            &mut value,
+            "",
+            &|expression| context.resolve(&vars, expression),
+            &mut problems,
+        );
+
+        match serde_yaml::from_value::<ProjectCfg>(value) {
+            Ok(cfg) => *self = cfg,
+            Err(e) => problems.push((String::new(), e.to_string())),
+        }
+
+        self.vars = vars;
+
+        problems
+    }
+}
+
+fn interpolate_value(
+    value: &mut Value,
+    path: &str,
+    resolve: &dyn Fn(&str) -> Result<String, String>,
+    problems: &mut Vec<(String, String)>,
+) {
+    match value {
+        Value::String(text) if text.contains("${{") => match interpolate(text, resolve) {
+            Ok(interpolated) => *text = interpolated,
+            Err(message) => problems.push((path.to_string(), message)),
+        },
+        Value::Sequence(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                interpolate_value(item, &format!("{path}[{i}]"), resolve, problems);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                let path = join(path, key.as_str().unwrap_or_default());
+                interpolate_value(value, &path, resolve, problems);
+            }
+        }
+        // Tags select an enum variant, the variant is not part of the path.
+        Value::Tagged(tagged) => interpolate_value(&mut tagged.value, path, resolve, problems),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grpc::task_cfg::Executor;
+
+    use super::*;
+
+    fn context() -> InterpolationContext {
+        InterpolationContext {
+            env: Some(BTreeMap::from([(
+                "AME_VAR_BUCKET".to_string(),
+                "models".to_string(),
+            )])),
+            git_sha: Some("4f2c1a".to_string()),
+        }
+    }
+
+    #[test]
+    fn resolves_vars_env_and_git() {
+        let mut cfg: ProjectCfg = serde_yaml::from_str(
+            r#"
+name: myproject
+vars:
+  python: "3.11"
+  output: s3://${{ env.AME_VAR_BUCKET }}/train
+tasks:
+  - name: train
+    executor:
+      !poetry
+      pythonVersion: ${{ vars.python }}
+      command: python train.py --out ${{vars.output}}/${{ git.sha }}
+"#,
+        )
+        .unwrap();
+
+        assert!(cfg.interpolate(&context()).is_empty());
+
+        let Some(Executor::Poetry(ref executor)) = cfg.tasks[0].executor else {
+            panic!("expected a poetry executor");
+        };
+
+        assert_eq!(executor.python_version, "3.11");
+        assert_eq!(
+            executor.command,
+            "python train.py --out s3://models/train/4f2c1a"
+        );
+    }
+
+    #[test]
+    fn reports_unknown_variables() {
+        let mut cfg: ProjectCfg = serde_yaml::from_str(
+            r#"
+name: myproject
+vars:
+  nested: ${{ vars.other }}
+tasks:
+  - name: ${{ vars.missing }}
+    dataSets:
+      - ${{ env.HOME }}
+      - ${{ env.AME_VAR_UNSET }}
+"#,
+        )
+        .unwrap();
+
+        let problems: Vec<String> = cfg
+            .interpolate(&context())
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+
+        assert_eq!(
+            problems,
+            vec![
+                "vars.nested",
+                "tasks[0].name",
+                "tasks[0].dataSets[0]",
+                "tasks[0].dataSets[1]",
+            ]
+        );
+    }
+
+    #[test]
+    fn env_expressions_require_an_environment() {
+        let mut cfg: ProjectCfg = serde_yaml::from_str(
+            r#"
+name: myproject
+vars:
+  output: s3://${{ env.AME_VAR_BUCKET }}/train
+tasks:
+  - name: train-${{ git.sha }}
+"#,
+        )
+        .unwrap();
+
+        let context = InterpolationContext {
+            env: None,
+            ..context()
+        };
+
+        let problems: Vec<String> = cfg
+            .interpolate(&context)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+
+        assert_eq!(problems, vec!["vars.output"]);
+        assert_eq!(cfg.tasks[0].name.as_deref(), Some("train-4f2c1a"));
+    }
+
+    #[test]
+    fn rejects_unterminated_expressions() {
+        assert!(interpolate("${{ vars.x", |_| Ok(String::new())).is_err());
+        assert_eq!(
+            interpolate("no expressions", |_| Err(String::new())),
+            Ok("no expressions".to_string())
+        );
+    }
+}
@@ -18,6 +18,15 @@ pub mod client;
 #[cfg(feature = "project-tools")]
 pub mod project;
 
+#[cfg(any(feature = "project-tools", feature = "custom-resources"))]
+mod include;
+
+#[cfg(any(feature = "project-tools", feature = "custom-resources"))]
+pub mod interpolation;
+
+#[cfg(any(feature = "project-tools", feature = "custom-resources"))]
+pub mod validation;
+
 pub mod grpc {
     #![allow(clippy::all)]
 
@@ -125,6 +134,8 @@ pub mod grpc {
                     username,
                     secret,
                     sync_interval: None,
+                    include: vec![],
+                    exclude: vec![],
                 }),
             }
         }
@@ -216,6 +227,59 @@ pub mod grpc {
                 }
             }
         }
+
+        /// Generates a script which prepares the same Python environment as [`Executor::command`]
+        /// and activates it, so the commands following it run with the project's dependencies.
+        pub fn environment_command(&self) -> String {
+            match self {
+                Executor::Poetry(PoetryExecutor { python_version, .. }) => {
+                    format!(
+                        "
+                        source ~/.bashrc
+
+                        pyenv install {python_version}
+
+                        pyenv global {python_version}
+
+                        poetry install
+
+                        source \"$(poetry env info --path)/bin/activate\"
+                    "
+                    )
+                }
+                Executor::Mlflow(_) => "export PATH=$HOME/.pyenv/bin:$PATH".to_string(),
+                Executor::PipEnv(_) => "
+                            pipenv sync
+
+                            source \"$(pipenv --venv)/bin/activate\"
+                        "
+                .to_string(),
+                Executor::Pip(PipExecutor { python_version, .. }) => {
+                    format!(
+                        "
+                        source ~/.bashrc
+
+                        pyenv install {python_version}
+
+                        pyenv global {python_version}
+
+                        pip install -r requirements.txt
+                    "
+                    )
+                }
+                Executor::Custom(CustomExecutor { python_version, .. }) => {
+                    format!(
+                        "
+                        source ~/.bashrc
+
+                        pyenv install {python_version}
+
+                        pyenv global {python_version}
+                    "
+                    )
+                }
+            }
+        }
     }
 }
 
@@ -223,8 +287,20 @@ pub mod proto;
 
 pub mod api;
 
+pub mod condition;
+
 pub mod error;
 
+pub mod model_validation;
+
+pub mod monitoring;
+
+pub mod rollout;
+
+pub mod schedule;
+
+pub mod task_defaults;
+
 #[cfg(feature = "ame-control")]
 pub mod ctrl;
 
@@ -0,0 +1,161 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    error::AmeError,
+    grpc::{ModelStatus, ModelVersionStatus},
+    Result,
+};
+
+/// The validation task for a model version has been created but has not finished.
+pub static OUTCOME_PENDING: &str = "Pending";
+
+/// The model version passed validation and may be deployed.
+pub static OUTCOME_SUCCEEDED: &str = "Succeeded";
+
+/// The model version failed validation, it is not validated or deployed again unless the
+/// validation is retried.
+pub static OUTCOME_FAILED: &str = "Failed";
+
+/// Number of model versions kept in a model's validation history.
+pub const MAX_VERSION_HISTORY: usize = 20;
+
+impl ModelVersionStatus {
+    pub fn pending(version: &str, source: &str, now: DateTime<Utc>) -> Self {
+        ModelVersionStatus {
+            version: version.to_string(),
+            source: source.to_string(),
+            outcome: OUTCOME_PENDING.to_string(),
+            timestamp: Some(now.to_rfc3339()),
+            attempt: 1,
+            ..ModelVersionStatus::default()
+        }
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.outcome == OUTCOME_FAILED
+    }
+
+    pub fn is_validated(&self) -> bool {
+        self.outcome == OUTCOME_SUCCEEDED
+    }
+
+    /// Returns the name of the validation task for the current attempt, retries get a new task
+    /// so the outcome of a previous attempt is not picked up again.
+    pub fn validation_task_name(&self, model: &str) -> String {
+        if self.attempt > 1 {
+            format!("validate-{model}-{}-{}", self.version, self.attempt)
+        } else {
+            format!("validate-{model}-{}", self.version)
+        }
+    }
+}
+
+impl ModelStatus {
+    /// Returns the validation history entry for the version stored at `source`.
+    pub fn version_status(&self, source: &str) -> Option<&ModelVersionStatus> {
+        self.versions.iter().find(|v| v.source == source)
+    }
+
+    /// Records the outcome of validating a version, replacing any earlier entry for the same
+    /// version. Only the latest `MAX_VERSION_HISTORY` versions are kept.
+    pub fn record_validation(&mut self, status: ModelVersionStatus) {
+        self.versions.retain(|v| v.source != status.source);
+        self.versions.push(status);
+
+        if self.versions.len() > MAX_VERSION_HISTORY {
+            let excess = self.versions.len() - MAX_VERSION_HISTORY;
+            self.versions.drain(..excess);
+        }
+    }
+
+    /// Marks a failed version for validation again, `version` defaults to the most recently
+    /// failed version.
+    pub fn retry_validation(
+        &mut self,
+        model: &str,
+        version: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Result<ModelVersionStatus> {
+        let Some(status) = self
+            .versions
+            .iter_mut()
+            .rev()
+            .filter(|v| v.is_failed())
+            .find(|v| version.map_or(true, |version| v.version == version))
+        else {
+            return Err(AmeError::NoFailedModelValidation(
+                model.to_string(),
+                version.unwrap_or("any version").to_string(),
+            ));
+        };
+
+        status.outcome = OUTCOME_PENDING.to_string();
+        status.reason = Some("validation was retried".to_string());
+        status.timestamp = Some(now.to_rfc3339());
+        status.validation_task = None;
+        status.attempt += 1;
+
+        Ok(status.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn failed(version: &str) -> ModelVersionStatus {
+        ModelVersionStatus {
+            outcome: OUTCOME_FAILED.to_string(),
+            reason: Some("accuracy too low".to_string()),
+            ..ModelVersionStatus::pending(version, &format!("s3://models/{version}"), Utc::now())
+        }
+    }
+
+    #[test]
+    fn history_keeps_one_entry_per_version() {
+        let mut status = ModelStatus::default();
+
+        status.record_validation(ModelVersionStatus::pending(
+            "1",
+            "s3://models/1",
+            Utc::now(),
+        ));
+        status.record_validation(failed("1"));
+
+        assert_eq!(status.versions.len(), 1);
+        assert!(status.version_status("s3://models/1").unwrap().is_failed());
+
+        for version in 2..30 {
+            status.record_validation(failed(&version.to_string()));
+        }
+
+        assert_eq!(status.versions.len(), MAX_VERSION_HISTORY);
+        assert_eq!(status.versions.last().unwrap().version, "29");
+    }
+
+    #[test]
+    fn retries_get_a_new_validation_task() -> Result<()> {
+        let mut status = ModelStatus::default();
+        status.record_validation(failed("1"));
+        status.record_validation(failed("2"));
+
+        assert!(status
+            .retry_validation("mymodel", Some("3"), Utc::now())
+            .is_err());
+
+        let retried = status.retry_validation("mymodel", None, Utc::now())?;
+
+        assert_eq!(retried.version, "2");
+        assert_eq!(retried.outcome, OUTCOME_PENDING);
+        assert_eq!(
+            retried.validation_task_name("mymodel"),
+            "validate-mymodel-2-2"
+        );
+        assert_eq!(
+            failed("1").validation_task_name("mymodel"),
+            "validate-mymodel-1"
+        );
+
+        Ok(())
+    }
+}
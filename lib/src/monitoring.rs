@@ -0,0 +1,131 @@
+use serde_json::Value;
+
+use crate::{
+    error::AmeError,
+    grpc::{ModelMonitoringCfg, MonitoringThreshold, TriggerCfg},
+    Result,
+};
+
+/// Path monitoring tasks write their report to, unless they configure another path.
+pub static DEFAULT_REPORT_PATH: &str = "monitoring-report.json";
+
+/// Environment variable monitoring tasks receive the path to write their report to through.
+pub static REPORT_PATH_ENV: &str = "AME_REPORT_PATH";
+
+impl ModelMonitoringCfg {
+    /// Returns a trigger firing on the monitoring schedule.
+    pub fn trigger(&self) -> TriggerCfg {
+        TriggerCfg {
+            schedule: Some(self.schedule.clone()),
+            timezone: self.timezone.clone(),
+            ..TriggerCfg::default()
+        }
+    }
+
+    pub fn report_path_or_default(&self) -> String {
+        self.report_path
+            .clone()
+            .unwrap_or(DEFAULT_REPORT_PATH.to_string())
+    }
+
+    /// Checks a JSON report against the thresholds, returning a description of every breached
+    /// threshold.
+    pub fn breaches(&self, report: &str) -> Result<Vec<String>> {
+        let report: Value = serde_json::from_str(report)
+            .map_err(|e| AmeError::InvalidMonitoringReport(e.to_string()))?;
+
+        self.thresholds
+            .iter()
+            .filter_map(|threshold| threshold.breach(&report).transpose())
+            .collect()
+    }
+}
+
+impl MonitoringThreshold {
+    /// Returns a description of the breach if the metric in `report` is outside the threshold.
+    ///
+    /// Metrics are looked up by their path in the report, with nested fields separated by dots,
+    /// e.g. `drift.score`.
+    pub fn breach(&self, report: &Value) -> Result<Option<String>> {
+        let value = self
+            .metric
+            .split('.')
+            .try_fold(report, |value, field| value.get(field))
+            .ok_or(AmeError::InvalidMonitoringReport(format!(
+                "missing metric {}",
+                self.metric
+            )))?;
+
+        let Some(value) = value.as_f64() else {
+            return Err(AmeError::InvalidMonitoringReport(format!(
+                "metric {} is not a number: {value}",
+                self.metric
+            )));
+        };
+
+        Ok(match (self.min, self.max) {
+            (Some(min), _) if value < min => Some(format!(
+                "{} is {value}, below the minimum {min}",
+                self.metric
+            )),
+            (_, Some(max)) if value > max => Some(format!(
+                "{} is {value}, above the maximum {max}",
+                self.metric
+            )),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn cfg() -> ModelMonitoringCfg {
+        ModelMonitoringCfg {
+            thresholds: vec![
+                MonitoringThreshold {
+                    metric: "drift.score".to_string(),
+                    max: Some(0.3),
+                    ..MonitoringThreshold::default()
+                },
+                MonitoringThreshold {
+                    metric: "accuracy".to_string(),
+                    min: Some(0.8),
+                    ..MonitoringThreshold::default()
+                },
+            ],
+            ..ModelMonitoringCfg::default()
+        }
+    }
+
+    #[test]
+    fn reports_within_thresholds_have_no_breaches() -> Result<()> {
+        assert!(cfg()
+            .breaches(r#"{"drift": {"score": 0.1}, "accuracy": 0.9}"#)?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn breached_thresholds_are_described() -> Result<()> {
+        let breaches = cfg().breaches(r#"{"drift": {"score": 0.5}, "accuracy": 0.7}"#)?;
+
+        assert_eq!(
+            breaches,
+            vec![
+                "drift.score is 0.5, above the maximum 0.3".to_string(),
+                "accuracy is 0.7, below the minimum 0.8".to_string(),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_reports_missing_metrics() {
+        assert!(cfg().breaches(r#"{"accuracy": 0.9}"#).is_err());
+        assert!(cfg().breaches("not json").is_err());
+    }
+}
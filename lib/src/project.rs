@@ -1,22 +1,20 @@
 use anyhow::{Context, Result};
-use std::fs;
+use std::{fs, path::Path};
 
 use crate::grpc::{ProjectCfg, TaskCfg};
 
 impl ProjectCfg {
     pub fn try_from_working_dir() -> Result<Self> {
-        serde_yaml::from_str(
-            &fs::read_to_string("ame.yaml")
-                .context("Could not read ame.yaml, are you in an AME project?")?,
-        )
-        .context("Could not parse ame.yaml :(")
+        let source = fs::read_to_string("ame.yaml")
+            .context("Could not read ame.yaml, are you in an AME project?")?;
+
+        ProjectCfg::from_yaml(Path::new("."), "ame.yaml", &source).context("ame.yaml is invalid")
     }
 
     pub fn try_from_dir(dir: &str) -> Result<Self> {
-        Ok(serde_yaml::from_str(&fs::read_to_string(format!(
-            "{}/ame.yaml",
-            dir
-        ))?)?)
+        let source = fs::read_to_string(Path::new(dir).join("ame.yaml"))?;
+
+        Ok(ProjectCfg::from_yaml(Path::new(dir), "ame.yaml", &source)?)
     }
 
     pub fn task_names(&self) -> Vec<String> {
@@ -0,0 +1,294 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    error::AmeError,
+    grpc::{
+        model_deployment_cfg::Rollout, CanaryRollout, ModelDeploymentCfg, ModelStatus,
+        RolloutStatus,
+    },
+    Result,
+};
+
+/// Time each canary step receives its share of traffic, unless the rollout configures its own.
+pub const DEFAULT_STEP_INTERVAL_SECONDS: u64 = 300;
+
+/// Number of previously deployed versions kept for rolling back a model.
+pub const MAX_DEPLOYMENT_HISTORY: usize = 10;
+
+/// Only the stable model version receives traffic.
+pub static PHASE_STABLE: &str = "Stable";
+
+/// A candidate version receives an increasing share of traffic.
+pub static PHASE_PROGRESSING: &str = "Progressing";
+
+/// A candidate version is deployed alongside the stable version and waits for promotion.
+pub static PHASE_AWAITING_PROMOTION: &str = "AwaitingPromotion";
+
+/// Annotation prefix for promoting the candidate version of a model. The controller promotes the
+/// candidate once `promote.ame.teainspace.com/<model>` is `true` on the project and removes the
+/// annotation afterwards, the `PromoteModelRollout` RPC (`ame model promote-rollout`) sets it.
+pub static PROMOTE_ANNOTATION_PREFIX: &str = "promote.ame.teainspace.com/";
+
+/// Annotation promoting the rollout candidate of `model`.
+pub fn promote_annotation(model: &str) -> String {
+    format!("{PROMOTE_ANNOTATION_PREFIX}{model}")
+}
+
+impl CanaryRollout {
+    /// Returns the share of traffic the candidate receives at `step`, once the steps are
+    /// exhausted the candidate receives all traffic.
+    pub fn weight(&self, step: u32) -> u32 {
+        let weight = match step {
+            0 => Some(self.traffic_percentage),
+            step => self.steps.get(step as usize - 1).copied(),
+        };
+
+        weight.unwrap_or(100).min(100)
+    }
+
+    pub fn step_interval(&self) -> Duration {
+        let seconds = self
+            .step_interval_seconds
+            .unwrap_or(DEFAULT_STEP_INTERVAL_SECONDS);
+
+        Duration::seconds(seconds.min(u32::MAX as u64) as i64)
+    }
+}
+
+impl RolloutStatus {
+    /// A rollout where `source` receives all traffic.
+    pub fn stable(source: &str) -> Self {
+        RolloutStatus {
+            phase: PHASE_STABLE.to_string(),
+            stable_source: source.to_string(),
+            ..RolloutStatus::default()
+        }
+    }
+
+    /// Moves the rollout towards serving `source`, the latest validated model version.
+    ///
+    /// Without a strategy, or when nothing is deployed yet, `source` replaces the stable version
+    /// right away. A canary candidate moves to the next step once the step interval has passed,
+    /// and is promoted when it receives all traffic. A blue/green candidate receives no traffic
+    /// until `promote` is set, which also skips the remaining steps of a canary. `promote` is set
+    /// through the annotation returned by [`promote_annotation`].
+    pub fn progress(
+        self,
+        strategy: Option<&Rollout>,
+        source: &str,
+        promote: bool,
+        now: DateTime<Utc>,
+    ) -> Result<Self> {
+        let Some(strategy) = strategy else {
+            return Ok(RolloutStatus::stable(source));
+        };
+
+        if self.stable_source.is_empty() || self.stable_source == source {
+            return Ok(RolloutStatus::stable(source));
+        }
+
+        if self.candidate_source.as_deref() != Some(source) {
+            let (phase, canary_weight) = match strategy {
+                Rollout::Canary(canary) => (PHASE_PROGRESSING, canary.weight(0)),
+                Rollout::BlueGreen(_) => (PHASE_AWAITING_PROMOTION, 0),
+            };
+
+            return Ok(RolloutStatus {
+                phase: phase.to_string(),
+                stable_source: self.stable_source,
+                candidate_source: Some(source.to_string()),
+                step: 0,
+                canary_weight,
+                step_start_time: Some(now.to_rfc3339()),
+            });
+        }
+
+        if promote {
+            return Ok(RolloutStatus::stable(source));
+        }
+
+        let Rollout::Canary(canary) = strategy else {
+            return Ok(self);
+        };
+
+        let step_start = match self.step_start_time {
+            Some(ref time) => DateTime::parse_from_rfc3339(time)
+                .map_err(|_| AmeError::InvalidTime(time.clone()))?
+                .with_timezone(&Utc),
+            None => now,
+        };
+
+        if now.signed_duration_since(step_start) < canary.step_interval() {
+            return Ok(self);
+        }
+
+        let step = self.step + 1;
+        let canary_weight = canary.weight(step);
+
+        if canary_weight >= 100 {
+            return Ok(RolloutStatus::stable(source));
+        }
+
+        Ok(RolloutStatus {
+            step,
+            canary_weight,
+            step_start_time: Some(now.to_rfc3339()),
+            ..self
+        })
+    }
+}
+
+impl ModelStatus {
+    /// Returns the version which should be deployed instead of the latest validated version, a
+    /// version pinned through promotion or rollback takes precedence over one pinned in the
+    /// deployment configuration.
+    pub fn target_version<'a>(&'a self, cfg: Option<&'a ModelDeploymentCfg>) -> Option<&'a str> {
+        self.pinned_version
+            .as_deref()
+            .or(cfg.and_then(|cfg| cfg.pinned_version.as_deref()))
+    }
+
+    /// Records that `version` now receives all traffic.
+    ///
+    /// The replaced version is added to the deployment history, unless `version` is the most
+    /// recent entry in the history in which case the deployment is a rollback and the entry is
+    /// consumed instead.
+    pub fn record_deployment(&mut self, version: &str) {
+        if self.deployed_version.as_deref() == Some(version) {
+            return;
+        }
+
+        if self.deployment_history.last().map(String::as_str) == Some(version) {
+            self.deployment_history.pop();
+        } else if let Some(previous) = self.deployed_version.take() {
+            self.deployment_history.push(previous);
+
+            if self.deployment_history.len() > MAX_DEPLOYMENT_HISTORY {
+                let excess = self.deployment_history.len() - MAX_DEPLOYMENT_HISTORY;
+                self.deployment_history.drain(..excess);
+            }
+        }
+
+        self.deployed_version = Some(version.to_string());
+    }
+
+    /// Returns the version deployed before the current one, which a rollback pins.
+    pub fn rollback_version(&self, model: &str) -> Result<String> {
+        self.deployment_history
+            .last()
+            .cloned()
+            .ok_or(AmeError::NoPreviousModelVersion(model.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::grpc::BlueGreenRollout;
+
+    fn canary() -> Rollout {
+        Rollout::Canary(CanaryRollout {
+            traffic_percentage: 10,
+            steps: vec![50],
+            step_interval_seconds: Some(60),
+        })
+    }
+
+    #[test]
+    fn first_deployments_are_stable_right_away() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 10, 0, 0).unwrap();
+
+        assert_eq!(
+            RolloutStatus::default().progress(Some(&canary()), "v1", false, now)?,
+            RolloutStatus::stable("v1")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn canaries_step_through_their_weights() -> Result<()> {
+        let start = Utc.with_ymd_and_hms(2023, 8, 1, 10, 0, 0).unwrap();
+        let strategy = canary();
+
+        let status = RolloutStatus::stable("v1").progress(Some(&strategy), "v2", false, start)?;
+        assert_eq!(status.phase, PHASE_PROGRESSING);
+        assert_eq!(status.canary_weight, 10);
+
+        let status =
+            status.progress(Some(&strategy), "v2", false, start + Duration::seconds(30))?;
+        assert_eq!(status.canary_weight, 10);
+
+        let status =
+            status.progress(Some(&strategy), "v2", false, start + Duration::seconds(60))?;
+        assert_eq!(status.canary_weight, 50);
+
+        let status =
+            status.progress(Some(&strategy), "v2", false, start + Duration::seconds(120))?;
+        assert_eq!(status, RolloutStatus::stable("v2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn blue_green_candidates_wait_for_promotion() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 10, 0, 0).unwrap();
+        let strategy = Rollout::BlueGreen(BlueGreenRollout {});
+
+        let status = RolloutStatus::stable("v1").progress(Some(&strategy), "v2", false, now)?;
+        assert_eq!(status.phase, PHASE_AWAITING_PROMOTION);
+        assert_eq!(status.canary_weight, 0);
+
+        let status = status.progress(Some(&strategy), "v2", false, now + Duration::days(1))?;
+        assert_eq!(status.candidate_source.as_deref(), Some("v2"));
+
+        assert_eq!(
+            status.progress(Some(&strategy), "v2", true, now)?,
+            RolloutStatus::stable("v2")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn rollbacks_walk_back_through_the_deployment_history() -> Result<()> {
+        let mut status = ModelStatus::default();
+
+        assert!(status.rollback_version("mymodel").is_err());
+
+        for version in ["1", "2", "3"] {
+            status.record_deployment(version);
+        }
+
+        status.pinned_version = Some(status.rollback_version("mymodel")?);
+        assert_eq!(status.pinned_version.as_deref(), Some("2"));
+
+        status.record_deployment("2");
+        assert_eq!(status.rollback_version("mymodel")?, "1");
+
+        status.record_deployment("1");
+        assert!(status.rollback_version("mymodel").is_err());
+
+        status.record_deployment("4");
+        assert_eq!(status.deployment_history, vec!["1".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn promoted_versions_take_precedence_over_configured_pins() {
+        let cfg = ModelDeploymentCfg {
+            pinned_version: Some("1".to_string()),
+            ..ModelDeploymentCfg::default()
+        };
+        let mut status = ModelStatus::default();
+
+        assert_eq!(status.target_version(None), None);
+        assert_eq!(status.target_version(Some(&cfg)), Some("1"));
+
+        status.pinned_version = Some("2".to_string());
+        assert_eq!(status.target_version(Some(&cfg)), Some("2"));
+    }
+}
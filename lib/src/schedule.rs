@@ -0,0 +1,407 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::{
+    error::AmeError,
+    grpc::{BatchInferenceCfg, EnvVar, ProjectCfg, PushTriggerCfg, TaskCfg, TriggerCfg},
+    Result,
+};
+
+/// Number of fields in a cron expression, `cron_parser` panics on expressions with fewer fields
+/// so they are rejected before parsing.
+const CRON_FIELDS: usize = 5;
+
+/// Time to wait for further data set updates before a triggered run is started, unless the
+/// trigger configures its own.
+pub const DEFAULT_DEBOUNCE_SECONDS: u64 = 60;
+
+/// Upper limit on the number of runs a single backfill can create.
+pub const MAX_BACKFILL_RUNS: usize = 1000;
+
+/// Environment variable scheduled runs receive the instant they were scheduled for in, as an
+/// RFC 3339 timestamp.
+pub static LOGICAL_TIME_ENV: &str = "AME_LOGICAL_TIME";
+
+/// Determines what happens when a scheduled run is due while an earlier run of the same task is
+/// still active.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConcurrencyPolicy {
+    /// Start the new run alongside the active runs.
+    #[default]
+    Allow,
+
+    /// Skip the new run.
+    Forbid,
+
+    /// Remove the active runs before starting the new run.
+    Replace,
+}
+
+impl FromStr for ConcurrencyPolicy {
+    type Err = AmeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "Allow" => Ok(ConcurrencyPolicy::Allow),
+            "Forbid" => Ok(ConcurrencyPolicy::Forbid),
+            "Replace" => Ok(ConcurrencyPolicy::Replace),
+            _ => Err(AmeError::InvalidConcurrencyPolicy(s.to_string())),
+        }
+    }
+}
+
+/// Parses an RFC 3339 timestamp or a date, dates are interpreted as midnight UTC.
+pub fn parse_time(time: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(time, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|time| Utc.from_utc_datetime(&time))
+        .ok_or(AmeError::InvalidTime(time.to_string()))
+}
+
+/// Checks that `schedule` is a cron expression with minute, hour, day of month, month and day
+/// of week fields.
+pub fn validate_cron_expression(schedule: &str) -> Result<()> {
+    let fields = schedule.split_whitespace().count();
+
+    if fields != CRON_FIELDS {
+        return Err(AmeError::InvalidCronSchedule(
+            schedule.to_string(),
+            format!("expected {CRON_FIELDS} fields but found {fields}"),
+        ));
+    }
+
+    cron_parser::parse(schedule, &Utc::now())
+        .map_err(|e| AmeError::InvalidCronSchedule(schedule.to_string(), format!("{e:?}")))?;
+
+    Ok(())
+}
+
+impl TriggerCfg {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(ref schedule) = self.schedule {
+            validate_cron_expression(schedule)?;
+        }
+
+        self.tz()?;
+        self.parsed_concurrency_policy()?;
+
+        Ok(())
+    }
+
+    /// Returns the timezone the schedule is evaluated in, defaults to UTC.
+    pub fn tz(&self) -> Result<Tz> {
+        match self.timezone {
+            Some(ref timezone) => timezone
+                .parse()
+                .map_err(|_| AmeError::InvalidTimezone(timezone.clone())),
+            None => Ok(Tz::UTC),
+        }
+    }
+
+    /// Returns the concurrency policy of the schedule, defaults to `Allow`.
+    pub fn parsed_concurrency_policy(&self) -> Result<ConcurrencyPolicy> {
+        self.concurrency_policy
+            .as_deref()
+            .map(ConcurrencyPolicy::from_str)
+            .unwrap_or(Ok(ConcurrencyPolicy::default()))
+    }
+
+    /// Returns the first schedule instant after `after`, `None` is returned if there is no
+    /// schedule.
+    pub fn next_run(&self, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>> {
+        let Some(ref schedule) = self.schedule else {
+            return Ok(None);
+        };
+
+        validate_cron_expression(schedule)?;
+
+        let after = after.with_timezone(&self.tz()?);
+
+        Ok(Some(
+            cron_parser::parse(schedule, &after)?.with_timezone(&Utc),
+        ))
+    }
+
+    /// Returns the latest schedule instant after `last_run` which is not after `now`.
+    ///
+    /// Only the latest instant is returned when several have passed, so a controller which
+    /// has been unavailable for a while starts a single run instead of one per missed instant.
+    /// Instants are searched for in a window before `now` which is doubled until it contains one,
+    /// so a `last_run` far in the past does not require evaluating every instant since then.
+    pub fn due_run(
+        &self,
+        last_run: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let mut window = Duration::minutes(1);
+
+        loop {
+            let from = (now - window).max(last_run);
+
+            if let Some(mut due) = self.next_run(from)?.filter(|instant| instant <= &now) {
+                while let Some(instant) = self.next_run(due)?.filter(|instant| instant <= &now) {
+                    due = instant;
+                }
+
+                return Ok(Some(due));
+            }
+
+            if from == last_run {
+                return Ok(None);
+            }
+
+            window = window * 2;
+        }
+    }
+
+    /// Returns every schedule instant from `from` up to and including `to`.
+    pub fn runs_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>> {
+        if from > to {
+            return Err(AmeError::InvalidBackfill(format!(
+                "the start {from} is after the end {to}"
+            )));
+        }
+
+        let mut runs = vec![];
+        let mut next = self.next_run(from - Duration::seconds(1))?;
+
+        while let Some(instant) = next.filter(|instant| instant <= &to) {
+            if runs.len() == MAX_BACKFILL_RUNS {
+                return Err(AmeError::InvalidBackfill(format!(
+                    "more than {MAX_BACKFILL_RUNS} runs are scheduled between {from} and {to}"
+                )));
+            }
+
+            runs.push(instant);
+            next = self.next_run(instant)?;
+        }
+
+        Ok(runs)
+    }
+
+    /// Returns how long data sets must go without updates before a run is triggered.
+    pub fn debounce(&self) -> Duration {
+        let seconds = self.debounce_seconds.unwrap_or(DEFAULT_DEBOUNCE_SECONDS);
+
+        Duration::seconds(seconds.min(u32::MAX as u64) as i64)
+    }
+
+    /// Checks if a run scheduled for `instant` may still be started at `now`, runs which are
+    /// later than the starting deadline are skipped.
+    pub fn is_within_deadline(&self, instant: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        self.starting_deadline_seconds.map_or(true, |deadline| {
+            now.signed_duration_since(instant) <= Duration::seconds(deadline as i64)
+        })
+    }
+}
+
+/// Checks if `value` matches a branch or path filter. Filters ending in `*` match any value with
+/// the preceding prefix, other filters match the value itself and, for paths, anything below it.
+fn matches_filter(filter: &str, value: &str) -> bool {
+    match filter.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix),
+        None => value == filter || value.starts_with(&format!("{}/", filter.trim_end_matches('/'))),
+    }
+}
+
+impl PushTriggerCfg {
+    /// Checks if a push to `branch` changing `changed_paths` should start a run, empty filters
+    /// match every branch or path.
+    pub fn matches(&self, branch: &str, changed_paths: &[String]) -> bool {
+        let branch_matches = self.branches.is_empty()
+            || self
+                .branches
+                .iter()
+                .any(|filter| matches_filter(filter, branch));
+
+        let paths_match = self.paths.is_empty()
+            || changed_paths.iter().any(|path| {
+                self.paths
+                    .iter()
+                    .any(|filter| matches_filter(filter.trim_start_matches("./"), path))
+            });
+
+        branch_matches && paths_match
+    }
+}
+
+impl BatchInferenceCfg {
+    /// Returns a trigger firing on the batch inference schedule.
+    pub fn trigger(&self) -> TriggerCfg {
+        TriggerCfg {
+            schedule: Some(self.schedule.clone()),
+            timezone: self.timezone.clone(),
+            ..TriggerCfg::default()
+        }
+    }
+}
+
+impl TaskCfg {
+    /// Exposes the instant a run was scheduled for to the task through `AME_LOGICAL_TIME`.
+    pub fn set_logical_time(&mut self, instant: DateTime<Utc>) {
+        self.env.retain(|var| var.key != LOGICAL_TIME_ENV);
+        self.env.push(EnvVar {
+            key: LOGICAL_TIME_ENV.to_string(),
+            val: instant.to_rfc3339(),
+        });
+    }
+}
+
+impl ProjectCfg {
+    /// Validates the triggers of every task and template in the project, and the batch
+    /// inference and monitoring schedules of its models.
+    pub fn validate_triggers(&self) -> Result<()> {
+        for task in self.tasks.iter().chain(self.templates.iter()) {
+            if let Some(ref triggers) = task.triggers {
+                triggers.validate()?;
+            }
+        }
+
+        for model in self.models.iter() {
+            if let Some(ref batch_inference) = model.batch_inference {
+                batch_inference.trigger().validate()?;
+            }
+
+            if let Some(ref monitoring) = model.monitoring {
+                monitoring.trigger().validate()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn trigger(schedule: &str) -> TriggerCfg {
+        TriggerCfg {
+            schedule: Some(schedule.to_string()),
+            ..TriggerCfg::default()
+        }
+    }
+
+    #[test]
+    fn rejects_short_cron_expressions() {
+        assert!(matches!(
+            trigger("* *").validate(),
+            Err(AmeError::InvalidCronSchedule(..))
+        ));
+        assert!(trigger("0 3 * * *").validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_timezones_and_policies() {
+        let mut cfg = trigger("0 3 * * *");
+        cfg.timezone = Some("Mars/Olympus_Mons".to_string());
+
+        assert!(matches!(cfg.validate(), Err(AmeError::InvalidTimezone(_))));
+
+        cfg.timezone = None;
+        cfg.concurrency_policy = Some("Sometimes".to_string());
+
+        assert!(matches!(
+            cfg.validate(),
+            Err(AmeError::InvalidConcurrencyPolicy(_))
+        ));
+    }
+
+    #[test]
+    fn only_the_latest_missed_run_is_due() -> Result<()> {
+        let cfg = trigger("0 * * * *");
+        let last_run = Utc.with_ymd_and_hms(2023, 8, 1, 10, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2023, 8, 1, 13, 30, 0).unwrap();
+
+        assert_eq!(
+            cfg.due_run(last_run, now)?,
+            Some(Utc.with_ymd_and_hms(2023, 8, 1, 13, 0, 0).unwrap())
+        );
+        assert_eq!(cfg.due_run(now, now)?, None);
+        assert_eq!(
+            cfg.due_run(Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap(), now)?,
+            Some(Utc.with_ymd_and_hms(2023, 8, 1, 13, 0, 0).unwrap())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn schedules_are_evaluated_in_their_timezone() -> Result<()> {
+        let mut cfg = trigger("0 3 * * *");
+        cfg.timezone = Some("Europe/Oslo".to_string());
+
+        let after = Utc.with_ymd_and_hms(2023, 8, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            cfg.next_run(after)?,
+            Some(Utc.with_ymd_and_hms(2023, 8, 1, 1, 0, 0).unwrap())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn runs_past_the_starting_deadline_are_skipped() {
+        let mut cfg = trigger("0 * * * *");
+        cfg.starting_deadline_seconds = Some(300);
+
+        let instant = Utc.with_ymd_and_hms(2023, 8, 1, 10, 0, 0).unwrap();
+
+        assert!(cfg.is_within_deadline(instant, instant + Duration::seconds(60)));
+        assert!(!cfg.is_within_deadline(instant, instant + Duration::seconds(600)));
+    }
+
+    #[test]
+    fn pushes_match_branch_and_path_filters() {
+        let cfg = PushTriggerCfg {
+            branches: vec!["main".to_string(), "release/*".to_string()],
+            paths: vec!["training".to_string(), "./ame.yaml".to_string()],
+        };
+
+        let paths = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        assert!(cfg.matches("main", &paths(&["training/train.py"])));
+        assert!(cfg.matches("release/1.0", &paths(&["ame.yaml"])));
+        assert!(!cfg.matches("feature", &paths(&["training/train.py"])));
+        assert!(!cfg.matches("main", &paths(&["training_data/raw.csv", "README.md"])));
+
+        assert!(PushTriggerCfg::default().matches("feature", &paths(&["README.md"])));
+    }
+
+    #[test]
+    fn backfills_every_run_in_the_range() -> Result<()> {
+        let cfg = trigger("0 3 * * *");
+
+        let runs = cfg.runs_between(
+            parse_time("2023-08-01")?,
+            parse_time("2023-08-03T03:00:00Z")?,
+        )?;
+
+        assert_eq!(
+            runs,
+            vec![
+                Utc.with_ymd_and_hms(2023, 8, 1, 3, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2023, 8, 2, 3, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2023, 8, 3, 3, 0, 0).unwrap(),
+            ]
+        );
+
+        assert!(cfg
+            .runs_between(parse_time("2023-08-03")?, parse_time("2023-08-01")?)
+            .is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use crate::grpc::{ProjectCfg, TaskCfg};
+
+impl ProjectCfg {
+    /// Merges the project's task defaults under `cfg`.
+    ///
+    /// Tasks created from a template are left as is, they receive the defaults once the template
+    /// is resolved so the template takes precedence over the defaults.
+    pub fn with_task_defaults(&self, cfg: TaskCfg) -> TaskCfg {
+        match self.defaults {
+            Some(ref defaults) if cfg.from_template.is_none() => merge_task_defaults(defaults, cfg),
+            _ => cfg,
+        }
+    }
+}
+
+/// Merges `defaults` under `cfg`, anything set in `cfg` takes precedence.
+///
+/// The executor and artifact configuration are used when `cfg` has none, resources and
+/// environment variables are merged by key and secrets are added to those of `cfg`. The name,
+/// task reference, data sets and triggers of the defaults are ignored.
+pub fn merge_task_defaults(defaults: &TaskCfg, mut cfg: TaskCfg) -> TaskCfg {
+    if cfg.executor.is_none() {
+        cfg.executor = defaults.executor.clone();
+    }
+
+    if cfg.artifact_cfg.is_none() {
+        cfg.artifact_cfg = defaults.artifact_cfg.clone();
+    }
+
+    for (key, val) in defaults.resources.iter() {
+        cfg.resources
+            .entry(key.clone())
+            .or_insert_with(|| val.clone());
+    }
+
+    let mut env: Vec<_> = defaults
+        .env
+        .iter()
+        .filter(|var| !cfg.env.iter().any(|v| v.key == var.key))
+        .cloned()
+        .collect();
+    env.append(&mut cfg.env);
+    cfg.env = env;
+
+    for secret in defaults.secrets.iter() {
+        if !cfg.secrets.contains(secret) {
+            cfg.secrets.push(secret.clone());
+        }
+    }
+
+    cfg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grpc::{
+        task_cfg::Executor, AmeSecretVariant, EnvVar, PipEnvExecutor, PoetryExecutor, Secret,
+        TemplateRef,
+    };
+
+    fn env(key: &str, val: &str) -> EnvVar {
+        EnvVar {
+            key: key.to_string(),
+            val: val.to_string(),
+        }
+    }
+
+    fn defaults() -> TaskCfg {
+        TaskCfg {
+            name: Some("defaults".to_string()),
+            executor: Some(Executor::Poetry(PoetryExecutor {
+                python_version: "3.11".to_string(),
+                command: "python train.py".to_string(),
+            })),
+            resources: [
+                ("cpu".to_string(), "2".to_string()),
+                ("memory".to_string(), "4Gi".to_string()),
+            ]
+            .into(),
+            env: vec![env("STAGE", "dev"), env("LOG_LEVEL", "info")],
+            secrets: vec![Secret {
+                variant: Some(crate::grpc::secret::Variant::Ame(AmeSecretVariant {
+                    key: "token".to_string(),
+                    inject_as: "TOKEN".to_string(),
+                })),
+            }],
+            ..TaskCfg::default()
+        }
+    }
+
+    #[test]
+    fn tasks_take_precedence_over_defaults() {
+        let cfg = TaskCfg {
+            name: Some("train".to_string()),
+            executor: Some(Executor::PipEnv(PipEnvExecutor {
+                command: "python other.py".to_string(),
+            })),
+            resources: [("memory".to_string(), "8Gi".to_string())].into(),
+            env: vec![env("STAGE", "prod")],
+            ..TaskCfg::default()
+        };
+
+        let cfg = merge_task_defaults(&defaults(), cfg);
+
+        assert_eq!(cfg.name.as_deref(), Some("train"));
+        assert!(matches!(cfg.executor, Some(Executor::PipEnv(_))));
+        assert_eq!(cfg.resources.get("memory").unwrap(), "8Gi");
+        assert_eq!(cfg.resources.get("cpu").unwrap(), "2");
+        assert_eq!(
+            cfg.env,
+            vec![env("LOG_LEVEL", "info"), env("STAGE", "prod")]
+        );
+        assert_eq!(cfg.secrets, defaults().secrets);
+    }
+
+    #[test]
+    fn defaults_fill_in_empty_tasks() {
+        let cfg = merge_task_defaults(&defaults(), TaskCfg::default());
+
+        assert_eq!(cfg.executor, defaults().executor);
+        assert_eq!(cfg.env, defaults().env);
+        assert_eq!(cfg.name, None);
+
+        let merged_again = merge_task_defaults(&defaults(), cfg.clone());
+        assert_eq!(merged_again, cfg);
+    }
+
+    #[test]
+    fn templated_tasks_receive_defaults_after_their_template() {
+        let project = ProjectCfg {
+            defaults: Some(defaults()),
+            ..ProjectCfg::default()
+        };
+        let cfg = TaskCfg {
+            from_template: Some(TemplateRef {
+                name: "gpu".to_string(),
+                project: None,
+            }),
+            ..TaskCfg::default()
+        };
+
+        assert_eq!(project.with_task_defaults(cfg.clone()), cfg);
+        assert!(project
+            .with_task_defaults(TaskCfg::default())
+            .executor
+            .is_some());
+    }
+}
@@ -0,0 +1,864 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    path::Path,
+};
+
+use schemars::{
+    schema::{RootSchema, Schema, SchemaObject, SingleOrVec},
+    schema_for, JsonSchema, Map,
+};
+use serde::de::DeserializeOwned;
+use serde_yaml::Value;
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+use crate::{
+    grpc::{batch_inference_cfg::Scoring, ProjectCfg, TaskCfg, TaskRef, TemplateRef, TriggerCfg},
+    include::{resolve_includes, Origins},
+    interpolation::InterpolationContext,
+};
+
+/// A problem found in a project file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub file: String,
+
+    /// Line of the problem, starting at 1.
+    pub line: usize,
+
+    /// Column of the problem, starting at 1.
+    pub column: usize,
+
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+/// Every problem found in a project file, ordered by position.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.0.iter().map(Diagnostic::to_string).collect();
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Returns the JSON Schema of project files, editors can use it to validate and complete them.
+pub fn project_schema() -> RootSchema {
+    schema_for!(ProjectCfg)
+}
+
+impl ProjectCfg {
+    /// Parses and validates the project file `file`, failing with every problem found in it or
+    /// in the files it includes. `file` and included files are relative to `root`.
+    pub fn from_yaml(root: &Path, file: &str, source: &str) -> Result<ProjectCfg, Diagnostics> {
+        let context = InterpolationContext::current(root);
+
+        ProjectCfg::from_yaml_checked(root, file, source, &context, |_| vec![])
+    }
+
+    /// Parses and validates the project file `file` like [`ProjectCfg::from_yaml`], resolving
+    /// expressions with `context`. `checks` returns the path and a description of further
+    /// problems, e.g. features which are not supported where the project is read from.
+    pub fn from_yaml_checked(
+        root: &Path,
+        file: &str,
+        source: &str,
+        context: &InterpolationContext,
+        checks: impl Fn(&ProjectCfg) -> Vec<(String, String)>,
+    ) -> Result<ProjectCfg, Diagnostics> {
+        match ProjectCfg::diagnose_checked(root, file, source, context, checks) {
+            (Some(cfg), diagnostics) if diagnostics.is_empty() => Ok(cfg),
+            (_, diagnostics) => Err(Diagnostics(diagnostics)),
+        }
+    }
+
+    /// Parses the project file `file`, merges the files it includes and resolves its `${{ ... }}`
+    /// expressions, returning the project if every file could be parsed and every problem found
+    /// in them.
+    ///
+    /// Fields which are not part of the project schema are rejected, and parsed projects are
+    /// checked for duplicate names, references to tasks, templates and data sets which do not
+    /// exist in the project, invalid schedules and invalid resource quantities.
+    pub fn diagnose(
+        root: &Path,
+        file: &str,
+        source: &str,
+    ) -> (Option<ProjectCfg>, Vec<Diagnostic>) {
+        let context = InterpolationContext::current(root);
+
+        ProjectCfg::diagnose_checked(root, file, source, &context, |_| vec![])
+    }
+
+    fn diagnose_checked(
+        root: &Path,
+        file: &str,
+        source: &str,
+        context: &InterpolationContext,
+        checks: impl Fn(&ProjectCfg) -> Vec<(String, String)>,
+    ) -> (Option<ProjectCfg>, Vec<Diagnostic>) {
+        let (cfg, mut diagnostics, main) = parse::<ProjectCfg>(file, source);
+
+        let Some(mut cfg) = cfg else {
+            diagnostics.sort();
+            return (None, diagnostics);
+        };
+
+        let mut files = vec![main];
+        let mut origins = Origins::default();
+
+        if !resolve_includes(root, &mut cfg, &mut files, &mut origins, &mut diagnostics) {
+            diagnostics.sort();
+            return (None, diagnostics);
+        }
+
+        for (path, message) in cfg.interpolate(context) {
+            let (file, path) = origins.locate(&path);
+            diagnostics.push(files[file].diagnostic(&path, message));
+        }
+
+        for (path, message) in cfg.problems().into_iter().chain(checks(&cfg)) {
+            let (file, path) = origins.locate(&path);
+            diagnostics.push(files[file].diagnostic(&path, message));
+        }
+
+        diagnostics.sort();
+
+        (Some(cfg), diagnostics)
+    }
+
+    /// Checks names, references, schedules and resource quantities, returning the path and a
+    /// description of every problem.
+    pub fn problems(&self) -> Vec<(String, String)> {
+        let mut problems = vec![];
+
+        duplicates(
+            "tasks",
+            "task",
+            self.tasks.iter().map(|t| t.name.as_deref()),
+            &mut problems,
+        );
+        duplicates(
+            "templates",
+            "template",
+            self.templates.iter().map(|t| t.name.as_deref()),
+            &mut problems,
+        );
+        duplicates(
+            "dataSets",
+            "data set",
+            self.data_sets.iter().map(|ds| Some(ds.name.as_str())),
+            &mut problems,
+        );
+        duplicates(
+            "models",
+            "model",
+            self.models.iter().map(|m| Some(m.name.as_str())),
+            &mut problems,
+        );
+
+        for (path, task) in self.task_cfgs() {
+            self.task_problems(&path, task, &mut problems);
+        }
+
+        for (i, data_set) in self.data_sets.iter().enumerate() {
+            let path = format!("dataSets[{i}]");
+
+            if let Some(ref size) = data_set.size {
+                quantity_problems(&format!("{path}.size"), size, &mut problems);
+            }
+
+            if let Some(min_size) = data_set
+                .validation
+                .as_ref()
+                .and_then(|v| v.min_size.as_ref())
+            {
+                quantity_problems(
+                    &format!("{path}.validation.minSize"),
+                    min_size,
+                    &mut problems,
+                );
+            }
+        }
+
+        for (i, model) in self.models.iter().enumerate() {
+            let path = format!("models[{i}]");
+
+            if let Some(ref cfg) = model.batch_inference {
+                let path = format!("{path}.batchInference");
+
+                self.data_set_problems(
+                    &format!("{path}.inputDataSet"),
+                    &cfg.input_data_set,
+                    &mut problems,
+                );
+                self.data_set_problems(
+                    &format!("{path}.outputDataSet"),
+                    &cfg.output_data_set,
+                    &mut problems,
+                );
+                trigger_problems(&format!("{path}.schedule"), &cfg.trigger(), &mut problems);
+            }
+
+            if let Some(ref cfg) = model.monitoring {
+                let path = format!("{path}.monitoring");
+
+                self.data_set_problems(
+                    &format!("{path}.referenceDataSet"),
+                    &cfg.reference_data_set,
+                    &mut problems,
+                );
+                trigger_problems(&format!("{path}.schedule"), &cfg.trigger(), &mut problems);
+            }
+
+            if let Some(ref deployment) = model.deployment {
+                for (key, quantity) in deployment.resources.iter() {
+                    quantity_problems(
+                        &format!("{path}.deployment.resources.{key}"),
+                        quantity,
+                        &mut problems,
+                    );
+                }
+            }
+        }
+
+        problems
+    }
+
+    /// Returns every task configuration in the project with its path.
+    fn task_cfgs(&self) -> Vec<(String, &TaskCfg)> {
+        let mut cfgs = vec![];
+
+        for (i, task) in self.tasks.iter().enumerate() {
+            cfgs.push((format!("tasks[{i}]"), task));
+        }
+
+        for (i, template) in self.templates.iter().enumerate() {
+            cfgs.push((format!("templates[{i}]"), template));
+        }
+
+        if let Some(ref defaults) = self.defaults {
+            cfgs.push(("defaults".to_string(), defaults));
+        }
+
+        for (i, data_set) in self.data_sets.iter().enumerate() {
+            if let Some(ref task) = data_set.task {
+                cfgs.push((format!("dataSets[{i}].task"), task));
+            }
+        }
+
+        for (i, model) in self.models.iter().enumerate() {
+            let path = format!("models[{i}]");
+
+            if let Some(ref task) = model.validation_task {
+                cfgs.push((format!("{path}.validationTask"), task));
+            }
+
+            if let Some(task) = model.training.as_ref().and_then(|t| t.task.as_ref()) {
+                cfgs.push((format!("{path}.training.task"), task));
+            }
+
+            if let Some(Scoring::Task(task)) = model
+                .batch_inference
+                .as_ref()
+                .and_then(|b| b.scoring.as_ref())
+            {
+                cfgs.push((format!("{path}.batchInference.scoring.task"), task));
+            }
+
+            if let Some(task) = model.monitoring.as_ref().and_then(|m| m.task.as_ref()) {
+                cfgs.push((format!("{path}.monitoring.task"), task));
+            }
+        }
+
+        cfgs
+    }
+
+    fn task_problems(&self, path: &str, task: &TaskCfg, problems: &mut Vec<(String, String)>) {
+        if let Some(TaskRef {
+            ref name,
+            project: None,
+        }) = task.task_ref
+        {
+            if !self.tasks.iter().any(|t| t.name.as_ref() == Some(name)) {
+                problems.push((
+                    format!("{path}.taskRef.name"),
+                    format!("task `{name}` does not exist in the project"),
+                ));
+            }
+        }
+
+        if let Some(TemplateRef {
+            ref name,
+            project: None,
+        }) = task.from_template
+        {
+            if !self.templates.iter().any(|t| t.name.as_ref() == Some(name)) {
+                problems.push((
+                    format!("{path}.fromTemplate.name"),
+                    format!("template `{name}` does not exist in the project"),
+                ));
+            }
+        }
+
+        for (i, data_set) in task.data_sets.iter().enumerate() {
+            self.data_set_problems(&format!("{path}.dataSets[{i}]"), data_set, problems);
+        }
+
+        if let Some(ref triggers) = task.triggers {
+            trigger_problems(&format!("{path}.triggers"), triggers, problems);
+
+            for (i, data_set) in triggers.on_data_set_update.iter().enumerate() {
+                self.data_set_problems(
+                    &format!("{path}.triggers.onDataSetUpdate[{i}]"),
+                    data_set,
+                    problems,
+                );
+            }
+        }
+
+        for (key, quantity) in task.resources.iter() {
+            quantity_problems(&format!("{path}.resources.{key}"), quantity, problems);
+        }
+    }
+
+    /// Checks that a reference to a data set in this project exists, references to data sets in
+    /// other projects are resolved when the project is deployed.
+    fn data_set_problems(&self, path: &str, data_set: &str, problems: &mut Vec<(String, String)>) {
+        if data_set.contains(['.', '/']) {
+            return;
+        }
+
+        if !self.data_sets.iter().any(|ds| ds.name == data_set) {
+            problems.push((
+                path.to_string(),
+                format!("data set `{data_set}` does not exist in the project"),
+            ));
+        }
+    }
+}
+
+fn duplicates<'a>(
+    section: &str,
+    kind: &str,
+    names: impl Iterator<Item = Option<&'a str>>,
+    problems: &mut Vec<(String, String)>,
+) {
+    let mut seen = BTreeSet::new();
+
+    for (i, name) in names.enumerate() {
+        let Some(name) = name else {
+            continue;
+        };
+
+        if !seen.insert(name) {
+            problems.push((
+                format!("{section}[{i}].name"),
+                format!("duplicate {kind} name `{name}`"),
+            ));
+        }
+    }
+}
+
+fn trigger_problems(path: &str, trigger: &TriggerCfg, problems: &mut Vec<(String, String)>) {
+    if let Err(e) = trigger.validate() {
+        problems.push((path.to_string(), e.to_string()));
+    }
+}
+
+fn quantity_problems(path: &str, quantity: &str, problems: &mut Vec<(String, String)>) {
+    if !is_quantity(quantity) {
+        problems.push((
+            path.to_string(),
+            format!("`{quantity}` is not a valid quantity, e.g. 500m, 2 or 4Gi"),
+        ));
+    }
+}
+
+/// Checks that `quantity` is a Kubernetes resource quantity: a decimal number followed by an
+/// optional binary or decimal SI suffix or exponent.
+pub fn is_quantity(quantity: &str) -> bool {
+    let quantity = quantity.strip_prefix(['+', '-']).unwrap_or(quantity);
+    let number_len = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(number_len);
+
+    let valid_number =
+        number.chars().any(|c| c.is_ascii_digit()) && number.matches('.').count() < 2;
+
+    let valid_suffix = matches!(
+        suffix,
+        "" | "n"
+            | "u"
+            | "m"
+            | "k"
+            | "M"
+            | "G"
+            | "T"
+            | "P"
+            | "E"
+            | "Ki"
+            | "Mi"
+            | "Gi"
+            | "Ti"
+            | "Pi"
+            | "Ei"
+    ) || suffix
+        .strip_prefix(['e', 'E'])
+        .map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent))
+        .map_or(false, |exponent| {
+            !exponent.is_empty() && exponent.chars().all(|c| c.is_ascii_digit())
+        });
+
+    valid_number && valid_suffix
+}
+
+fn yaml_diagnostic(file: &str, e: &serde_yaml::Error) -> Diagnostic {
+    let (line, column) = e
+        .location()
+        .map(|location| (location.line(), location.column()))
+        .unwrap_or((1, 1));
+
+    Diagnostic {
+        file: file.to_string(),
+        line,
+        column,
+        message: e
+            .to_string()
+            .replacen(&format!(" at line {line} column {column}"), "", 1),
+    }
+}
+
+pub(crate) fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+/// A parsed file with the positions of its nodes.
+#[derive(Debug)]
+pub(crate) struct SourceFile {
+    pub(crate) file: String,
+    positions: Positions,
+}
+
+impl SourceFile {
+    /// Returns a diagnostic positioned at the node at `path` in this file.
+    pub(crate) fn diagnostic(&self, path: &str, message: String) -> Diagnostic {
+        let (line, column) = self.positions.get(path);
+
+        Diagnostic {
+            file: self.file.clone(),
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+/// Parses `source` as a `T`, reporting syntax errors, type errors and fields which are not part
+/// of the schema of `T`.
+pub(crate) fn parse<T: DeserializeOwned + JsonSchema>(
+    file: &str,
+    source: &str,
+) -> (Option<T>, Vec<Diagnostic>, SourceFile) {
+    let mut source_file = SourceFile {
+        file: file.to_string(),
+        positions: Positions::default(),
+    };
+
+    let value: Value = match serde_yaml::from_str(source) {
+        Ok(value) => value,
+        Err(e) => return (None, vec![yaml_diagnostic(file, &e)], source_file),
+    };
+
+    source_file.positions = Positions::parse(source);
+
+    let root = schema_for!(T);
+    let walker = SchemaWalker {
+        definitions: &root.definitions,
+    };
+
+    let mut unknown = vec![];
+    walker.unknown_fields(
+        &Schema::Object(root.schema.clone()),
+        &value,
+        "",
+        &mut unknown,
+    );
+
+    let mut diagnostics: Vec<Diagnostic> = unknown
+        .into_iter()
+        .map(|(path, message)| source_file.diagnostic(&path, message))
+        .collect();
+
+    let parsed = match serde_yaml::from_str::<T>(source) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            diagnostics.push(yaml_diagnostic(file, &e));
+            None
+        }
+    };
+
+    (parsed, diagnostics, source_file)
+}
+
+struct SchemaWalker<'a> {
+    definitions: &'a Map<String, Schema>,
+}
+
+impl<'a> SchemaWalker<'a> {
+    /// Collects the schema objects a value matching `schema` has to match one of, following
+    /// references and sub schemas.
+    fn alternatives(&self, schema: &'a Schema, found: &mut Vec<&'a SchemaObject>) {
+        let Schema::Object(object) = schema else {
+            return;
+        };
+
+        if let Some(schema) = object
+            .reference
+            .as_deref()
+            .and_then(|reference| reference.strip_prefix("#/definitions/"))
+            .and_then(|name| self.definitions.get(name))
+        {
+            self.alternatives(schema, found);
+        }
+
+        found.push(object);
+
+        if let Some(ref subschemas) = object.subschemas {
+            for schema in [&subschemas.all_of, &subschemas.any_of, &subschemas.one_of]
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                self.alternatives(schema, found);
+            }
+        }
+    }
+
+    /// Looks up the schema of the field `key` among `alternatives`.
+    fn field(&self, alternatives: &[&'a SchemaObject], key: &str) -> Option<&'a Schema> {
+        let objects = alternatives.iter().filter_map(|o| o.object.as_deref());
+
+        objects
+            .clone()
+            .find_map(|o| o.properties.get(key))
+            .or(objects
+                .filter_map(|o| o.additional_properties.as_deref())
+                .find(|schema| !matches!(schema, Schema::Bool(false))))
+    }
+
+    fn unknown_fields(
+        &self,
+        schema: &'a Schema,
+        value: &Value,
+        path: &str,
+        found: &mut Vec<(String, String)>,
+    ) {
+        let mut alternatives = vec![];
+        self.alternatives(schema, &mut alternatives);
+
+        match value {
+            // Tags select an enum variant, e.g. `!poetry`, the variant is part of the value so
+            // the path is left as is.
+            Value::Tagged(tagged) => {
+                let variant = tagged.tag.to_string();
+                if let Some(schema) = self.field(&alternatives, variant.trim_start_matches('!')) {
+                    self.unknown_fields(schema, &tagged.value, path, found);
+                }
+            }
+            Value::Mapping(mapping) => {
+                if alternatives.iter().all(|o| o.object.is_none()) {
+                    return;
+                }
+
+                for (key, value) in mapping.iter() {
+                    let Some(key) = key.as_str() else {
+                        continue;
+                    };
+
+                    match self.field(&alternatives, key) {
+                        Some(schema) => self.unknown_fields(schema, value, &join(path, key), found),
+                        None => found.push((join(path, key), format!("unknown field `{key}`"))),
+                    }
+                }
+            }
+            Value::Sequence(items) => {
+                let Some(SingleOrVec::Single(schema)) = alternatives
+                    .iter()
+                    .find_map(|o| o.array.as_ref().and_then(|a| a.items.as_ref()))
+                else {
+                    return;
+                };
+
+                for (i, item) in items.iter().enumerate() {
+                    self.unknown_fields(schema, item, &format!("{path}[{i}]"), found);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Positions of the nodes in a YAML document by their path, e.g. `tasks[0].name`. Values in a
+/// mapping are positioned at their key.
+#[derive(Debug, Default)]
+pub struct Positions {
+    positions: BTreeMap<String, (usize, usize)>,
+    stack: Vec<Collection>,
+}
+
+#[derive(Debug)]
+enum Collection {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, index: usize },
+}
+
+impl Positions {
+    pub fn parse(source: &str) -> Positions {
+        let mut positions = Positions::default();
+
+        // Syntax errors are reported by serde_yaml, they only leave the rest of the document
+        // without positions here.
+        let _ = Parser::new(source.chars()).load(&mut positions, false);
+
+        positions
+    }
+
+    /// Returns the line and column of the node at `path`, or of its closest ancestor with a
+    /// known position.
+    pub fn get(&self, path: &str) -> (usize, usize) {
+        let mut path = path;
+
+        loop {
+            if let Some(position) = self.positions.get(path) {
+                return *position;
+            }
+
+            match path.rfind(['.', '[']) {
+                Some(i) => path = &path[..i],
+                None => return (1, 1),
+            }
+        }
+    }
+
+    /// Returns the path of the node starting at `position`, keys are recorded and `None` is
+    /// returned for them.
+    fn node_path(&mut self, key: Option<&str>, position: (usize, usize)) -> Option<String> {
+        let path = match self.stack.last_mut() {
+            None => String::new(),
+            Some(Collection::Mapping {
+                path,
+                key: key_slot,
+            }) => match key_slot.take() {
+                Some(key) => join(path, &key),
+                None => {
+                    let key = key.unwrap_or_default().to_string();
+                    self.positions.entry(join(path, &key)).or_insert(position);
+                    *key_slot = Some(key);
+
+                    return None;
+                }
+            },
+            Some(Collection::Sequence { path, index }) => {
+                *index += 1;
+                format!("{path}[{}]", *index - 1)
+            }
+        };
+
+        self.positions.entry(path.clone()).or_insert(position);
+
+        Some(path)
+    }
+}
+
+impl MarkedEventReceiver for Positions {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let position = (mark.line(), mark.col() + 1);
+
+        match ev {
+            Event::Scalar(ref value, ..) => {
+                self.node_path(Some(value), position);
+            }
+            Event::Alias(_) => {
+                self.node_path(None, position);
+            }
+            Event::MappingStart(_) => {
+                // Complex keys are not positioned, their contents are tracked under an empty
+                // path so the end of the key pops the right collection.
+                let path = self.node_path(None, position).unwrap_or_default();
+                self.stack.push(Collection::Mapping { path, key: None });
+            }
+            Event::SequenceStart(_) => {
+                let path = self.node_path(None, position).unwrap_or_default();
+                self.stack.push(Collection::Sequence { path, index: 0 });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn messages(source: &str) -> Vec<String> {
+        match ProjectCfg::from_yaml(Path::new("."), "ame.yaml", source) {
+            Ok(_) => vec![],
+            Err(diagnostics) => diagnostics.0.iter().map(Diagnostic::to_string).collect(),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_projects() {
+        let source = r#"
+name: myproject
+dataSets:
+  - name: features
+    path: data/features.csv
+    size: 10Gi
+templates:
+  - name: gpu
+    resources:
+      nvidia.com/gpu: "1"
+tasks:
+  - name: prepare
+    dataSets:
+      - features
+      - otherproject.raw
+    resources:
+      cpu: 500m
+      memory: 4Gi
+    triggers:
+      schedule: "0 3 * * *"
+    executor:
+      !poetry
+      pythonVersion: 3.11
+      command: python prepare.py
+  - name: train
+    fromTemplate:
+      name: gpu
+models:
+  - name: mymodel
+    training:
+      task:
+        taskRef:
+          name: train
+"#;
+
+        assert_eq!(messages(source), Vec::<String>::new());
+    }
+
+    #[test]
+    fn rejects_unknown_fields_at_their_position() {
+        let source = r#"name: myproject
+data_sets:
+tasks:
+  - name: train
+    executor:
+      !poetry
+      command: python train.py
+      pyhtonVersion: 3.11
+    secrets:
+      - ame:
+          key: token
+          injectAs: TOKEN
+          env: x
+"#;
+
+        assert_eq!(
+            messages(source),
+            vec![
+                "ame.yaml:2:1: unknown field `data_sets`",
+                "ame.yaml:6:7: tasks[0].executor: missing field `pythonVersion`",
+                "ame.yaml:8:7: unknown field `pyhtonVersion`",
+                "ame.yaml:13:11: unknown field `env`",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_type_errors_with_their_position() {
+        assert_eq!(
+            messages("name: [poetry]\n"),
+            vec!["ame.yaml:1:7: name: invalid type: sequence, expected a string"]
+        );
+    }
+
+    #[test]
+    fn rejects_duplicates_and_dangling_references() {
+        let source = r#"name: myproject
+tasks:
+  - name: train
+    taskRef:
+      name: missing
+  - name: train
+    fromTemplate:
+      name: gpu
+    dataSets:
+      - features
+"#;
+
+        assert_eq!(
+            messages(source),
+            vec![
+                "ame.yaml:5:7: task `missing` does not exist in the project",
+                "ame.yaml:6:5: duplicate task name `train`",
+                "ame.yaml:8:7: template `gpu` does not exist in the project",
+                "ame.yaml:10:9: data set `features` does not exist in the project",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_schedules_and_quantities() {
+        let source = r#"name: myproject
+tasks:
+  - name: train
+    resources:
+      memory: 4 GB
+    triggers:
+      schedule: "* *"
+"#;
+
+        let messages = messages(source);
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("ame.yaml:5:7: `4 GB` is not a valid quantity"));
+        assert!(messages[1].starts_with("ame.yaml:6:5: Invalid cron schedule"));
+    }
+
+    #[test]
+    fn parses_resource_quantities() {
+        for quantity in ["1", "500m", "4Gi", "1.5", "2e3", "+1k"] {
+            assert!(is_quantity(quantity), "{quantity}");
+        }
+
+        for quantity in ["", "Gi", "4 GB", "1.2.3", "4gb", "1e"] {
+            assert!(!is_quantity(quantity), "{quantity}");
+        }
+    }
+}
@@ -262,6 +262,7 @@ async fn ame_trigger_tasks(
 
     project.tasks[0].triggers = Some(ame::grpc::TriggerCfg {
         schedule: Some(format!("{} {} * * *", target.minute(), target.hour())),
+        ..ame::grpc::TriggerCfg::default()
     });
 
     let f = std::fs::File::create(project_file_path)?;
@@ -465,6 +466,7 @@ async fn can_use_data_set_train_validate_and_deploy_model() -> Result<(), Box<dy
             status:
                 Some(DataSetStatus {
                     phase: Some(DataSetPhase::Ready { .. }),
+                    ..
                 }),
             ..
         } = data_set?
@@ -505,6 +507,7 @@ async fn can_use_data_set_train_validate_and_deploy_model() -> Result<(), Box<dy
             if let Some(
                 TaskStatus {
                     phase: Some(Phase::Succeeded(_)),
+                    ..
                 },
                 ..,
             ) = task.status
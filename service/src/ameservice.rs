@@ -2,19 +2,25 @@ use crate::{Error, Result};
 
 use ame::{
     custom_resources::{
-        new_task::{self, Task, TaskBuilder},
-        project::{self, Project},
+        common::{bounded_name, MAX_NAME_LEN},
+        data_set::DataSet,
+        find_project,
+        model_registry::{ModelVersion, ObjectStorageRegistry},
+        new_task::{self, BackfillSpec, Task, TaskBuilder},
+        project::{self, generate_task_name, DeletionPolicy, Project},
         project_source_ctrl::ProjectSrcCtrl,
         secrets::SecretCtrl,
-        task_ctrl::approve_deletion,
+        task_ctrl::{approve_deletion, resolve_data_set_path},
     },
     error::AmeError,
+    rollout::promote_annotation,
+    schedule::parse_time,
 };
 use either::Either;
 use futures_util::StreamExt;
-use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::{api::core::v1::Pod, chrono::Utc};
 use kube::{
-    api::{DeleteParams, ListParams, LogParams, PostParams},
+    api::{DeleteParams, ListParams, LogParams, Patch, PatchParams, PostParams},
     runtime::wait::{await_condition, conditions},
 };
 
@@ -26,14 +32,25 @@ use ame::grpc::{ame_service_server::AmeService, project_file_chunk::Messages};
 
 use ame::grpc::*;
 
-use crate::storage::{AmeFile, ObjectStorage, S3Config, S3StorageDriver};
+use crate::{
+    preview::{
+        is_previewable, parquet_footer_len, parquet_preview_range, preview_parquet, preview_text,
+        RangedFile, DEFAULT_PREVIEW_ROWS, MAX_PARQUET_PREVIEW_BYTES, MAX_PREVIEW_FILES,
+        PARQUET_FOOTER_BYTES, TEXT_PREVIEW_BYTES,
+    },
+    storage::{AmeFile, ObjectStorage, S3Config, S3StorageDriver},
+};
+use serde_json::json;
 use tracing::{debug, instrument};
 
 use ame::ctrl::AmeKubeResourceCtrl;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 use tokio::time::{sleep, Duration};
 use tonic::{Code, Request, Response, Status, Streaming};
 
+/// Size of the chunks data set files are pulled in.
+const PULL_CHUNK_BYTES: u64 = 1024 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct AmeServiceConfig {
     pub s3config: S3Config,
@@ -49,6 +66,9 @@ pub struct Service {
     secret_ctrl: Arc<SecretCtrl>,
     project_src_ctrl: Arc<ProjectSrcCtrl>,
     new_tasks: Arc<Api<new_task::Task>>,
+    data_sets: Arc<Api<DataSet>>,
+    model_registry: Arc<ObjectStorageRegistry>,
+    bucket: String,
 }
 
 #[tonic::async_trait]
@@ -101,7 +121,8 @@ impl AmeService for Service {
         let mut oref = parent_project.controller_owner_ref(&()).unwrap();
         oref.controller = Some(false);
 
-        let mut task_builder = TaskBuilder::from_cfg(task_cfg.clone());
+        let mut task_builder =
+            TaskBuilder::from_cfg(parent_project.spec.cfg.with_task_defaults(task_cfg.clone()));
         let task = task_builder
             .set_project(project_id.name.clone())
             .add_owner_reference(oref)
@@ -409,6 +430,486 @@ impl AmeService for Service {
         Ok(Response::new(Empty {}))
     }
 
+    #[instrument]
+    async fn backfill_task(
+        &self,
+        request: Request<BackfillTaskRequest>,
+    ) -> Result<Response<BackfillTaskResponse>, Status> {
+        let BackfillTaskRequest {
+            project,
+            task,
+            from,
+            to,
+            parallelism,
+        } = request.into_inner();
+
+        let project = find_project((*self.projects).clone(), project.clone(), "".to_string())
+            .await
+            .map_err(|_| Status::not_found(format!("project {project} was not found")))?;
+
+        let Some(task_cfg) = project
+            .spec
+            .cfg
+            .tasks
+            .iter()
+            .find(|t| t.name.as_ref() == Some(&task))
+        else {
+            return Err(Status::not_found(format!(
+                "task {task} was not found in project {}",
+                project.spec.cfg.name
+            )));
+        };
+
+        let Some(ref trigger) = task_cfg.triggers.clone().filter(|t| t.schedule.is_some()) else {
+            return Err(Status::failed_precondition(format!(
+                "task {task} does not have a schedule to backfill"
+            )));
+        };
+
+        let invalid = |e: AmeError| Status::invalid_argument(e.to_string());
+
+        let from = parse_time(&from).map_err(invalid)?;
+        let to = parse_time(&to).map_err(invalid)?;
+        let runs = trigger.runs_between(from, to).map_err(invalid)?;
+
+        let Some(mut oref) = project.controller_owner_ref(&()) else {
+            return Err(AmeError::FailedToCreateOref(project.name_any()).into());
+        };
+        oref.controller = Some(false);
+
+        // Runs are named after the backfill and their index, padded so names sort in the order
+        // the runs are admitted in, and must fit in a label.
+        let backfill = BackfillSpec {
+            id: bounded_name(
+                &format!(
+                    "{}-backfill-{}",
+                    generate_task_name(project.name_any(), task),
+                    Utc::now().timestamp()
+                ),
+                MAX_NAME_LEN - 4,
+            ),
+            parallelism: parallelism.unwrap_or(1).max(1),
+        };
+
+        let mut tasks = vec![];
+
+        for (index, instant) in runs.into_iter().enumerate() {
+            let mut cfg = project.spec.cfg.with_task_defaults(task_cfg.clone());
+            cfg.set_logical_time(instant);
+
+            let mut task_builder = TaskBuilder::from_cfg(cfg);
+            task_builder
+                .set_project(project.spec.cfg.name.clone())
+                .add_owner_reference(oref.clone())
+                .set_name(format!("{}-{index:03}", backfill.id));
+
+            let mut run = task_builder.build();
+            run.spec.backfill = Some(backfill.clone());
+
+            let run = self
+                .new_tasks
+                .create(&PostParams::default(), &run)
+                .await
+                .map_err(AmeError::KubeApi)?;
+
+            tasks.push(run.name_any());
+        }
+
+        Ok(Response::new(BackfillTaskResponse { tasks }))
+    }
+
+    #[instrument]
+    async fn retry_model_validation(
+        &self,
+        request: Request<RetryModelValidationRequest>,
+    ) -> Result<Response<ModelVersionStatus>, Status> {
+        let RetryModelValidationRequest {
+            project,
+            model,
+            version,
+        } = request.into_inner();
+
+        let project = find_project((*self.projects).clone(), project.clone(), "".to_string())
+            .await
+            .map_err(|_| Status::not_found(format!("project {project} was not found")))?;
+
+        let Some(mut model_status) = project
+            .status
+            .as_ref()
+            .and_then(|status| status.models.get(&model))
+            .cloned()
+        else {
+            return Err(Status::not_found(format!(
+                "model {model} in project {} has not been validated",
+                project.spec.cfg.name
+            )));
+        };
+
+        let retried = model_status
+            .retry_validation(&model, version.as_deref(), Utc::now())
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+
+        self.projects
+            .patch_status(
+                &project.name_any(),
+                &PatchParams::default(),
+                &Patch::Merge(
+                    json!({ "status": { "models": { model: { "versions": model_status.versions } } } }),
+                ),
+            )
+            .await
+            .map_err(AmeError::KubeApi)?;
+
+        Ok(Response::new(retried))
+    }
+
+    #[instrument]
+    async fn promote_model_version(
+        &self,
+        request: Request<PromoteModelVersionRequest>,
+    ) -> Result<Response<ModelStatus>, Status> {
+        let PromoteModelVersionRequest {
+            project,
+            model,
+            version,
+        } = request.into_inner();
+
+        let project = self.find_model_project(&project, &model).await?;
+
+        let model_status = self.pin_model_version(&project, &model, version).await?;
+
+        Ok(Response::new(model_status))
+    }
+
+    /// Promotes the rollout candidate of a model, skipping the remaining canary steps or ending
+    /// the wait of a blue/green rollout. The controller promotes the candidate on its next
+    /// reconciliation.
+    #[instrument]
+    async fn promote_model_rollout(
+        &self,
+        request: Request<PromoteModelRolloutRequest>,
+    ) -> Result<Response<ModelStatus>, Status> {
+        let PromoteModelRolloutRequest { project, model } = request.into_inner();
+
+        let project = self.find_model_project(&project, &model).await?;
+
+        let model_status = project
+            .status
+            .as_ref()
+            .and_then(|status| status.models.get(&model))
+            .cloned()
+            .unwrap_or_default();
+
+        if model_status
+            .rollout
+            .as_ref()
+            .and_then(|rollout| rollout.candidate_source.as_ref())
+            .is_none()
+        {
+            return Err(Status::failed_precondition(format!(
+                "model {model} has no rollout candidate to promote"
+            )));
+        }
+
+        self.projects
+            .patch(
+                &project.name_any(),
+                &PatchParams::default(),
+                &Patch::Merge(
+                    json!({ "metadata": { "annotations": { promote_annotation(&model): "true" } } }),
+                ),
+            )
+            .await
+            .map_err(AmeError::KubeApi)?;
+
+        Ok(Response::new(model_status))
+    }
+
+    /// Registers a model version with the object storage model registry, the artifacts are expected
+    /// under `models/<model>/<version>/` in the AME bucket unless a source is given.
+    #[instrument]
+    async fn register_model_version(
+        &self,
+        request: Request<RegisterModelVersionRequest>,
+    ) -> Result<Response<RegisteredModelVersion>, Status> {
+        let RegisterModelVersionRequest {
+            project,
+            model,
+            version,
+            source,
+            run_id,
+        } = request.into_inner();
+
+        if version.is_empty() {
+            return Err(Status::invalid_argument("a model version is required"));
+        }
+
+        self.find_model_project(&project, &model).await?;
+
+        let registered = self
+            .model_registry
+            .register_version(ModelVersion {
+                name: model,
+                version,
+                creation_timestamp: Utc::now().timestamp_millis(),
+                source: source.unwrap_or_default(),
+                run_id: run_id.unwrap_or_default(),
+                ..ModelVersion::default()
+            })
+            .await
+            .map_err(|e| AmeError::ModelRegistryError(e.to_string()))?;
+
+        Ok(Response::new(RegisteredModelVersion {
+            model: registered.name,
+            version: registered.version,
+            source: registered.source,
+        }))
+    }
+
+    #[instrument]
+    async fn rollback_model(
+        &self,
+        request: Request<RollbackModelRequest>,
+    ) -> Result<Response<ModelStatus>, Status> {
+        let RollbackModelRequest { project, model } = request.into_inner();
+
+        let project = self.find_model_project(&project, &model).await?;
+
+        let version = project
+            .status
+            .as_ref()
+            .and_then(|status| status.models.get(&model))
+            .cloned()
+            .unwrap_or_default()
+            .rollback_version(&model)
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+
+        let model_status = self
+            .pin_model_version(&project, &model, Some(version))
+            .await?;
+
+        Ok(Response::new(model_status))
+    }
+
+    #[instrument]
+    async fn get_project_status(
+        &self,
+        request: Request<ProjectId>,
+    ) -> Result<Response<ProjectStatusReport>, Status> {
+        let ProjectId { name } = request.into_inner();
+
+        let project = find_project((*self.projects).clone(), name.clone(), "".to_string())
+            .await
+            .map_err(|_| Status::not_found(format!("project {name} was not found")))?;
+
+        let data_sets = self
+            .data_sets
+            .list(&ListParams::default())
+            .await
+            .map_err(AmeError::KubeApi)?
+            .into_iter()
+            .filter(|ds| ds.info().map_or(false, |info| info.project == name))
+            .map(|ds| ObjectConditions {
+                name: ds.spec.cfg.name.clone(),
+                conditions: ds.status.map(|s| s.conditions).unwrap_or_default(),
+            })
+            .collect();
+
+        let tasks = self
+            .new_tasks
+            .list(&ListParams::default())
+            .await
+            .map_err(AmeError::KubeApi)?
+            .into_iter()
+            .filter(|task| task.spec.project.as_ref() == Some(&name))
+            .map(|task| ObjectConditions {
+                name: task.name_any(),
+                conditions: task.status.map(|s| s.conditions).unwrap_or_default(),
+            })
+            .collect();
+
+        Ok(Response::new(ProjectStatusReport {
+            project: name,
+            status: project.status,
+            data_sets,
+            tasks,
+        }))
+    }
+
+    #[instrument]
+    async fn get_data_set_graph(
+        &self,
+        request: Request<DataSetGraphRequest>,
+    ) -> Result<Response<DataSetGraph>, Status> {
+        let DataSetGraphRequest { project } = request.into_inner();
+
+        let projects: Vec<ProjectCfg> = self
+            .projects
+            .list(&ListParams::default())
+            .await
+            .map_err(AmeError::KubeApi)?
+            .into_iter()
+            .map(|p| p.spec.cfg)
+            .collect();
+
+        let Some(root) = projects.iter().find(|p| p.name == project) else {
+            return Err(Status::not_found(format!(
+                "project {project} was not found"
+            )));
+        };
+
+        Ok(Response::new(DataSetGraph::from_projects(root, &projects)?))
+    }
+
+    #[instrument]
+    async fn list_data_sets(
+        &self,
+        request: Request<ListDataSetsRequest>,
+    ) -> Result<Response<ListDataSetsResponse>, Status> {
+        let ListDataSetsRequest { project } = request.into_inner();
+
+        let data_sets = self
+            .data_sets
+            .list(&ListParams::default())
+            .await
+            .map_err(AmeError::KubeApi)?
+            .iter()
+            .map(DataSet::info)
+            .collect::<Result<Vec<DataSetInfo>, AmeError>>()?
+            .into_iter()
+            .filter(|info| project.as_ref().map_or(true, |p| &info.project == p))
+            .collect();
+
+        Ok(Response::new(ListDataSetsResponse { data_sets }))
+    }
+
+    #[instrument]
+    async fn get_data_set(
+        &self,
+        request: Request<DataSetId>,
+    ) -> Result<Response<DataSetInfo>, Status> {
+        let data_set = self.find_data_set(request.get_ref()).await?;
+
+        let mut info = data_set.info()?;
+        info.files = self
+            .data_set_files(&data_set)
+            .await?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+
+        Ok(Response::new(info))
+    }
+
+    #[instrument]
+    async fn preview_data_set(
+        &self,
+        request: Request<PreviewDataSetRequest>,
+    ) -> Result<Response<DataSetPreview>, Status> {
+        let PreviewDataSetRequest { id, rows, file } = request.into_inner();
+
+        let Some(id) = id else {
+            return Err(Status::invalid_argument("missing data set identifier"));
+        };
+
+        let data_set = self.find_data_set(&id).await?;
+        let rows = rows.map_or(DEFAULT_PREVIEW_ROWS, |r| r as usize);
+        let files = self.data_set_files(&data_set).await?;
+
+        if let Some(ref file) = file {
+            if !files.iter().any(|(path, _)| path == file) {
+                return Err(Status::not_found(format!(
+                    "file {file} was not found in data set {}",
+                    id.name
+                )));
+            }
+        }
+
+        let selected = files
+            .iter()
+            .filter(|(path, _)| match file {
+                Some(ref file) => path == file,
+                None => is_previewable(path),
+            })
+            .take(MAX_PREVIEW_FILES);
+
+        let mut previews = vec![];
+
+        for (path, key) in selected {
+            previews.push(self.preview_data_set_file(path, key, rows).await.map_err(
+                |e| match e {
+                    Error::S3Error(_) => Status::from_error(Box::new(e)),
+                    e => Status::invalid_argument(e.to_string()),
+                },
+            )?);
+        }
+
+        Ok(Response::new(DataSetPreview {
+            files: files.into_iter().map(|(path, _)| path).collect(),
+            previews,
+        }))
+    }
+
+    type PullDataSetStream = ReceiverStream<Result<DataSetFileChunk, Status>>;
+
+    #[instrument]
+    async fn pull_data_set(
+        &self,
+        request: Request<DataSetId>,
+    ) -> Result<Response<Self::PullDataSetStream>, Status> {
+        let data_set = self.find_data_set(request.get_ref()).await?;
+        let files = self.data_set_files(&data_set).await?;
+
+        let (chunk_sender, chunk_receiver) = mpsc::channel(1);
+        let storage = self.storage.clone();
+
+        let _handle: JoinHandle<Result<()>> = tokio::spawn(async move {
+            for (path, key) in files {
+                let size = match storage.get_data_set_file_size(&key).await {
+                    Ok(size) => size,
+                    Err(e) => {
+                        let _ = chunk_sender
+                            .send(Err(Status::from_error(Box::new(e))))
+                            .await;
+                        return Ok(());
+                    }
+                };
+
+                // Empty files are sent as a single empty chunk so they are still created.
+                for offset in (0..size.max(1)).step_by(PULL_CHUNK_BYTES as usize) {
+                    let end = (offset + PULL_CHUNK_BYTES).min(size);
+
+                    let chunk = storage
+                        .get_data_set_file_range(&key, offset, end)
+                        .await
+                        .map(|contents| DataSetFileChunk {
+                            path: path.clone(),
+                            contents,
+                            offset,
+                        })
+                        .map_err(|e| Status::from_error(Box::new(e)));
+
+                    let failed = chunk.is_err();
+
+                    chunk_sender
+                        .send(chunk)
+                        .await
+                        .or(Err(Error::TokioSendError(format!(
+                            "failed to send data set file: {path}"
+                        ))))?;
+
+                    if failed {
+                        return Ok(());
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        Ok(Response::new(ReceiverStream::new(chunk_receiver)))
+    }
+
     #[instrument]
     async fn create_project(
         &self,
@@ -420,9 +921,18 @@ impl AmeService for Service {
             return Err(AmeError::MissingProjectcfg.into());
         };
 
+        cfg.validate_triggers()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        if let Some(ref policy) = request.deletion_policy {
+            DeletionPolicy::from_str(policy)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        }
+
         let mut project = Project::from_cfg(cfg);
 
         project.spec.enable_triggers = Some(request.enable_triggers.unwrap_or(false));
+        project.spec.deletion_policy = request.deletion_policy;
 
         let name = self
             .projects
@@ -621,6 +1131,14 @@ impl Service {
         let pods = Api::<Pod>::namespaced(client.clone(), target_namespace);
         let projects = Api::<Project>::namespaced(client.clone(), target_namespace);
 
+        let model_registry = ObjectStorageRegistry::new(
+            &cfg.bucket,
+            cfg.s3config.endpoint.clone(),
+            cfg.s3config.region.clone(),
+            &cfg.s3config.access_id,
+            &cfg.s3config.secret,
+        )?;
+
         let task_service = Service {
             tasks: Arc::new(tasks),
             pods: Arc::new(pods),
@@ -631,12 +1149,168 @@ impl Service {
                 cfg.s3config,
             )?),
             secret_ctrl: Arc::new(SecretCtrl::new(client.clone(), target_namespace)),
-            project_src_ctrl: Arc::new(ProjectSrcCtrl::new(client, target_namespace)),
+            project_src_ctrl: Arc::new(ProjectSrcCtrl::new(client.clone(), target_namespace)),
+            data_sets: Arc::new(Api::<DataSet>::namespaced(client, target_namespace)),
+            model_registry: Arc::new(model_registry),
+            bucket: cfg.bucket,
         };
 
         Ok(task_service)
     }
 
+    async fn find_model_project(&self, project: &str, model: &str) -> Result<Project, Status> {
+        let project = find_project(
+            (*self.projects).clone(),
+            project.to_string(),
+            "".to_string(),
+        )
+        .await
+        .map_err(|_| Status::not_found(format!("project {project} was not found")))?;
+
+        if !project.spec.cfg.models.iter().any(|m| m.name == model) {
+            return Err(Status::not_found(format!(
+                "model {model} was not found in project {}",
+                project.spec.cfg.name
+            )));
+        }
+
+        Ok(project)
+    }
+
+    /// Pins the deployed version of a model, `None` removes the pin so the latest validated
+    /// version is deployed again.
+    async fn pin_model_version(
+        &self,
+        project: &Project,
+        model: &str,
+        version: Option<String>,
+    ) -> Result<ModelStatus, Status> {
+        let project = self
+            .projects
+            .patch_status(
+                &project.name_any(),
+                &PatchParams::default(),
+                &Patch::Merge(
+                    json!({ "status": { "models": { model: { "pinnedVersion": version } } } }),
+                ),
+            )
+            .await
+            .map_err(AmeError::KubeApi)?;
+
+        Ok(project
+            .status
+            .and_then(|status| status.models.get(model).cloned())
+            .unwrap_or_default())
+    }
+
+    async fn find_data_set(&self, id: &DataSetId) -> Result<DataSet, Status> {
+        let data_sets = self
+            .data_sets
+            .list(&ListParams::default())
+            .await
+            .map_err(AmeError::KubeApi)?;
+
+        for data_set in data_sets {
+            let info = data_set.info()?;
+            if info.name == id.name && info.project == id.project {
+                return Ok(data_set);
+            }
+        }
+
+        Err(Status::not_found(format!(
+            "data set {} was not found in project {}",
+            id.name, id.project
+        )))
+    }
+
+    /// Previews a data set file, only the start of CSV and JSONL files and the metadata and first
+    /// row groups of Parquet files are read from object storage.
+    async fn preview_data_set_file(
+        &self,
+        path: &str,
+        key: &str,
+        rows: usize,
+    ) -> Result<FilePreview> {
+        let size = self.storage.get_data_set_file_size(key).await?;
+
+        if !path.ends_with(".parquet") {
+            let end = size.min(TEXT_PREVIEW_BYTES);
+            let contents = self.storage.get_data_set_file_range(key, 0, end).await?;
+
+            return preview_text(path, contents, end < size, rows);
+        }
+
+        let mut file = RangedFile::new(size);
+
+        let tail_start = size.saturating_sub(PARQUET_FOOTER_BYTES);
+        let tail = self
+            .storage
+            .get_data_set_file_range(key, tail_start, size)
+            .await?;
+        let footer_len = parquet_footer_len(&tail)?;
+
+        file.add(tail_start, tail);
+
+        if footer_len > MAX_PARQUET_PREVIEW_BYTES {
+            return Err(Error::PreviewTooLarge(format!(
+                "the metadata of {path} is {footer_len} bytes"
+            )));
+        }
+
+        if footer_len > size - tail_start {
+            let start = size.saturating_sub(footer_len);
+            let metadata = self
+                .storage
+                .get_data_set_file_range(key, start, size)
+                .await?;
+
+            file.add(start, metadata);
+        }
+
+        let (row_groups, start, end) = parquet_preview_range(&file, rows)?;
+
+        if end - start > MAX_PARQUET_PREVIEW_BYTES {
+            return Err(Error::PreviewTooLarge(format!(
+                "the first row group of {path} is {} bytes",
+                end - start
+            )));
+        }
+
+        let contents = self
+            .storage
+            .get_data_set_file_range(key, start, end)
+            .await?;
+        file.add(start, contents);
+
+        preview_parquet(path, file, row_groups, rows)
+    }
+
+    /// Lists the files in a data set as pairs of paths relative to the data set and
+    /// object storage keys.
+    async fn data_set_files(&self, data_set: &DataSet) -> Result<Vec<(String, String)>, Status> {
+        let path = resolve_data_set_path(data_set.clone())?;
+        let prefix = path
+            .strip_prefix(&format!("{}/", self.bucket))
+            .unwrap_or(&path)
+            .to_string();
+
+        Ok(self
+            .storage
+            .list_data_set_files(&prefix)
+            .await
+            .map_err(|e| Status::from_error(Box::new(e)))?
+            .into_iter()
+            .map(|key| {
+                let path = key
+                    .strip_prefix(&prefix)
+                    .unwrap_or(&key)
+                    .trim_start_matches('/')
+                    .to_string();
+                (path, key)
+            })
+            .collect())
+    }
+
     pub async fn prepare_environment(&self) -> Result<()> {
         match self.storage.ensure_storage_is_ready().await {
             Ok(_) => Ok(()),
@@ -1,4 +1,5 @@
 pub mod ameservice;
+pub mod preview;
 pub mod storage;
 
 use ame::custom_resources::new_task::Task;
@@ -60,6 +61,18 @@ pub enum Error {
 
     #[error("Project config is missing from request")]
     MissingProjectCfg,
+
+    #[error("Previews are only supported for CSV, JSONL and Parquet files, got: {0}")]
+    UnsupportedPreviewFormat(String),
+
+    #[error("File is too large to preview: {0}")]
+    PreviewTooLarge(String),
+
+    #[error("Failed to read Parquet file: {0}")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+
+    #[error("Failed to set up the model registry: {0}")]
+    ModelRegistryError(#[from] ame::custom_resources::Error),
 }
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
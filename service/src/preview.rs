@@ -0,0 +1,256 @@
+use ame::grpc::FilePreview;
+use bytes::{Buf, Bytes};
+use parquet::{
+    errors::ParquetError,
+    file::{
+        footer::{decode_footer, parse_metadata},
+        reader::{ChunkReader, FileReader, Length, SerializedFileReader},
+        serialized_reader::ReadOptionsBuilder,
+        FOOTER_SIZE,
+    },
+};
+
+use crate::{Error, Result};
+
+/// Number of rows previewed when the request does not specify it.
+pub const DEFAULT_PREVIEW_ROWS: usize = 10;
+
+/// Number of files previewed when the request does not select a file.
+pub const MAX_PREVIEW_FILES: usize = 5;
+
+/// Number of bytes read from the start of CSV and JSONL files.
+pub const TEXT_PREVIEW_BYTES: u64 = 1024 * 1024;
+
+/// Number of bytes read from the end of Parquet files, enough for the metadata of most files.
+pub const PARQUET_FOOTER_BYTES: u64 = 64 * 1024;
+
+/// Upper limit on the bytes read from a Parquet file, for its metadata or its previewed rows.
+pub const MAX_PARQUET_PREVIEW_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Returns whether a preview can be generated for the file at `path`.
+pub fn is_previewable(path: &str) -> bool {
+    [".csv", ".jsonl", ".parquet"]
+        .iter()
+        .any(|ext| path.ends_with(ext))
+}
+
+/// Extracts the first `rows` rows of a CSV or JSONL file from the first bytes of the file.
+///
+/// CSV previews include the header in addition to the requested rows. If `truncated` is set the
+/// contents end in the middle of the file and the last, possibly partial, line is dropped.
+pub fn preview_text(
+    path: &str,
+    mut contents: Vec<u8>,
+    truncated: bool,
+    rows: usize,
+) -> Result<FilePreview> {
+    if truncated {
+        let end = contents.iter().rposition(|b| *b == b'\n').unwrap_or(0);
+        contents.truncate(end);
+    }
+
+    let rows = if path.ends_with(".csv") {
+        text_lines(contents, rows + 1)?
+    } else if path.ends_with(".jsonl") {
+        text_lines(contents, rows)?
+    } else {
+        return Err(Error::UnsupportedPreviewFormat(path.to_string()));
+    };
+
+    Ok(FilePreview {
+        path: path.to_string(),
+        rows,
+    })
+}
+
+fn text_lines(contents: Vec<u8>, rows: usize) -> Result<Vec<String>> {
+    let contents = String::from_utf8(contents)?;
+
+    Ok(contents
+        .lines()
+        .filter(|l| !l.is_empty())
+        .take(rows)
+        .map(String::from)
+        .collect())
+}
+
+/// Returns the length of the metadata and footer of a Parquet file from the last bytes of it.
+pub fn parquet_footer_len(tail: &[u8]) -> Result<u64> {
+    let footer: &[u8; FOOTER_SIZE] = tail
+        .get(tail.len().saturating_sub(FOOTER_SIZE)..)
+        .and_then(|footer| footer.try_into().ok())
+        .ok_or(ParquetError::General(
+            "Invalid Parquet file. Size is smaller than footer".to_string(),
+        ))?;
+
+    Ok((decode_footer(footer)? + FOOTER_SIZE) as u64)
+}
+
+/// Returns the number of row groups needed to preview `rows` rows of a Parquet file, with the
+/// start and end of the bytes they are stored in. Only the metadata of `file` is read.
+pub fn parquet_preview_range(file: &RangedFile, rows: usize) -> Result<(usize, u64, u64)> {
+    let metadata = parse_metadata(file)?;
+
+    let mut row_groups = 0;
+    let mut row_count = 0;
+    let mut range: Option<(u64, u64)> = None;
+
+    for row_group in metadata.row_groups() {
+        if row_groups > 0 && row_count >= rows {
+            break;
+        }
+
+        for column in row_group.columns() {
+            let (start, len) = column.byte_range();
+            let (first, last) = range.unwrap_or((start, start + len));
+
+            range = Some((first.min(start), last.max(start + len)));
+        }
+
+        row_groups += 1;
+        row_count += row_group.num_rows().max(0) as usize;
+    }
+
+    let (start, end) = range.unwrap_or_default();
+
+    Ok((row_groups, start, end))
+}
+
+/// Extracts the first `rows` rows of a Parquet file, rendered as JSON objects, from its first
+/// `row_groups` row groups.
+pub fn preview_parquet(
+    path: &str,
+    file: RangedFile,
+    row_groups: usize,
+    rows: usize,
+) -> Result<FilePreview> {
+    let options = ReadOptionsBuilder::new()
+        .with_predicate(Box::new(move |_, i| i < row_groups))
+        .build();
+
+    let reader = SerializedFileReader::new_with_options(file, options)?;
+
+    let rows = reader
+        .get_row_iter(None)?
+        .take(rows)
+        .map(|row| Ok(row?.to_json_value().to_string()))
+        .collect::<Result<Vec<String>>>()?;
+
+    Ok(FilePreview {
+        path: path.to_string(),
+        rows,
+    })
+}
+
+/// The parts of a file which have been read from object storage, so Parquet files can be read
+/// without downloading them completely.
+#[derive(Debug, Clone, Default)]
+pub struct RangedFile {
+    len: u64,
+    ranges: Vec<(u64, Bytes)>,
+}
+
+impl RangedFile {
+    pub fn new(len: u64) -> Self {
+        RangedFile {
+            len,
+            ranges: vec![],
+        }
+    }
+
+    /// Adds the bytes read from `start`.
+    pub fn add(&mut self, start: u64, contents: Vec<u8>) {
+        self.ranges.push((start, Bytes::from(contents)));
+    }
+
+    fn bytes_from(&self, start: u64) -> parquet::errors::Result<Bytes> {
+        self.ranges
+            .iter()
+            .find(|(offset, bytes)| *offset <= start && start < offset + bytes.len() as u64)
+            .map(|(offset, bytes)| bytes.slice((start - offset) as usize..))
+            .ok_or(ParquetError::General(format!(
+                "byte {start} of the file has not been read"
+            )))
+    }
+}
+
+impl Length for RangedFile {
+    fn len(&self) -> u64 {
+        self.len
+    }
+}
+
+impl ChunkReader for RangedFile {
+    type T = bytes::buf::Reader<Bytes>;
+
+    fn get_read(&self, start: u64) -> parquet::errors::Result<Self::T> {
+        Ok(self.bytes_from(start)?.reader())
+    }
+
+    fn get_bytes(&self, start: u64, length: usize) -> parquet::errors::Result<Bytes> {
+        let bytes = self.bytes_from(start)?;
+
+        if bytes.len() < length {
+            return Err(ParquetError::General(format!(
+                "bytes {start} to {} of the file have not been read",
+                start + length as u64
+            )));
+        }
+
+        Ok(bytes.slice(..length))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_preview_includes_header() -> Result<()> {
+        let contents = "a,b\n1,2\n3,4\n5,6\n".as_bytes().to_vec();
+
+        let preview = preview_text("data/train.csv", contents, false, 2)?;
+
+        assert_eq!(preview.rows, vec!["a,b", "1,2", "3,4"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn jsonl_preview_skips_blank_lines() -> Result<()> {
+        let contents = "{\"a\":1}\n\n{\"a\":2}\n{\"a\":3}\n".as_bytes().to_vec();
+
+        let preview = preview_text("events.jsonl", contents, false, 2)?;
+
+        assert_eq!(preview.rows, vec!["{\"a\":1}", "{\"a\":2}"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_previews_drop_the_partial_line() -> Result<()> {
+        let contents = "a,b\n1,2\n3,4".as_bytes().to_vec();
+
+        let preview = preview_text("data/train.csv", contents, true, 10)?;
+
+        assert_eq!(preview.rows, vec!["a,b", "1,2"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn ranged_files_only_serve_read_bytes() {
+        let mut file = RangedFile::new(100);
+        file.add(90, vec![1; 10]);
+
+        assert_eq!(file.get_bytes(95, 5).unwrap().as_ref(), &[1; 5]);
+        assert!(file.get_bytes(95, 10).is_err());
+        assert!(file.get_read(10).is_err());
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() {
+        assert!(!is_previewable("model.bin"));
+        assert!(preview_text("model.bin", vec![1, 2, 3], false, 2).is_err());
+    }
+}
@@ -14,6 +14,10 @@ pub struct AmeFile {
 pub trait ObjectStorageDriver {
     async fn write(&self, prefix: &str, file: AmeFile) -> Result<()>;
     async fn read(&self, key: String) -> Result<AmeFile>;
+
+    /// Reads the bytes from `start` up to, but not including, `end`.
+    async fn read_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>>;
+    async fn size(&self, key: &str) -> Result<u64>;
     async fn list(&self, key: &str) -> Result<Vec<String>>;
     async fn delete(&self, key: &str) -> Result<()>;
     async fn init_storage(&self) -> Result<()>;
@@ -132,6 +136,29 @@ impl<T: ObjectStorageDriver> ObjectStorage<T> {
             .await
     }
 
+    /// Lists the keys of every file stored under a data set's path.
+    pub async fn list_data_set_files(&self, data_set_path: &str) -> Result<Vec<String>> {
+        self.driver.list(data_set_path).await
+    }
+
+    pub async fn get_data_set_file(&self, key: &str) -> Result<AmeFile> {
+        self.driver.read(key.to_string()).await
+    }
+
+    /// Reads the bytes of a data set file from `start` up to, but not including, `end`.
+    pub async fn get_data_set_file_range(
+        &self,
+        key: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<u8>> {
+        self.driver.read_range(key, start, end).await
+    }
+
+    pub async fn get_data_set_file_size(&self, key: &str) -> Result<u64> {
+        self.driver.size(key).await
+    }
+
     pub async fn health_check(&self) -> Result<()> {
         self.driver.list("").await.map(|_| ())
     }
@@ -203,6 +230,29 @@ impl ObjectStorageDriver for S3StorageDriver {
         })
     }
 
+    async fn read_range(&self, key: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+        if start >= end {
+            return Ok(vec![]);
+        }
+
+        // S3 ranges include their last byte.
+        let obj = self
+            .bucket
+            .get_object_range(key, start, Some(end - 1).filter(|last| *last > start))
+            .await?;
+
+        let mut contents = obj.bytes().to_vec();
+        contents.truncate((end - start) as usize);
+
+        Ok(contents)
+    }
+
+    async fn size(&self, key: &str) -> Result<u64> {
+        let (head, _) = self.bucket.head_object(key).await?;
+
+        Ok(head.content_length.unwrap_or_default().max(0) as u64)
+    }
+
     async fn list(&self, key: &str) -> Result<Vec<String>> {
         Ok(self
             .bucket